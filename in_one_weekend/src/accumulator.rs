@@ -0,0 +1,308 @@
+use std::io::{self, Read, Write};
+
+use crate::color::{self, Color};
+use crate::filter::Filter;
+use crate::image::Image;
+use crate::tile::Tile;
+
+// Running per-pixel sums of radiance samples.
+// Samples are splatted through the reconstruction filter into every pixel they overlap, and each
+// pixel keeps the filter-weighted color sum and the sum of weights it received.
+// Besides that we keep a running mean and M2 of the luminance of the pixel's own samples
+// (Welford's algorithm) so the variance (noise) of each pixel's estimate can be computed at
+// any point during a render.
+// Light tracing (see bdpt.rs) adds samples that land in arbitrary pixels, which are summed apart:
+// they don't belong to the pixel's own samples, every sample of the image contributes to them.
+pub struct Accumulator {
+    width: u32,
+    height: u32,
+    filter: Box<dyn Filter>,
+    weighted_sum: Vec<Color>,
+    weight_sum: Vec<f64>,
+    lum_mean: Vec<f64>,
+    lum_m2: Vec<f64>,
+    samples: Vec<u32>,
+    light_sum: Vec<Color>,
+}
+
+impl Accumulator {
+    pub fn new(width: u32, height: u32, filter: Box<dyn Filter>) -> Self {
+        let len = width as usize * height as usize;
+        Accumulator {
+            width,
+            height,
+            filter,
+            weighted_sum: vec![Color::default(); len],
+            weight_sum: vec![0.0; len],
+            lum_mean: vec![0.0; len],
+            lum_m2: vec![0.0; len],
+            samples: vec![0; len],
+            light_sum: vec![Color::default(); len],
+        }
+    }
+}
+
+//getters
+impl Accumulator {
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+    pub fn samples(&self, i: u32, j: u32) -> u32 {
+        self.samples[self.index(i, j)]
+    }
+}
+
+//utils
+impl Accumulator {
+    // Adds a sample taken for pixel i, j at `offset` (in [-0.5, 0.5]^2) from the pixel center.
+    pub fn add_sample(&mut self, i: u32, j: u32, offset: (f64, f64), sample: Color) {
+        self.splat(i as f64 + 0.5 + offset.0, j as f64 + 0.5 + offset.1, sample);
+
+        let idx = self.index(i, j);
+        let y = color::luminance(&sample);
+        self.samples[idx] += 1;
+
+        let delta = y - self.lum_mean[idx];
+        self.lum_mean[idx] += delta / (self.samples[idx] as f64);
+        self.lum_m2[idx] += delta * (y - self.lum_mean[idx]);
+    }
+
+    // Adds a sample of a path traced from a light that reached the camera at continuous image
+    // position x, y. It goes to the pixel it landed in, unfiltered.
+    pub fn add_light_sample(&mut self, x: f64, y: f64, sample: Color) {
+        let i = (x.max(0.0) as u32).min(self.width - 1);
+        let j = (y.max(0.0) as u32).min(self.height - 1);
+        let idx = self.index(i, j);
+        self.light_sum[idx] += sample;
+    }
+
+    // Filtered estimate of pixel i, j: the weighted average of the samples splatted into it.
+    pub fn mean(&self, i: u32, j: u32) -> Color {
+        let idx = self.index(i, j);
+        let weight = self.weight_sum[idx];
+        if weight.abs() < 1e-12 {
+            return Color::default();
+        }
+        self.weighted_sum[idx] / weight
+    }
+
+    // Sample variance of the pixel's luminance (0 until there are at least two samples).
+    pub fn variance(&self, i: u32, j: u32) -> f64 {
+        let idx = self.index(i, j);
+        let n = self.samples[idx] as f64;
+        if n < 2.0 {
+            return 0.0;
+        }
+        self.lum_m2[idx] / (n - 1.0)
+    }
+
+    // Standard error of the pixel's mean, relative to its brightness.
+    // The small offset keeps almost black pixels from dominating the estimate.
+    pub fn relative_error(&self, i: u32, j: u32) -> f64 {
+        let n = self.samples(i, j) as f64;
+        if n < 2.0 {
+            return f64::INFINITY;
+        }
+        let std_error = (self.variance(i, j) / n).sqrt();
+        std_error / (self.lum_mean[self.index(i, j)].abs() + 0.01)
+    }
+
+    // Average relative error over the whole image, used as a global noise level.
+    pub fn noise(&self) -> f64 {
+        let mut total = 0.0;
+        for j in 0..self.height {
+            for i in 0..self.width {
+                total += self.relative_error(i, j);
+            }
+        }
+        total / ((self.width * self.height) as f64)
+    }
+
+    // The current estimate of every pixel.
+    // Each sample of the image traced one light path, so the light samples are averaged over all of
+    // them; their estimates are for the whole image, scaled by the pixel count to be per pixel.
+    pub fn to_image(&self) -> Image {
+        let total_samples: f64 = self.samples.iter().map(|&n| n as f64).sum();
+        let light_scale = if total_samples > 0.0 {
+            (self.width * self.height) as f64 / total_samples
+        } else {
+            0.0
+        };
+        let mut image = Image::new(self.width, self.height);
+        for j in 0..self.height {
+            for i in 0..self.width {
+                let light = light_scale * self.light_sum[self.index(i, j)];
+                image.set_pixel(i, j, &(self.mean(i, j) + light));
+            }
+        }
+        image
+    }
+
+    // The number of samples each pixel received as colors going from blue (no samples)
+    // through green to red (`max_samples`).
+    pub fn heatmap(&self, max_samples: u32) -> Image {
+        let mut image = Image::new(self.width, self.height);
+        for j in 0..self.height {
+            for i in 0..self.width {
+                let t = (self.samples(i, j) as f64 / max_samples.max(1) as f64).clamp(0.0, 1.0);
+                let ramp = if t < 0.5 {
+                    Color::new(0.0, 2.0 * t, 1.0 - 2.0 * t)
+                } else {
+                    Color::new(2.0 * t - 1.0, 2.0 - 2.0 * t, 0.0)
+                };
+                image.set_pixel(i, j, &ramp);
+            }
+        }
+        image
+    }
+
+    // Adds `sample`, taken at continuous image position x, y, to every pixel within the filter radius.
+    fn splat(&mut self, x: f64, y: f64, sample: Color) {
+        let radius = self.filter.radius();
+        // Pixel centers sit at half-integer positions.
+        let i0 = (x - 0.5 - radius).ceil().max(0.0) as u32;
+        let j0 = (y - 0.5 - radius).ceil().max(0.0) as u32;
+        let i1 = ((x - 0.5 + radius).floor().max(-1.0) as i64).min(self.width as i64 - 1);
+        let j1 = ((y - 0.5 + radius).floor().max(-1.0) as i64).min(self.height as i64 - 1);
+
+        for pj in j0 as i64..=j1 {
+            for pi in i0 as i64..=i1 {
+                let weight = self.filter.evaluate(x - (pi as f64 + 0.5), y - (pj as f64 + 0.5));
+                if weight == 0.0 {
+                    continue;
+                }
+                let idx = self.index(pi as u32, pj as u32);
+                self.weighted_sum[idx] += weight * sample;
+                self.weight_sum[idx] += weight;
+            }
+        }
+    }
+
+    // Writes the raw sums (but not the size or filter) in little endian, for checkpoints.
+    pub fn write_state(&self, out: &mut dyn Write) -> io::Result<()> {
+        for c in &self.weighted_sum {
+            for v in c.vector {
+                out.write_all(&v.to_le_bytes())?;
+            }
+        }
+        for values in [&self.weight_sum, &self.lum_mean, &self.lum_m2] {
+            for v in values {
+                out.write_all(&v.to_le_bytes())?;
+            }
+        }
+        for n in &self.samples {
+            out.write_all(&n.to_le_bytes())?;
+        }
+        for c in &self.light_sum {
+            for v in c.vector {
+                out.write_all(&v.to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    // Reads back what write_state wrote for an accumulator of the same size.
+    pub fn read_state(&mut self, input: &mut dyn Read) -> io::Result<()> {
+        let mut f64_bytes = [0u8; 8];
+        let mut read_f64 = |input: &mut dyn Read| -> io::Result<f64> {
+            input.read_exact(&mut f64_bytes)?;
+            Ok(f64::from_le_bytes(f64_bytes))
+        };
+        for c in &mut self.weighted_sum {
+            for v in &mut c.vector {
+                *v = read_f64(input)?;
+            }
+        }
+        for values in [&mut self.weight_sum, &mut self.lum_mean, &mut self.lum_m2] {
+            for v in values.iter_mut() {
+                *v = read_f64(input)?;
+            }
+        }
+        let mut u32_bytes = [0u8; 4];
+        for n in &mut self.samples {
+            input.read_exact(&mut u32_bytes)?;
+            *n = u32::from_le_bytes(u32_bytes);
+        }
+        for c in &mut self.light_sum {
+            for v in &mut c.vector {
+                *v = read_f64(input)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Pixels that samples taken inside `tile` can splat into: the tile plus the filter radius.
+    pub fn splat_bounds(&self, tile: &Tile) -> Tile {
+        let pad = (self.filter.radius() + 0.5).ceil() as u32;
+        tile.expand(pad, self.width, self.height)
+    }
+
+    // Writes what sampling `tile` added: the sums of every pixel in its splat bounds, and
+    // the statistics of the tile's own pixels (which only the tile's samples touch).
+    // Light samples can land anywhere, tiles don't carry them.
+    pub fn write_tile_state(&self, tile: &Tile, out: &mut dyn Write) -> io::Result<()> {
+        for (i, j) in self.splat_bounds(tile).pixels() {
+            let idx = self.index(i, j);
+            for v in self.weighted_sum[idx].vector {
+                out.write_all(&v.to_le_bytes())?;
+            }
+            out.write_all(&self.weight_sum[idx].to_le_bytes())?;
+        }
+        for (i, j) in tile.pixels() {
+            let idx = self.index(i, j);
+            out.write_all(&self.lum_mean[idx].to_le_bytes())?;
+            out.write_all(&self.lum_m2[idx].to_le_bytes())?;
+            out.write_all(&self.samples[idx].to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    // Number of bytes write_tile_state writes for `tile`.
+    pub fn tile_state_size(&self, tile: &Tile) -> usize {
+        let bounds = self.splat_bounds(tile);
+        (bounds.width() * bounds.height()) as usize * 32 + (tile.width() * tile.height()) as usize * 20
+    }
+
+    // Adds what write_tile_state wrote (by an accumulator of the same size and filter) to this one.
+    pub fn merge_tile_state(&mut self, tile: &Tile, input: &mut dyn Read) -> io::Result<()> {
+        let mut f64_bytes = [0u8; 8];
+        let mut read_f64 = |input: &mut dyn Read| -> io::Result<f64> {
+            input.read_exact(&mut f64_bytes)?;
+            Ok(f64::from_le_bytes(f64_bytes))
+        };
+        for (i, j) in self.splat_bounds(tile).pixels() {
+            let idx = self.index(i, j);
+            let sum = Color::new(read_f64(input)?, read_f64(input)?, read_f64(input)?);
+            self.weighted_sum[idx] += sum;
+            self.weight_sum[idx] += read_f64(input)?;
+        }
+        let mut u32_bytes = [0u8; 4];
+        for (i, j) in tile.pixels() {
+            let idx = self.index(i, j);
+            self.lum_mean[idx] = read_f64(input)?;
+            self.lum_m2[idx] = read_f64(input)?;
+            input.read_exact(&mut u32_bytes)?;
+            self.samples[idx] = u32::from_le_bytes(u32_bytes);
+        }
+        Ok(())
+    }
+
+    // Forgets everything sampling `tile` added, so the accumulator can be reused for the next tile.
+    pub fn clear_tile(&mut self, tile: &Tile) {
+        for (i, j) in self.splat_bounds(tile).pixels() {
+            let idx = self.index(i, j);
+            self.weighted_sum[idx] = Color::default();
+            self.weight_sum[idx] = 0.0;
+            self.lum_mean[idx] = 0.0;
+            self.lum_m2[idx] = 0.0;
+            self.samples[idx] = 0;
+        }
+    }
+
+    fn index(&self, i: u32, j: u32) -> usize {
+        (j * self.width + i) as usize
+    }
+}
@@ -0,0 +1,50 @@
+use std::path::PathBuf;
+
+use crate::error::{Error, Result};
+
+// Settings for Camera::render_adaptive.
+// Every pixel gets `min_samples`; after that only pixels whose relative error is still above
+// `error_threshold` keep receiving batches of `samples_per_pass`, up to `max_samples`.
+pub struct AdaptiveSettings {
+    pub min_samples: u32,
+    pub max_samples: u32,
+    pub samples_per_pass: u32,
+    pub error_threshold: f64, // Relative standard error of a pixel's mean, e.g. 0.01 for 1%
+    pub heatmap: Option<PathBuf>, // Where to save the samples-per-pixel heatmap image, if anywhere
+}
+
+impl Default for AdaptiveSettings {
+    fn default() -> Self {
+        AdaptiveSettings {
+            min_samples: 16,
+            max_samples: 1024,
+            samples_per_pass: 8,
+            error_threshold: 0.01,
+            heatmap: None,
+        }
+    }
+}
+
+impl AdaptiveSettings {
+    pub fn validate(&self) -> Result<()> {
+        if self.samples_per_pass == 0 {
+            return Err(Error::invalid("samples_per_pass", "must be at least 1"));
+        }
+        if self.max_samples == 0 {
+            return Err(Error::invalid("max_samples", "must be at least 1"));
+        }
+        if self.min_samples > self.max_samples {
+            return Err(Error::invalid(
+                "min_samples",
+                format!("{} is more than max_samples ({})", self.min_samples, self.max_samples),
+            ));
+        }
+        if !(self.error_threshold.is_finite() && self.error_threshold > 0.0) {
+            return Err(Error::invalid(
+                "error_threshold",
+                format!("must be a positive number, got {}", self.error_threshold),
+            ));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,47 @@
+use serde::{Deserialize, Serialize};
+
+use crate::color::Color;
+use crate::light::Light;
+use crate::sky::Sky;
+use crate::vec3::Vec3;
+
+// What a ray sees when it leaves the scene without hitting anything.
+// In settings files this is a table tagged by `type`, e.g. `type = "sky"` next to the sky parameters.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Background {
+    #[default]
+    Gradient, // White to light blue blend along y
+    Sky(Box<Sky>),
+}
+
+impl Background {
+    // `include_lights` is false when the light parts of the background (the sun disk) were
+    // already accounted for by sampling them directly at the previous hit.
+    pub fn radiance(&self, direction: &Vec3, include_lights: bool) -> Color {
+        match self {
+            Background::Gradient => {
+                let unit_direction = Vec3::unit_vector(*direction);
+                let a: f64 = 0.5 * (unit_direction.y() + 1.0);
+
+                // blendedValue
+                (1.0 - a) * Color::new(1.0, 1.0, 1.0) + a * Color::new(0.5, 0.7, 1.0)
+            }
+            Background::Sky(sky) => {
+                if include_lights {
+                    sky.radiance(direction)
+                } else {
+                    sky.sky_radiance(direction)
+                }
+            }
+        }
+    }
+
+    // The part of the background that can be sampled as a light, if any.
+    pub fn light(&self) -> Option<&dyn Light> {
+        match self {
+            Background::Gradient => None,
+            Background::Sky(sky) => Some(sky.as_ref()),
+        }
+    }
+}
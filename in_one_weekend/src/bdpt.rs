@@ -0,0 +1,541 @@
+use crate::accumulator::Accumulator;
+use crate::camera::Camera;
+use crate::color::Color;
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::light::Light;
+use crate::light_list::LightList;
+use crate::ray::Ray;
+use crate::rtweekend::INFINITY;
+use crate::sampler::Sampler;
+use crate::vec3::{Point3, Vec3};
+
+// Bidirectional path tracing (Veach 1997, following pbrt's structure).
+//
+// Every sample traces a path from the camera and one from a light picked at random, then joins
+// each prefix of one to each prefix of the other: the strategy (s, t) uses the first s vertices of
+// the light path and the first t of the camera path. t = 1 joins a light path to the camera
+// directly (light tracing), which lands anywhere on the image, and is added to the accumulator as a
+// light sample. s = 0 is a camera path that escaped to the background, s = 1 resamples a point on
+// a light as the path tracer does. A path of n vertices can come from n - 1 such strategies; they
+// are weighted with the balance heuristic, computed from the densities every vertex would have been
+// sampled with from either side.
+//
+// Specular vertices (and the random walks of subsurface materials) can't be joined, and their
+// densities are delta distributions. Around them we store density 1 on both sides, so the ratios
+// across them cancel, and leave the strategies that join at them out of the weights.
+// Densities that are really 0, such as a light path starting at a light at infinity, stay 0 and
+// rule out every strategy that would need them.
+
+// Traces one sample for the camera ray `r`: returns the radiance of the strategies that end at
+// the camera ray's pixel and adds the light tracing ones to `accumulator`.
+pub(crate) fn sample(
+    camera: &Camera,
+    r: &Ray,
+    world: &dyn Hittable,
+    lights: &LightList,
+    sampler: &mut dyn Sampler,
+    accumulator: &mut Accumulator,
+) -> Color {
+    Tracer::new(camera, world, lights).sample(r, sampler, accumulator)
+}
+
+enum VertexKind {
+    Camera(Point3),
+    // A light at a point; `light` indexes Tracer::lights.
+    Light { light: usize, p: Point3 },
+    // Infinitely far away along `direction` (seen from the scene): a light at infinity, or the
+    // background where a camera path escaped (`light` None).
+    Distant { light: Option<usize>, direction: Vec3 },
+    // A hit on a surface, reached travelling against `wo`.
+    Surface { rec: HitRecord, wo: Vec3 },
+}
+
+struct Vertex {
+    kind: VertexKind,
+    throughput: Color, // Of the subpath up to this vertex, divided by its density
+    pdf_fwd: f64,      // Area density of sampling this vertex from its predecessor on its own path
+    pdf_rev: f64,      // Area density of sampling it the other way, from its successor
+    delta: bool,       // Scattered specularly, can't be joined
+}
+
+// The densities a MIS weight looks at, with the joined vertices' replaced.
+#[derive(Clone, Copy)]
+struct Densities {
+    pdf_fwd: f64,
+    pdf_rev: f64,
+    delta: bool,
+}
+
+struct Tracer<'a> {
+    camera: &'a Camera,
+    world: &'a dyn Hittable,
+    lights: Vec<&'a dyn Light>,   // The scene's lights, then the background's
+    background_light: Option<usize>,
+    max_depth: usize,
+}
+
+impl Vertex {
+    fn new(kind: VertexKind, throughput: Color, pdf_fwd: f64) -> Self {
+        Vertex {
+            kind,
+            throughput,
+            pdf_fwd,
+            pdf_rev: 0.0,
+            delta: false,
+        }
+    }
+
+    // Position, for the vertices that have one.
+    fn p(&self) -> Point3 {
+        match &self.kind {
+            VertexKind::Camera(p) | VertexKind::Light { p, .. } => *p,
+            VertexKind::Surface { rec, .. } => rec.p,
+            VertexKind::Distant { .. } => Point3::default(),
+        }
+    }
+
+    // Unit direction from this vertex towards `next`.
+    fn direction_to(&self, next: &Vertex) -> Vec3 {
+        match (&self.kind, &next.kind) {
+            (_, VertexKind::Distant { direction, .. }) => *direction,
+            (VertexKind::Distant { direction, .. }, _) => -*direction,
+            _ => Vec3::unit_vector(next.p() - self.p()),
+        }
+    }
+
+    fn densities(&self) -> Densities {
+        Densities {
+            pdf_fwd: self.pdf_fwd,
+            pdf_rev: self.pdf_rev,
+            delta: self.delta,
+        }
+    }
+
+    // Turns a solid angle density of the direction from this vertex to `next` into an area
+    // density at `next`. Distant vertices have no area, their density stays per solid angle.
+    fn convert_density(&self, pdf: f64, next: &Vertex) -> f64 {
+        if let VertexKind::Distant { .. } = next.kind {
+            return pdf;
+        }
+        let to_next = next.p() - self.p();
+        let distance_squared = to_next.length_squared();
+        if distance_squared == 0.0 {
+            return 0.0;
+        }
+        match &next.kind {
+            VertexKind::Surface { rec, .. } => {
+                pdf * Vec3::dot(&rec.normal, &Vec3::unit_vector(to_next)).abs() / distance_squared
+            }
+            _ => pdf / distance_squared,
+        }
+    }
+}
+
+impl<'a> Tracer<'a> {
+    fn new(camera: &'a Camera, world: &'a dyn Hittable, lights: &'a LightList) -> Self {
+        let mut all: Vec<&dyn Light> = lights.lights.iter().map(|light| light.as_ref()).collect();
+        let background_light = camera.background().light().map(|light| {
+            all.push(light);
+            all.len() - 1
+        });
+        Tracer {
+            camera,
+            world,
+            lights: all,
+            background_light,
+            max_depth: camera.max_depth() as usize,
+        }
+    }
+
+    fn sample(&self, r: &Ray, sampler: &mut dyn Sampler, accumulator: &mut Accumulator) -> Color {
+        let camera_path = self.camera_path(r, sampler);
+        let light_path = self.light_path(sampler);
+
+        let mut l = Color::default();
+        for t in 1..=camera_path.len() {
+            for s in 0..=light_path.len().max(1) {
+                if s + t < 2 || (s == 1 && t == 1) || s + t - 2 > self.max_depth {
+                    continue;
+                }
+                if t == 1 {
+                    if let Some((raster, contribution)) = self.connect_to_camera(&light_path, s, sampler) {
+                        accumulator.add_light_sample(raster.0, raster.1, contribution);
+                    }
+                } else {
+                    l += self.connect(&camera_path, &light_path, s, t, sampler);
+                }
+            }
+        }
+        l
+    }
+}
+
+//utils
+impl Tracer<'_> {
+    // Probability of picking any one light, for light paths and for s = 1.
+    fn light_pdf(&self) -> f64 {
+        1.0 / self.lights.len() as f64
+    }
+
+    fn pick_light(&self, sampler: &mut dyn Sampler) -> usize {
+        ((sampler.get_1d() * self.lights.len() as f64) as usize).min(self.lights.len() - 1)
+    }
+
+    fn camera_path(&self, r: &Ray, sampler: &mut dyn Sampler) -> Vec<Vertex> {
+        let mut path = vec![Vertex::new(VertexKind::Camera(r.origin()), Color::new(1.0, 1.0, 1.0), 1.0)];
+        let pdf = self.camera.pdf_we(&r.origin(), &r.direction());
+        self.walk(*r, Color::new(1.0, 1.0, 1.0), Some(pdf), false, sampler, &mut path);
+        path
+    }
+
+    fn light_path(&self, sampler: &mut dyn Sampler) -> Vec<Vertex> {
+        let mut path = Vec::new();
+        if self.lights.is_empty() {
+            return path;
+        }
+        let light = self.pick_light(sampler);
+        let Some(emission) = self.lights[light].sample_le(sampler) else {
+            return path; // Lights at infinity only take part through s = 1
+        };
+        if emission.pdf <= 0.0 || emission.radiance.near_zero() {
+            return path;
+        }
+        let p = emission.ray.origin();
+        path.push(Vertex::new(VertexKind::Light { light, p }, emission.radiance, self.light_pdf()));
+        let throughput = emission.radiance / (self.light_pdf() * emission.pdf);
+        self.walk(emission.ray, throughput, Some(emission.pdf), true, sampler, &mut path);
+        path
+    }
+
+    // Extends `path` from its last vertex along `ray`, which was sampled with solid angle density
+    // `pdf` (None for a delta distribution), until it leaves the scene, is absorbed, or has
+    // max_depth surface vertices. Camera paths that leave end with a Distant vertex.
+    fn walk(
+        &self,
+        mut ray: Ray,
+        mut throughput: Color,
+        mut pdf: Option<f64>,
+        from_light: bool,
+        sampler: &mut dyn Sampler,
+        path: &mut Vec<Vertex>,
+    ) {
+        loop {
+            let mut rec = HitRecord::default();
+            let direction = Vec3::unit_vector(ray.direction());
+            if !self.world.hit(&ray, Interval::new(0.001, INFINITY), &mut rec) {
+                if !from_light {
+                    let distant = VertexKind::Distant { light: None, direction };
+                    path.push(Vertex::new(distant, throughput, pdf.unwrap_or(1.0)));
+                }
+                return;
+            }
+
+            let mat = rec.mat.clone();
+            let mut vertex = Vertex::new(VertexKind::Surface { rec: rec.clone(), wo: -direction }, throughput, 1.0);
+            let prev = path.last().expect("paths start at the camera or a light");
+            if let Some(pdf) = pdf {
+                vertex.pdf_fwd = prev.convert_density(pdf, &vertex);
+            }
+            path.push(vertex);
+            if path.len() > self.max_depth {
+                return;
+            }
+
+            let Some(mat) = mat else {
+                return;
+            };
+            let Some(srec) = mat.scatter(&ray, &rec, sampler) else {
+                return;
+            };
+            let n = path.len();
+            let (prev, vertex) = path.split_at_mut(n - 1);
+            let (prev, vertex) = (&mut prev[n - 2], &mut vertex[0]);
+
+            if srec.is_specular {
+                vertex.delta = true;
+                prev.pdf_rev = 1.0;
+                throughput = throughput * srec.attenuation;
+                pdf = None;
+                // A light path can't follow a subsurface walk back: it would have to leave where
+                // the walk entered.
+                if from_light && srec.ray.origin() != rec.p {
+                    return;
+                }
+            } else {
+                let wi = Vec3::unit_vector(srec.ray.direction());
+                if from_light {
+                    // Light paths carry importance: the BSDF with its directions swapped.
+                    throughput = throughput * Tracer::f(&rec, &wi, &-direction) * Tracer::cos(&rec, &wi) / srec.pdf;
+                } else {
+                    throughput = throughput * srec.attenuation;
+                }
+                let pdf_rev = Tracer::pdf_dir(&rec, &wi, &-direction);
+                prev.pdf_rev = vertex.convert_density(pdf_rev, prev);
+                pdf = Some(srec.pdf);
+            }
+            if throughput.near_zero() {
+                return;
+            }
+            ray = srec.ray;
+        }
+    }
+
+    // The hit as seen by a ray arriving against `wo`, which decides the side materials work on.
+    fn seen_from(rec: &HitRecord, wo: &Vec3) -> HitRecord {
+        let outward = if rec.front_face { rec.normal } else { -rec.normal };
+        let mut seen = rec.clone();
+        seen.set_face_normal(&Ray::new(rec.p + *wo, -*wo), &outward);
+        seen
+    }
+
+    fn cos(rec: &HitRecord, direction: &Vec3) -> f64 {
+        Vec3::dot(&rec.normal, direction).abs()
+    }
+
+    // BSDF f(wo, wi), without the cosine materials' eval includes.
+    fn f(rec: &HitRecord, wo: &Vec3, wi: &Vec3) -> Color {
+        let Some(mat) = &rec.mat else {
+            return Color::default();
+        };
+        let cos = Tracer::cos(rec, wi);
+        if cos < 1e-9 {
+            return Color::default();
+        }
+        let seen = Tracer::seen_from(rec, wo);
+        mat.eval(&Ray::new(rec.p + *wo, -*wo), &seen, wi) / cos
+    }
+
+    // Solid angle density of the material sampling `wi` for light leaving along `wo`.
+    fn pdf_dir(rec: &HitRecord, wo: &Vec3, wi: &Vec3) -> f64 {
+        let Some(mat) = &rec.mat else {
+            return 0.0;
+        };
+        let seen = Tracer::seen_from(rec, wo);
+        mat.pdf(&Ray::new(rec.p + *wo, -*wo), &seen, wi)
+    }
+
+    // Direction towards the vertex a surface was reached from.
+    fn wo(v: &Vertex) -> Vec3 {
+        match &v.kind {
+            VertexKind::Surface { wo, .. } => *wo,
+            _ => Vec3::default(),
+        }
+    }
+
+    // Area density with which `v` samples `next`, where `wo` points to v's other neighbour
+    // (surfaces only).
+    fn pdf(&self, v: &Vertex, wo: &Vec3, next: &Vertex) -> f64 {
+        let direction = v.direction_to(next);
+        let pdf = match &v.kind {
+            VertexKind::Camera(p) => self.camera.pdf_we(p, &direction),
+            VertexKind::Light { light, .. } => self.lights[*light].pdf_le(&direction),
+            VertexKind::Distant { .. } => 0.0,
+            VertexKind::Surface { rec, .. } => Tracer::pdf_dir(rec, wo, &direction),
+        };
+        v.convert_density(pdf, next)
+    }
+
+    // Density of a light path starting at `v`, a vertex on a light: picking the light, then the
+    // point (which has a density per solid angle for the lights at infinity).
+    fn pdf_light_origin(&self, v: &Vertex, from: &Point3) -> f64 {
+        match &v.kind {
+            VertexKind::Light { .. } => self.light_pdf(),
+            VertexKind::Distant { light: Some(light), direction } => {
+                self.light_pdf() * self.lights[*light].pdf_li(from, direction)
+            }
+            VertexKind::Distant { light: None, direction } => match self.background_light {
+                Some(light) => self.light_pdf() * self.lights[light].pdf_li(from, direction),
+                None => 0.0,
+            },
+            _ => 0.0,
+        }
+    }
+
+    fn is_delta_light(&self, v: &Vertex) -> bool {
+        match &v.kind {
+            VertexKind::Light { light, .. } | VertexKind::Distant { light: Some(light), .. } => self.lights[*light].is_delta(),
+            _ => false,
+        }
+    }
+
+    fn visible(&self, a: &Point3, b: &Point3) -> bool {
+        let to_b = *b - *a;
+        let distance = to_b.length();
+        let mut rec = HitRecord::default();
+        !self.world.hit(&Ray::new(*a, to_b / distance), Interval::new(0.001, distance - 0.001), &mut rec)
+    }
+
+    // Strategy (s, t) with t >= 2, whose contribution belongs to the camera ray's pixel.
+    fn connect(&self, camera_path: &[Vertex], light_path: &[Vertex], s: usize, t: usize, sampler: &mut dyn Sampler) -> Color {
+        let pt = &camera_path[t - 1];
+        if s == 0 {
+            // The camera path escaped: the background, with the sun weighted against sampling it.
+            let VertexKind::Distant { direction, .. } = pt.kind else {
+                return Color::default();
+            };
+            let sky = self.camera.background().radiance(&direction, false);
+            let sun = self.camera.background().radiance(&direction, true) - sky;
+            if sun.near_zero() {
+                return pt.throughput * sky;
+            }
+            let weight = self.mis_weight(camera_path, light_path, None, s, t);
+            return pt.throughput * (sky + weight * sun);
+        }
+
+        let VertexKind::Surface { rec: pt_rec, wo: pt_wo } = &pt.kind else {
+            return Color::default();
+        };
+        if pt.delta {
+            return Color::default();
+        }
+
+        if s == 1 {
+            // Resample a point on a light, as the path tracer does.
+            if self.lights.is_empty() {
+                return Color::default();
+            }
+            let light = self.pick_light(sampler);
+            let Some(ls) = self.lights[light].sample_li(&pt_rec.p, sampler) else {
+                return Color::default();
+            };
+            if ls.pdf <= 0.0 || ls.radiance.near_zero() {
+                return Color::default();
+            }
+            let f = Tracer::f(pt_rec, pt_wo, &ls.direction) * Tracer::cos(pt_rec, &ls.direction);
+            if f.near_zero() {
+                return Color::default();
+            }
+            let shadow_ray = Ray::new(pt_rec.p, ls.direction);
+            let mut shadow_rec = HitRecord::default();
+            if self.world.hit(&shadow_ray, Interval::new(0.001, ls.distance - 0.001), &mut shadow_rec) {
+                return Color::default();
+            }
+
+            let kind = if ls.distance.is_finite() {
+                VertexKind::Light { light, p: pt_rec.p + ls.distance * ls.direction }
+            } else {
+                VertexKind::Distant { light: Some(light), direction: ls.direction }
+            };
+            let mut sampled = Vertex::new(kind, ls.radiance / (ls.pdf * self.light_pdf()), 0.0);
+            sampled.pdf_fwd = self.pdf_light_origin(&sampled, &pt_rec.p);
+            let weight = self.mis_weight(camera_path, light_path, Some(&sampled), s, t);
+            return weight * pt.throughput * f * sampled.throughput;
+        }
+
+        let qs = &light_path[s - 1];
+        let VertexKind::Surface { rec: qs_rec, wo: qs_wo } = &qs.kind else {
+            return Color::default();
+        };
+        if qs.delta {
+            return Color::default();
+        }
+        let to_pt = pt_rec.p - qs_rec.p;
+        let distance_squared = to_pt.length_squared();
+        let direction = Vec3::unit_vector(to_pt);
+        let f_qs = Tracer::f(qs_rec, &direction, qs_wo);
+        let f_pt = Tracer::f(pt_rec, pt_wo, &-direction);
+        if f_qs.near_zero() || f_pt.near_zero() || distance_squared == 0.0 {
+            return Color::default();
+        }
+        let g = Tracer::cos(qs_rec, &direction) * Tracer::cos(pt_rec, &direction) / distance_squared;
+        if !self.visible(&qs_rec.p, &pt_rec.p) {
+            return Color::default();
+        }
+        let weight = self.mis_weight(camera_path, light_path, None, s, t);
+        weight * g * qs.throughput * f_qs * f_pt * pt.throughput
+    }
+
+    // Strategy (s, 1), s >= 2: light tracing, the light path's end joined to a point on the lens.
+    // Returns the image position it lands at and its contribution.
+    fn connect_to_camera(&self, light_path: &[Vertex], s: usize, sampler: &mut dyn Sampler) -> Option<((f64, f64), Color)> {
+        let qs = &light_path[s - 1];
+        let VertexKind::Surface { rec, wo } = &qs.kind else {
+            return None;
+        };
+        if qs.delta {
+            return None;
+        }
+        let (lens, raster, importance) = self.camera.sample_we(&rec.p, sampler)?;
+        let direction = Vec3::unit_vector(lens - rec.p);
+        let f = Tracer::f(rec, &direction, wo) * Tracer::cos(rec, &direction);
+        if f.near_zero() || !self.visible(&rec.p, &lens) {
+            return None;
+        }
+        let sampled = Vertex::new(VertexKind::Camera(lens), Color::new(importance, importance, importance), 1.0);
+        let weight = self.mis_weight(&[], light_path, Some(&sampled), s, 1);
+        Some((raster, weight * importance * qs.throughput * f))
+    }
+
+    // Balance heuristic weight of strategy (s, t) for the path it joined: one over the sum, over
+    // every strategy that could have sampled the same path, of its density relative to this one's.
+    // `sampled` is the vertex resampled on a light (s = 1) or the camera (t = 1).
+    fn mis_weight(&self, camera_path: &[Vertex], light_path: &[Vertex], sampled: Option<&Vertex>, s: usize, t: usize) -> f64 {
+        if s + t == 2 {
+            return 1.0;
+        }
+        let qs = match s {
+            0 => None,
+            1 => sampled,
+            _ => Some(&light_path[s - 1]),
+        };
+        let pt = if t == 1 {
+            sampled.expect("light tracing samples the camera")
+        } else {
+            &camera_path[t - 1]
+        };
+        let qs_minus = (s > 1).then(|| &light_path[s - 2]);
+        let pt_minus = (t > 1).then(|| &camera_path[t - 2]);
+
+        let mut camera: Vec<Densities> = camera_path.iter().take(t).chain((t == 1).then_some(pt)).map(Vertex::densities).collect();
+        let mut light: Vec<Densities> = match s {
+            1 => qs.into_iter().map(Vertex::densities).collect(),
+            _ => light_path.iter().take(s).map(Vertex::densities).collect(),
+        };
+        let light_origin = if s == 1 { qs } else { light_path.first() };
+
+        // The joined vertices are sampled from the other side, and were never scattered specularly.
+        camera[t - 1].delta = false;
+        camera[t - 1].pdf_rev = match qs {
+            Some(qs) => self.pdf(qs, &Tracer::wo(qs), pt),
+            None => self.pdf_light_origin(pt, &pt_minus.map(Vertex::p).unwrap_or_default()),
+        };
+        if let Some(pt_minus) = pt_minus {
+            // Lights at infinity never start light paths, s = 0 can't be extended.
+            camera[t - 2].pdf_rev = match qs {
+                Some(qs) => self.pdf(pt, &pt.direction_to(qs), pt_minus),
+                None => 0.0,
+            };
+        }
+        if let Some(qs) = qs {
+            light[s - 1].delta = false;
+            light[s - 1].pdf_rev = self.pdf(pt, &Tracer::wo(pt), qs);
+            if let Some(qs_minus) = qs_minus {
+                light[s - 2].pdf_rev = self.pdf(qs, &qs.direction_to(pt), qs_minus);
+            }
+        }
+
+        let ratio = |d: &Densities| if d.pdf_fwd > 0.0 { d.pdf_rev / d.pdf_fwd } else { 0.0 };
+        let mut sum = 0.0;
+        let mut r = 1.0;
+        for i in (1..t).rev() {
+            r *= ratio(&camera[i]);
+            if !camera[i].delta && !camera[i - 1].delta {
+                sum += r;
+            }
+        }
+        r = 1.0;
+        for i in (0..s).rev() {
+            r *= ratio(&light[i]);
+            let delta_before = if i > 0 {
+                light[i - 1].delta
+            } else {
+                light_origin.is_some_and(|v| self.is_delta_light(v))
+            };
+            if !light[i].delta && !delta_before {
+                sum += r;
+            }
+        }
+        1.0 / (1.0 + sum)
+    }
+}
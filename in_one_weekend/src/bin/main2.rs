@@ -1,32 +1,25 @@
-use std::fs::File;
-use std::io::{self, Write};
+use std::path::Path;
+
+use in_one_weekend::{Color, Image, ToneMapping};
 
 const IMG_HEIGHT: u32 = 256;
 const IMG_WIDTH: u32 = 256;
 
-fn main() -> io::Result<()> {
-    let mut file = File::create("./images/img.ppm")?;
-    writeln!(file, "P3")?;
-    writeln!(file, "{} {}", IMG_WIDTH, IMG_HEIGHT)?;
-    writeln!(file, "255")?;
-
-    for i in 0..IMG_HEIGHT {
-        eprint!("\rScanlines remaining: {}", IMG_HEIGHT - i - 1);
-        io::stderr().flush()?;
+fn main() -> in_one_weekend::Result<()> {
+    let mut image = Image::new(IMG_WIDTH, IMG_HEIGHT);
 
-        for j in 0..IMG_WIDTH {
-            let r: f32 = j as f32 / (IMG_WIDTH - 1) as f32;
-            let g: f32 = i as f32 / (IMG_HEIGHT - 1) as f32;
-            let b: f32 = 0.0;
+    // Red grows from left to right, green from top to bottom.
+    for j in 0..IMG_HEIGHT {
+        for i in 0..IMG_WIDTH {
+            let r = i as f64 / (IMG_WIDTH - 1) as f64;
+            let g = j as f64 / (IMG_HEIGHT - 1) as f64;
+            let b = 0.0;
 
-            let ir: u8 = (255.999 * r) as u8;
-            let ig: u8 = (255.999 * g) as u8;
-            let ib: u8 = (255.999 * b) as u8;
-
-            writeln!(file, "{} {} {}", ir, ig, ib)?;
+            image.set_pixel(i, j, &Color::new(r, g, b));
         }
     }
-    eprint!("                           \n");
-    eprintln!("\rdone");
+
+    image.save(Path::new("./images/img.ppm"), &ToneMapping::default())?;
+    eprintln!("done");
     Ok(())
 }
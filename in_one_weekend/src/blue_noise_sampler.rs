@@ -0,0 +1,157 @@
+use std::sync::OnceLock;
+
+use crate::sampler::{self, Sampler};
+
+const TILE: usize = 64; // Side of the toroidal blue-noise tile
+const SIGMA: f64 = 1.5; // Width of the Gaussian energy filter used by void-and-cluster
+
+// Low-discrepancy sequences (golden ratio for 1D, Roberts' R2 for 2D) rotated per pixel by a
+// blue-noise mask (Georgiev & Fajardo, "Blue-noise Dithered Sampling").
+// Neighbouring pixels get very different offsets, so the remaining error is spread as high
+// frequency noise across the image instead of clumping, which looks far less noisy at low sample counts.
+// Each dimension reads the mask at a different toroidal shift to stay decorrelated.
+pub struct BlueNoiseSampler {
+    seed: u64,
+    pixel: (u32, u32),
+    sample_index: u32,
+    dimension: u32,
+}
+
+impl BlueNoiseSampler {
+    pub fn new(seed: u64) -> Self {
+        BlueNoiseSampler {
+            seed,
+            pixel: (0, 0),
+            sample_index: 0,
+            dimension: 0,
+        }
+    }
+
+    fn offset(&mut self) -> f64 {
+        let shift = sampler::hash_pixel(0, 0, self.dimension, self.seed ^ 0x5eed);
+        let x = (self.pixel.0 as usize + (shift as usize % TILE)) % TILE;
+        let y = (self.pixel.1 as usize + ((shift >> 32) as usize % TILE)) % TILE;
+        self.dimension += 1;
+        blue_noise_mask()[y * TILE + x]
+    }
+}
+
+impl Default for BlueNoiseSampler {
+    fn default() -> Self {
+        BlueNoiseSampler::new(0)
+    }
+}
+
+impl Sampler for BlueNoiseSampler {
+    fn start_pixel_sample(&mut self, i: u32, j: u32, sample_index: u32) {
+        self.pixel = (i, j);
+        self.sample_index = sample_index;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        const GOLDEN: f64 = 0.618_033_988_749_894_8; // 1 / phi
+        let n = self.sample_index as f64;
+        (n * GOLDEN + self.offset()).fract()
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        const G: f64 = 1.324_717_957_244_746; // Plastic number
+        let n = self.sample_index as f64;
+        let x = (n / G + self.offset()).fract();
+        let y = (n / (G * G) + self.offset()).fract();
+        (x, y)
+    }
+}
+
+// Blue-noise mask with values in [0, 1), built once with Ulichney's void-and-cluster method.
+fn blue_noise_mask() -> &'static [f64] {
+    static MASK: OnceLock<Vec<f64>> = OnceLock::new();
+    MASK.get_or_init(void_and_cluster)
+}
+
+fn void_and_cluster() -> Vec<f64> {
+    let n = TILE * TILE;
+
+    // Toroidal Gaussian weights indexed by the wrapped offset between two pixels.
+    let mut kernel = vec![0.0; n];
+    for dy in 0..TILE {
+        for dx in 0..TILE {
+            let wx = dx.min(TILE - dx) as f64;
+            let wy = dy.min(TILE - dy) as f64;
+            kernel[dy * TILE + dx] = (-(wx * wx + wy * wy) / (2.0 * SIGMA * SIGMA)).exp();
+        }
+    }
+
+    let mut pattern = vec![false; n];
+    let mut energy = vec![0.0; n];
+    let splat = |energy: &mut Vec<f64>, p: usize, sign: f64| {
+        let (px, py) = (p % TILE, p / TILE);
+        for y in 0..TILE {
+            for x in 0..TILE {
+                let dx = (x + TILE - px) % TILE;
+                let dy = (y + TILE - py) % TILE;
+                energy[y * TILE + x] += sign * kernel[dy * TILE + dx];
+            }
+        }
+    };
+    // Tightest cluster: the set pixel with the most energy. Largest void: the empty pixel with the least.
+    let tightest_cluster = |pattern: &[bool], energy: &[f64]| {
+        (0..n).filter(|&p| pattern[p]).max_by(|&a, &b| energy[a].total_cmp(&energy[b])).unwrap()
+    };
+    let largest_void = |pattern: &[bool], energy: &[f64]| {
+        (0..n).filter(|&p| !pattern[p]).min_by(|&a, &b| energy[a].total_cmp(&energy[b])).unwrap()
+    };
+
+    // Initial binary pattern: ~10% of the pixels at hashed positions, then relaxed by moving the
+    // tightest cluster into the largest void until that no longer changes anything.
+    let initial_count = n / 10;
+    let mut placed = 0;
+    let mut k = 0;
+    while placed < initial_count {
+        let p = (sampler::mix_bits(k) % n as u64) as usize;
+        k += 1;
+        if !pattern[p] {
+            pattern[p] = true;
+            splat(&mut energy, p, 1.0);
+            placed += 1;
+        }
+    }
+    loop {
+        let cluster = tightest_cluster(&pattern, &energy);
+        pattern[cluster] = false;
+        splat(&mut energy, cluster, -1.0);
+        let void = largest_void(&pattern, &energy);
+        if void == cluster {
+            pattern[cluster] = true;
+            splat(&mut energy, cluster, 1.0);
+            break;
+        }
+        pattern[void] = true;
+        splat(&mut energy, void, 1.0);
+    }
+
+    let mut rank = vec![0usize; n];
+
+    // Phase 1: rank the initial points by repeatedly removing the tightest cluster.
+    let mut removal = pattern.clone();
+    let mut removal_energy = energy.clone();
+    for r in (0..initial_count).rev() {
+        let cluster = tightest_cluster(&removal, &removal_energy);
+        removal[cluster] = false;
+        splat(&mut removal_energy, cluster, -1.0);
+        rank[cluster] = r;
+    }
+
+    // Phases 2 and 3: fill the remaining pixels into the largest void. Because the filter sums to
+    // the same value everywhere, the least energetic empty pixel is also the tightest cluster of
+    // empty pixels, so one rule covers both halves of the original algorithm.
+    for r in initial_count..n {
+        let void = largest_void(&pattern, &energy);
+        pattern[void] = true;
+        splat(&mut energy, void, 1.0);
+        rank[void] = r;
+    }
+
+    rank.into_iter().map(|r| (r as f64 + 0.5) / n as f64).collect()
+}
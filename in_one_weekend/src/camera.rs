@@ -1,158 +1,860 @@
 use crate::{
-    camera, color::{self, Color, write_color}, hittable::{HitRecord, Hittable}, interval::Interval, ray::Ray, rtweekend::*, vec3::{Point3, Vec3}
+    accumulator::Accumulator, adaptive::AdaptiveSettings, background::Background, bdpt, camera_settings::CameraSettings, checkpoint::Checkpoint, distributed::{self, DistributedSettings}, color::{self, Color}, encoder::ImageFormat, error::{Error, Result}, filter::{Filter, FilterKind}, hittable::{HitRecord, Hittable}, image::Image, integrator::IntegratorKind, interval::Interval, light::Light, light_list::LightList, material::Material, mlt, photon_mapping::{self, Caustics, PhotonMaps}, preview::TerminalPreview, progressive::ProgressiveSettings, render_status::RenderStatus, ray::Ray, rtweekend::*, sampler::{Sampler, SamplerKind}, sampling, scene_hash, spectrum::{SampledSpectrum, SampledWavelengths}, tile::Tile, tonemap::ToneMapping, vec3::{Point3, Vec3}
 };
 
-use indicatif::ProgressBar;
-use std::io::{self, BufWriter, Write};
+use indicatif::{ProgressBar, ProgressDrawTarget};
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+use std::time::{Duration, Instant};
 
 pub struct Camera {
-    pub aspect_ratio: f64,
-    pub img_width: u32,
-    pub samples_per_pixel: u32,   // Count of random samples for each pixel
-    pub max_depth: u32,   // Maximum number of ray bounces into scene
-
-    image_height : u32,
-    pixel_samples_scale: f64,
-    center: Point3,
-    pixel_delta_u: Vec3,
-    pixel_delta_v: Vec3,
-    pixel00_loc: Vec3,
+    settings: CameraSettings,
+
+    image_height: u32,  // Rendered image height
+    center: Point3,     // Camera center
+    pixel00_loc: Point3, // Location of pixel 0, 0
+    pixel_delta_u: Vec3, // Offset to pixel to the right
+    pixel_delta_v: Vec3, // Offset to pixel below
+    defocus_disk_u: Vec3, // Defocus disk horizontal radius
+    defocus_disk_v: Vec3, // Defocus disk vertical radius
+    forward: Vec3,       // Unit view direction
+    film_area: f64,      // Area of the viewport at distance 1 from the camera, for light tracing
+    photon_maps: PhotonMaps, // Of the render in progress, for the photon mapping integrators
+}
+
+// Builds a Camera; every setting has a default so only the interesting ones need to be given.
+//
+//     let camera = Camera::builder()
+//         .aspect_ratio(16.0 / 9.0)
+//         .image_width(400)
+//         .samples_per_pixel(100)
+//         .build()?;
+#[derive(Default)]
+pub struct CameraBuilder {
+    settings: CameraSettings,
+}
+
+impl CameraBuilder {
+    // Starts from saved settings, e.g. ones read with CameraSettings::load.
+    pub fn from_settings(settings: CameraSettings) -> Self {
+        CameraBuilder { settings }
+    }
+
+    pub fn aspect_ratio(mut self, aspect_ratio: f64) -> Self {
+        self.settings.aspect_ratio = aspect_ratio;
+        self
+    }
+    pub fn image_width(mut self, img_width: u32) -> Self {
+        self.settings.image_width = img_width;
+        self
+    }
+    pub fn samples_per_pixel(mut self, samples_per_pixel: u32) -> Self {
+        self.settings.samples_per_pixel = samples_per_pixel;
+        self
+    }
+    pub fn max_depth(mut self, max_depth: u32) -> Self {
+        self.settings.max_depth = max_depth;
+        self
+    }
+    pub fn vfov(mut self, vfov: f64) -> Self {
+        self.settings.vfov = vfov;
+        self
+    }
+    pub fn look_from(mut self, look_from: Point3) -> Self {
+        self.settings.look_from = look_from;
+        self
+    }
+    pub fn look_at(mut self, look_at: Point3) -> Self {
+        self.settings.look_at = look_at;
+        self
+    }
+    pub fn vup(mut self, vup: Vec3) -> Self {
+        self.settings.vup = vup;
+        self
+    }
+    pub fn defocus_angle(mut self, defocus_angle: f64) -> Self {
+        self.settings.defocus_angle = defocus_angle;
+        self
+    }
+    pub fn focus_dist(mut self, focus_dist: f64) -> Self {
+        self.settings.focus_dist = focus_dist;
+        self
+    }
+    pub fn background(mut self, background: Background) -> Self {
+        self.settings.background = background;
+        self
+    }
+    pub fn sampler(mut self, sampler: SamplerKind) -> Self {
+        self.settings.sampler = sampler;
+        self
+    }
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.settings.seed = seed;
+        self
+    }
+    pub fn filter(mut self, filter: FilterKind) -> Self {
+        self.settings.filter = filter;
+        self
+    }
+    pub fn filter_radius(mut self, filter_radius: Option<f64>) -> Self {
+        self.settings.filter_radius = filter_radius;
+        self
+    }
+    pub fn spectral(mut self, spectral: bool) -> Self {
+        self.settings.spectral = spectral;
+        self
+    }
+    pub fn integrator(mut self, integrator: IntegratorKind) -> Self {
+        self.settings.integrator = integrator;
+        self
+    }
+    pub fn tone_mapping(mut self, tone_mapping: ToneMapping) -> Self {
+        self.settings.tone_mapping = tone_mapping;
+        self
+    }
+
+    // The settings collected so far, without building a camera.
+    pub fn settings(&self) -> &CameraSettings {
+        &self.settings
+    }
+
+    // Checks the settings and builds the camera, or reports the first setting that makes no sense.
+    pub fn build(self) -> Result<Camera> {
+        Camera::new(self.settings)
+    }
 }
 
 impl Camera {
-    pub fn init(aspect_ratio: f64, img_width: u32, samples_per_pixel: u32, max_depth: u32) -> Self {
+    pub fn new(settings: CameraSettings) -> Result<Self> {
+        settings.validate()?;
+
         let mut camera = Camera {
-            aspect_ratio,
-            img_width,
-            samples_per_pixel,
-            max_depth,
+            settings,
 
             image_height: 0,
-            pixel_samples_scale: 0.0,
             center: Point3::default(),
+            pixel00_loc: Point3::default(),
             pixel_delta_u: Vec3::default(),
             pixel_delta_v: Vec3::default(),
-            pixel00_loc: Vec3::default(),
+            defocus_disk_u: Vec3::default(),
+            defocus_disk_v: Vec3::default(),
+            forward: Vec3::default(),
+            film_area: 0.0,
+            photon_maps: PhotonMaps::default(),
         };
         camera.initialize(); // Compute derived data here
-        camera
+        Ok(camera)
+    }
+
+    pub fn init(aspect_ratio: f64, img_width: u32, samples_per_pixel: u32, max_depth: u32) -> Result<Self> {
+        Camera::builder()
+            .aspect_ratio(aspect_ratio)
+            .image_width(img_width)
+            .samples_per_pixel(samples_per_pixel)
+            .max_depth(max_depth)
+            .build()
+    }
+
+    pub fn builder() -> CameraBuilder {
+        CameraBuilder::default()
+    }
+}
+
+//getters
+impl Camera {
+    pub fn settings(&self) -> &CameraSettings {
+        &self.settings
+    }
+    pub fn image_width(&self) -> u32 {
+        self.settings.image_width
+    }
+    pub fn image_height(&self) -> u32 {
+        self.image_height
+    }
+    pub fn samples_per_pixel(&self) -> u32 {
+        self.settings.samples_per_pixel
+    }
+    pub fn max_depth(&self) -> u32 {
+        self.settings.max_depth
+    }
+    pub fn background(&self) -> &Background {
+        &self.settings.background
+    }
+    pub fn tone_mapping(&self) -> &ToneMapping {
+        &self.settings.tone_mapping
     }
 }
 
 // public
 impl Camera {
-    pub fn render(&mut self, world: &dyn Hittable) {
+    pub fn render(&self, world: &dyn Hittable, lights: &LightList) -> Result<Image> {
+        self.render_to(world, lights, &RenderStatus::new(), ProgressDrawTarget::stderr())
+    }
 
-        let stdout = io::stdout();
-        let mut out = BufWriter::new(stdout.lock());
+    // Like render, but reports its progress to `status` instead of a progress bar and stops when it
+    // is cancelled, so another thread can watch (or abort) the render.
+    pub fn render_with_status(&self, world: &dyn Hittable, lights: &LightList, status: &RenderStatus) -> Result<Image> {
+        self.render_to(world, lights, status, ProgressDrawTarget::hidden())
+    }
+
+    // Takes all samples_per_pixel samples of the pixels in `tile`. The samples only depend on the
+    // pixel and the seed, so tiles can be rendered in any order, or on other machines, and give the
+    // same image as a render of the whole frame.
+    pub fn render_tile(&self, world: &dyn Hittable, lights: &LightList, tile: &Tile, accumulator: &mut Accumulator) {
+        // The photon maps are kept from one tile to the next, as long as the tiles are of the same scene.
+        if matches!(self.settings.integrator, IntegratorKind::PhotonMapping | IntegratorKind::ProgressivePhotonMapping) {
+            self.photon_maps.use_scene(scene_hash::scene_hash(world, lights, &self.settings));
+        }
+        self.sample_tile(world, lights, tile, 0..self.settings.samples_per_pixel, accumulator);
+    }
+
+    // An empty accumulation buffer for the camera's image size and filter.
+    pub fn create_accumulator(&self) -> Accumulator {
+        Accumulator::new(self.settings.image_width, self.image_height, self.create_filter())
+    }
+
+    // Renders with the samples spread over worker processes, see distributed.rs.
+    pub fn render_distributed(
+        &self,
+        world: &dyn Hittable,
+        lights: &LightList,
+        settings: &DistributedSettings,
+    ) -> Result<Image> {
+        self.photon_maps.clear();
+        let image = distributed::coordinate(self, world, lights, settings);
+        self.photon_maps.clear();
+        image
+    }
+
+    pub fn render_progressive(
+        &self,
+        world: &dyn Hittable,
+        lights: &LightList,
+        settings: &ProgressiveSettings,
+        output: &Path,
+        format: ImageFormat,
+    ) -> Result<Image> {
+        // Renders in passes into an accumulation buffer and rewrites `output` (as `format`) after
+        // each pass, so the image can be looked at (and the render stopped) at any time.
+        settings.validate()?;
+        self.require_pixel_sampling("progressive")?;
+        self.photon_maps.clear();
+        let start = Instant::now();
+        let mut accumulator = self.create_accumulator();
+        let samples_per_pass = settings.samples_per_pass.max(1);
+
+        let mut checkpoint = Checkpoint {
+            scene_hash: scene_hash::scene_hash(world, lights, &self.settings),
+            samples_taken: 0,
+            sampler_samples: settings.max_samples,
+            elapsed: Duration::ZERO,
+        };
+        if settings.resume
+            && let Some(path) = &settings.checkpoint
+        {
+            checkpoint = Checkpoint::read(path, checkpoint.scene_hash, &mut accumulator)?;
+        }
+        let mut total_samples = checkpoint.samples_taken;
+        let elapsed_before = checkpoint.elapsed; // Time spent by the renders this one resumes
+        // Sized like the sampler of the render this one resumes, which keeps taking the same samples.
+        let mut sampler = self.settings.sampler.create(checkpoint.sampler_samples, self.settings.seed);
+        let mut last_checkpoint = Instant::now();
+        let mut checkpointed_samples = total_samples;
+
+        // The preview shows the progress itself, instead of the bar.
+        let mut preview = settings.preview.map(TerminalPreview::new);
+        let bar = ProgressBar::new(settings.max_samples as u64);
+        if preview.is_some() {
+            bar.set_draw_target(ProgressDrawTarget::hidden());
+        }
+        bar.set_position(total_samples.min(settings.max_samples) as u64);
+
+        while total_samples < settings.max_samples {
+            let pass_samples = samples_per_pass.min(settings.max_samples - total_samples);
+            for j in 0..self.image_height {
+                for i in 0..self.settings.image_width {
+                    for s in total_samples..total_samples + pass_samples {
+                        self.sample_pixel(i, j, s, sampler.as_mut(), world, lights, &mut accumulator);
+                    }
+                }
+            }
+            total_samples += pass_samples;
+            self.photon_maps.forget_before(self.photon_pass(total_samples));
+            let image = accumulator.to_image();
+            self.write_snapshot(&image, output, format)?;
+
+            if let Some(path) = &settings.checkpoint
+                && last_checkpoint.elapsed() >= settings.checkpoint_interval
+            {
+                checkpoint.samples_taken = total_samples;
+                checkpoint.elapsed = elapsed_before + start.elapsed();
+                checkpoint.write(path, &accumulator)?;
+                last_checkpoint = Instant::now();
+                checkpointed_samples = total_samples;
+            }
+
+            let noise = accumulator.noise();
+            bar.set_position(total_samples as u64);
+            bar.set_message(format!("noise {:.4}", noise));
+            if let Some(preview) = &mut preview {
+                let status = format!("{}/{} samples per pixel, noise {:.4}", total_samples, settings.max_samples, noise);
+                // A terminal that can't be drawn on is no reason to stop the render.
+                let _ = preview.draw(&image, &self.settings.tone_mapping, &status);
+            }
+
+            if let Some(limit) = settings.time_limit
+                && elapsed_before + start.elapsed() >= limit
+            {
+                break;
+            }
+            if let Some(target) = settings.target_noise
+                && noise <= target
+            {
+                break;
+            }
+        }
+        bar.finish();
+        self.photon_maps.clear();
+        if let Some(preview) = &mut preview {
+            let _ = preview.finish();
+        }
 
-        writeln!(out, "P3").unwrap();
-        writeln!(out, "{} {}", self.img_width, self.image_height).unwrap();
-        writeln!(out, "255").unwrap();
+        if let Some(path) = &settings.checkpoint
+            && total_samples != checkpointed_samples
+        {
+            checkpoint.samples_taken = total_samples;
+            checkpoint.elapsed = elapsed_before + start.elapsed();
+            checkpoint.write(path, &accumulator)?;
+        }
+        Ok(accumulator.to_image())
+    }
+
+    pub fn render_adaptive(
+        &self,
+        world: &dyn Hittable,
+        lights: &LightList,
+        settings: &AdaptiveSettings,
+    ) -> Result<Image> {
+        settings.validate()?;
+        self.require_pixel_sampling("adaptive")?;
+        self.photon_maps.clear();
+        // Spends samples where the image is still noisy: after `min_samples` everywhere, each pass
+        // only revisits the pixels whose estimated error is above the threshold.
+        let mut accumulator = self.create_accumulator();
+        let max_samples = settings.max_samples.max(1);
+        let min_samples = settings.min_samples.min(max_samples);
+        let samples_per_pass = settings.samples_per_pass.max(1);
+        let pixel_count = self.settings.image_width as u64 * self.image_height as u64;
+        let mut sampler = self.settings.sampler.create(max_samples, self.settings.seed);
 
-        let bar = ProgressBar::new(self.image_height as u64);
+        let bar = ProgressBar::new(pixel_count);
 
-        for j in 0..self.image_height {
-            for i in 0..self.img_width {
+        for samples in self.photon_passes(0..min_samples) {
+            for j in 0..self.image_height {
+                for i in 0..self.settings.image_width {
+                    for s in samples.clone() {
+                        self.sample_pixel(i, j, s, sampler.as_mut(), world, lights, &mut accumulator);
+                    }
+                }
+            }
+            self.photon_maps.forget_before(self.photon_pass(samples.end));
+        }
 
-                let mut pixel_color = color::Color::new(0.0, 0.0, 0.0);
-                for _ in 0..self.samples_per_pixel {
-                    // For each pixel, take multiple stochastic samples (SSAA) and average their colors
-                    let r = self.get_ray(i, j);
-                    pixel_color += Camera::ray_color(&r, self.max_depth, world);
+        loop {
+            let mut active = 0;
+            // Only pixels sampled in this pass can be sampled in the next, from here on.
+            let mut next_sample = u32::MAX;
+            for j in 0..self.image_height {
+                for i in 0..self.settings.image_width {
+                    let n = accumulator.samples(i, j);
+                    if n >= max_samples || accumulator.relative_error(i, j) <= settings.error_threshold {
+                        continue;
+                    }
+                    active += 1;
+                    let end = n + samples_per_pass.min(max_samples - n);
+                    for s in n..end {
+                        self.sample_pixel(i, j, s, sampler.as_mut(), world, lights, &mut accumulator);
+                    }
+                    next_sample = next_sample.min(end);
                 }
-                pixel_color *= self.pixel_samples_scale;
-                
-                color::write_color(&mut out, &pixel_color).unwrap();
             }
-            bar.inc(1);
+            bar.set_position(pixel_count - active);
+            if active == 0 {
+                break;
+            }
+            self.photon_maps.forget_before(self.photon_pass(next_sample));
         }
         bar.finish();
-        out.flush().unwrap();
+        self.photon_maps.clear();
+
+        if let Some(heatmap) = &settings.heatmap {
+            accumulator.heatmap(max_samples).save(heatmap, &ToneMapping::default())?;
+        }
+        Ok(accumulator.to_image())
     }
 }
 // private
 impl Camera {
 
-    fn initialize(&mut self){
-        // Image
+    // Renders the whole frame, drawing the progress bar to `draw_target`.
+    fn render_to(
+        &self,
+        world: &dyn Hittable,
+        lights: &LightList,
+        status: &RenderStatus,
+        draw_target: ProgressDrawTarget,
+    ) -> Result<Image> {
+        if self.settings.integrator == IntegratorKind::Metropolis {
+            return mlt::render(self, world, lights, status, draw_target);
+        }
+        self.photon_maps.clear();
 
-        // Calculate the image height, and ensure that it's at least 1.
-        self.image_height = ((self.img_width as f64) / self.aspect_ratio) as u32;
+        // Pass by pass over the image, so only one photon map is kept at a time. There is just the
+        // one pass unless the photon mapping is progressive.
+        let passes = self.photon_passes(0..self.settings.samples_per_pixel);
+        let rows = self.image_height as u64 * passes.len() as u64;
+        let bar = ProgressBar::with_draw_target(Some(rows), draw_target);
+        let mut accumulator = self.create_accumulator();
+        status.start(rows);
 
-        if self.image_height < 1 {
-            self.image_height = 1;
-        } else {
-            self.image_height = self.image_height;
+        for samples in passes {
+            for j in 0..self.image_height {
+                if status.is_cancelled() {
+                    bar.abandon();
+                    return Err(Error::Cancelled);
+                }
+                let row = Tile::new(0, j, self.settings.image_width, j + 1);
+                self.sample_tile(world, lights, &row, samples.clone(), &mut accumulator);
+                bar.inc(1);
+                status.advance(1);
+            }
+            self.photon_maps.forget_before(self.photon_pass(samples.end));
         }
+        bar.finish();
+        self.photon_maps.clear();
+
+        Ok(accumulator.to_image())
+    }
 
-        self.pixel_samples_scale = 1.0 / (self.samples_per_pixel as f64);  // Color scale factor for a sum of pixel samples
+    fn initialize(&mut self){
+        let settings = &self.settings;
 
-        // Camera
-        self.center = Point3::default(); // eye point
+        // Calculate the image height, and ensure that it's at least 1.
+        self.image_height = settings.image_height();
 
-        let focal_length = 1.0; //[ CAMERA ] ---- distance ---- [ VIRTUAL SCREEN ]
+        self.center = settings.look_from;
 
+        // Determine viewport dimensions; the viewport sits on the plane of perfect focus.
         // Viewport width less than one are ok since they are real valued.
-        let viewport_height = 2.0;
-        let viewport_width = viewport_height * (self.img_width as f64) / (self.image_height as f64);
+        let theta = degrees_to_radians(settings.vfov);
+        let h = (theta / 2.0).tan();
+        let viewport_height = 2.0 * h * settings.focus_dist;
+        let viewport_width = viewport_height * (settings.image_width as f64) / (self.image_height as f64);
+
+        // Calculate the u,v,w unit basis vectors for the camera coordinate frame.
+        let w = Vec3::unit_vector(settings.look_from - settings.look_at);
+        let u = Vec3::unit_vector(Vec3::cross(&settings.vup, &w));
+        let v = Vec3::cross(&w, &u);
 
         // Calculate the vectors across the horizontal and down the vertical viewport edges.
-        let viewport_u = Vec3::new(viewport_width, 0.0, 0.0); // Left edge to Right edge
-        let viewport_v = Vec3::new(0.0, -viewport_height, 0.0); // Up edge to Down edge
+        let viewport_u = viewport_width * u; // Left edge to Right edge
+        let viewport_v = viewport_height * -v; // Up edge to Down edge
 
         // Calculate the horizontal and vertical delta vectors from pixel to pixel.
-        self.pixel_delta_u = viewport_u / (self.img_width as f64) ;
-        self.pixel_delta_v = viewport_v / (self.image_height as f64) ;
+        self.pixel_delta_u = viewport_u / (settings.image_width as f64);
+        self.pixel_delta_v = viewport_v / (self.image_height as f64);
 
         // Calculate the location of the upper left pixel.
-        let viewport_upper_left =
-            self.center - Vec3::new(0.0, 0.0, focal_length) - viewport_u / 2.0 - viewport_v / 2.0;
+        let viewport_upper_left = self.center - settings.focus_dist * w - viewport_u / 2.0 - viewport_v / 2.0;
         self.pixel00_loc = viewport_upper_left + 0.5 * (self.pixel_delta_u + self.pixel_delta_v);
 
+        // Calculate the camera defocus disk basis vectors.
+        let defocus_radius = settings.focus_dist * degrees_to_radians(settings.defocus_angle / 2.0).tan();
+        self.defocus_disk_u = defocus_radius * u;
+        self.defocus_disk_v = defocus_radius * v;
+
+        self.forward = -w;
+        self.film_area = viewport_width * viewport_height / (settings.focus_dist * settings.focus_dist);
+    }
+
+    // Takes one radiance sample (the `sample_index`-th) through pixel i, j and adds it to `accumulator`.
+    #[allow(clippy::too_many_arguments)]
+    fn sample_pixel(
+        &self,
+        i: u32,
+        j: u32,
+        sample_index: u32,
+        sampler: &mut dyn Sampler,
+        world: &dyn Hittable,
+        lights: &LightList,
+        accumulator: &mut Accumulator,
+    ) {
+        sampler.start_pixel_sample(i, j, sample_index);
+        let (offset, r) = self.camera_ray(i, j, sampler);
+        let sample = if self.settings.spectral {
+            // Every sample is converted to RGB right away, the conversion is linear so the pixel
+            // averages come out the same as converting the averaged spectra.
+            let mut wavelengths = SampledWavelengths::sample(sampler.get_1d());
+            let r = r.with_wavelength(wavelengths.hero());
+            let l = self.ray_spectrum(&r, self.settings.max_depth, world, lights, sampler, true, &mut wavelengths);
+            wavelengths.to_rgb(&l)
+        } else {
+            let depth = self.settings.max_depth;
+            match self.settings.integrator {
+                // Metropolis renders don't sample pixels (see mlt.rs), a tile of one is path traced.
+                IntegratorKind::PathTracing | IntegratorKind::Metropolis => {
+                    self.ray_color(&r, depth, world, lights, sampler, true, None)
+                }
+                IntegratorKind::Bidirectional => bdpt::sample(self, &r, world, lights, sampler, accumulator),
+                IntegratorKind::PhotonMapping | IntegratorKind::ProgressivePhotonMapping => {
+                    let caustics = self.caustics(sample_index, world, lights);
+                    self.ray_color(&r, depth, world, lights, sampler, true, Some(&caustics))
+                }
+            }
+        };
+        accumulator.add_sample(i, j, offset, sample);
+    }
+
+    // Takes the samples numbered `samples` of the pixels in `tile`.
+    fn sample_tile(
+        &self,
+        world: &dyn Hittable,
+        lights: &LightList,
+        tile: &Tile,
+        samples: Range<u32>,
+        accumulator: &mut Accumulator,
+    ) {
+        let mut sampler = self.settings.sampler.create(self.settings.samples_per_pixel, self.settings.seed);
+        for (i, j) in tile.pixels() {
+            for s in samples.clone() {
+                // For each pixel, take multiple stochastic samples (SSAA); the accumulator
+                // weights them with the reconstruction filter.
+                self.sample_pixel(i, j, s, sampler.as_mut(), world, lights, accumulator);
+            }
+        }
+    }
+
+    // The photon map pass the `sample_index`-th sample of a pixel belongs to.
+    fn photon_pass(&self, sample_index: u32) -> u32 {
+        match self.settings.integrator {
+            IntegratorKind::ProgressivePhotonMapping => sample_index / self.settings.photons.samples_per_pass,
+            _ => 0,
+        }
+    }
+
+    // `samples` split into the runs of samples that share a photon map pass.
+    fn photon_passes(&self, samples: Range<u32>) -> Vec<Range<u32>> {
+        let mut passes = Vec::new();
+        let mut start = samples.start;
+        while start < samples.end {
+            let mut end = start + 1;
+            while end < samples.end && self.photon_pass(end) == self.photon_pass(start) {
+                end += 1;
+            }
+            passes.push(start..end);
+            start = end;
+        }
+        passes
     }
 
-    fn ray_color(r: &Ray, depth: u32, world: &dyn Hittable) -> color::Color {
+    // The photon map and radius of the pass `sample_index` belongs to.
+    fn caustics(&self, sample_index: u32, world: &dyn Hittable, lights: &LightList) -> Caustics {
+        let photons = &self.settings.photons;
+        let pass = self.photon_pass(sample_index);
+        let map = self.photon_maps.get(pass, || {
+            photon_mapping::trace_photons(world, lights, photons, self.settings.max_depth, self.settings.seed, pass)
+        });
+        Caustics {
+            map,
+            radius: photons.radius_at(pass),
+        }
+    }
+
+    // Metropolis chains wander over the whole image, renders that go pixel by pixel can't use them.
+    fn require_pixel_sampling(&self, mode: &str) -> Result<()> {
+        if self.settings.integrator == IntegratorKind::Metropolis {
+            return Err(Error::invalid(
+                "integrator",
+                format!("{} renders can't be {}", self.settings.integrator, mode),
+            ));
+        }
+        Ok(())
+    }
+
+    fn create_filter(&self) -> Box<dyn Filter> {
+        self.settings.filter.create(self.settings.filter_radius)
+    }
+
+    fn write_snapshot(&self, image: &Image, output: &Path, format: ImageFormat) -> Result<()> {
+        // Write next to the output and rename, so readers never see a half-written image.
+        let tmp = output.with_extension("part");
+        image.save_as(&tmp, format, &self.settings.tone_mapping)?;
+        fs::rename(&tmp, output).map_err(|e| Error::file(output, e))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn ray_color(
+        &self,
+        r: &Ray,
+        depth: u32,
+        world: &dyn Hittable,
+        lights: &LightList,
+        sampler: &mut dyn Sampler,
+        include_lights: bool,
+        caustics: Option<&Caustics>,
+    ) -> color::Color {
         // If we've exceeded the ray bounce limit, no more light is gathered.
-        if depth <= 0 {
+        if depth == 0 {
             return Color::new(0.0, 0.0, 0.0);
         }
 
         let mut rec = HitRecord::default();
 
         if world.hit(r, Interval::new(0.001, INFINITY), &mut rec) {
-            // vec3 direction = rec.normal + random_unit_vector();
-            let direction = rec.normal + Vec3::random_unit_vector(); // Lambertian distribution
-            return 0.4 * Camera::ray_color(&Ray::new(rec.p, direction), depth - 1, world);
+            let Some(mat) = rec.mat.clone() else {
+                return Color::default();
+            };
+            let Some(srec) = mat.scatter(r, &rec, sampler) else {
+                return Color::default();
+            };
+
+            if srec.is_specular {
+                // A specular bounce can't sample lights, so it has to see them when it hits them.
+                return srec.attenuation * self.ray_color(&srec.ray, depth - 1, world, lights, sampler, true, caustics);
+            }
+
+            // Direct lighting: sample every light of the scene and of the background.
+            let mut direct = Color::default();
+            for light in &lights.lights {
+                if let Some((f, radiance, pdf)) = Camera::sample_light(light.as_ref(), mat.as_ref(), r, &rec, world, sampler) {
+                    direct += (1.0 / pdf) * (f * radiance);
+                }
+            }
+            if let Some(light) = self.settings.background.light()
+                && let Some((f, radiance, pdf)) = Camera::sample_light(light, mat.as_ref(), r, &rec, world, sampler)
+            {
+                direct += (1.0 / pdf) * (f * radiance);
+            }
+            // Light from the lights through mirrors and glass, which the bounce ray can't find.
+            if let Some(caustics) = caustics {
+                direct += caustics.radiance(r, &rec, mat.as_ref());
+            }
+
+            // The lights were sampled above, so the bounce ray only picks up the rest of the background.
+            return direct + srec.attenuation * self.ray_color(&srec.ray, depth - 1, world, lights, sampler, false, caustics);
         }
 
-        let unit_direction = Vec3::unit_vector(r.direction());
-        let a: f64 = 0.5 * (unit_direction.y() + 1.0);
-        
-        // blendedValue
-        (1.0 - a) * color::Color::new(1.0, 1.0, 1.0) + a * color::Color::new(0.5, 0.7, 1.0)
+        self.settings.background.radiance(&r.direction(), include_lights)
     }
 
-    fn get_ray(&self, i: u32, j: u32) -> Ray {
-        // Construct a camera ray originating from the origin and directed at randomly sampled
-        // point around the pixel location i, j.
+    // ray_color for spectral renders: the same paths, with every color the path meets upsampled to
+    // the path's wavelengths. The path follows the hero wavelength, which is all that dispersive
+    // materials look at, so after one of them the other wavelengths are dropped.
+    #[allow(clippy::too_many_arguments)]
+    fn ray_spectrum(
+        &self,
+        r: &Ray,
+        depth: u32,
+        world: &dyn Hittable,
+        lights: &LightList,
+        sampler: &mut dyn Sampler,
+        include_lights: bool,
+        wavelengths: &mut SampledWavelengths,
+    ) -> SampledSpectrum {
+        if depth == 0 {
+            return SampledSpectrum::default();
+        }
 
-        // Generate a random offset within the pixel area to perform supersampling (SSAA).
+        let mut rec = HitRecord::default();
+
+        if world.hit(r, Interval::new(0.001, INFINITY), &mut rec) {
+            let Some(mat) = rec.mat.clone() else {
+                return SampledSpectrum::default();
+            };
+            let Some(srec) = mat.scatter(r, &rec, sampler) else {
+                return SampledSpectrum::default();
+            };
+            if mat.is_dispersive() {
+                wavelengths.terminate_secondary();
+            }
+            let attenuation = wavelengths.upsample(&srec.attenuation);
+            let scattered = srec.ray.with_wavelength(wavelengths.hero());
+
+            if srec.is_specular {
+                return attenuation * self.ray_spectrum(&scattered, depth - 1, world, lights, sampler, true, wavelengths);
+            }
+
+            // Reflectance and light are upsampled apart, their product's RGB isn't the RGB of their product.
+            let mut direct = SampledSpectrum::default();
+            let background_light = self.settings.background.light();
+            for light in lights.lights.iter().map(|light| light.as_ref()).chain(background_light) {
+                if let Some((f, radiance, pdf)) = Camera::sample_light(light, mat.as_ref(), r, &rec, world, sampler) {
+                    direct += (1.0 / pdf) * (wavelengths.upsample(&f) * wavelengths.upsample(&radiance));
+                }
+            }
+
+            return direct
+                + attenuation * self.ray_spectrum(&scattered, depth - 1, world, lights, sampler, false, wavelengths);
+        }
+
+        wavelengths.upsample(&self.settings.background.radiance(&r.direction(), include_lights))
+    }
+
+    fn sample_light(
+        light: &dyn Light,
+        mat: &dyn Material,
+        r: &Ray,
+        rec: &HitRecord,
+        world: &dyn Hittable,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Color, Color, f64)> {
+        // Samples a point on `light` as seen from the hit point, returning the material's eval
+        // towards it, the radiance arriving from it and the pdf of the sample; None if the light is
+        // behind the surface or a shadow ray towards it is blocked.
+        let ls = light.sample_li(&rec.p, sampler)?;
+        if ls.pdf <= 0.0 {
+            return None;
+        }
+        let f = mat.eval(r, rec, &ls.direction);
+        if f.near_zero() {
+            return None;
+        }
+
+        let shadow_ray = Ray::new(rec.p, ls.direction);
+        let mut shadow_rec = HitRecord::default();
+        // Stop just short of lights at a finite distance so we don't count the light's own position.
+        if world.hit(&shadow_ray, Interval::new(0.001, ls.distance - 0.001), &mut shadow_rec) {
+            return None;
+        }
+
+        Some((f, ls.radiance, ls.pdf))
+    }
+
+    // A camera ray through a random point of pixel i, j, with the point's offset from the pixel center.
+    pub(crate) fn camera_ray(&self, i: u32, j: u32, sampler: &mut dyn Sampler) -> ((f64, f64), Ray) {
+        let offset = Camera::sample_square(sampler);
+        let r = self.get_ray(i, j, &offset, sampler);
+        ((offset.x(), offset.y()), r)
+    }
+
+    // A camera ray through a random point of the whole image, for the Metropolis chains: returns the
+    // continuous image position it goes through and the radiance the path tracer finds along it.
+    pub(crate) fn sample_image(&self, sampler: &mut dyn Sampler, world: &dyn Hittable, lights: &LightList) -> ((f64, f64), Color) {
+        let (u, v) = sampler.get_2d();
+        let (x, y) = (u * self.settings.image_width as f64, v * self.image_height as f64);
+        let i = (x as u32).min(self.settings.image_width - 1);
+        let j = (y as u32).min(self.image_height - 1);
+        let offset = Vec3::new(x - i as f64 - 0.5, y - j as f64 - 0.5, 0.0);
+        let r = self.get_ray(i, j, &offset, sampler);
+        ((x, y), self.ray_color(&r, self.settings.max_depth, world, lights, sampler, true, None))
+    }
+
+    // Continuous image position (pixel i, j covers [i, i + 1) x [j, j + 1)) of the camera ray
+    // leaving the lens at `lens` along `direction`, or None if it is outside the image.
+    fn raster_position(&self, lens: &Point3, direction: &Vec3) -> Option<(f64, f64)> {
+        let direction = Vec3::unit_vector(*direction);
+        let cos_theta = Vec3::dot(&direction, &self.forward);
+        if cos_theta <= 0.0 {
+            return None;
+        }
+        // Where the ray crosses the plane of focus, relative to the center of pixel 0, 0.
+        let q = *lens + (self.settings.focus_dist / cos_theta) * direction - self.pixel00_loc;
+        let x = Vec3::dot(&q, &self.pixel_delta_u) / self.pixel_delta_u.length_squared() + 0.5;
+        let y = Vec3::dot(&q, &self.pixel_delta_v) / self.pixel_delta_v.length_squared() + 0.5;
+        let inside = (0.0..self.settings.image_width as f64).contains(&x) && (0.0..self.image_height as f64).contains(&y);
+        inside.then_some((x, y))
+    }
+
+    // Density (solid angle) with which camera rays leaving `lens` go along `direction`: the
+    // image is sampled uniformly, so it is 1 / (A cos^3) for the film area A at distance 1.
+    pub(crate) fn pdf_we(&self, lens: &Point3, direction: &Vec3) -> f64 {
+        if self.raster_position(lens, direction).is_none() {
+            return 0.0;
+        }
+        let cos_theta = Vec3::dot(&Vec3::unit_vector(*direction), &self.forward);
+        1.0 / (self.film_area * cos_theta * cos_theta * cos_theta)
+    }
+
+    // Connects `p` to the camera for light tracing: picks a point on the lens, and returns it with
+    // the image position `p` is seen at and the camera's importance over the density of the lens
+    // point (per area at `p`), or None if `p` is out of view.
+    pub(crate) fn sample_we(&self, p: &Point3, sampler: &mut dyn Sampler) -> Option<(Point3, (f64, f64), f64)> {
+        let lens = if self.settings.defocus_angle <= 0.0 {
+            self.center
+        } else {
+            self.defocus_disk_sample(sampler)
+        };
+        let to_p = *p - lens;
+        let distance_squared = to_p.length_squared();
+        let raster = self.raster_position(&lens, &to_p)?;
+        let cos_theta = Vec3::dot(&Vec3::unit_vector(to_p), &self.forward);
+        // The lens area cancels between the importance and the density of the lens point.
+        let weight = 1.0 / (self.film_area * cos_theta * cos_theta * cos_theta * distance_squared);
+        Some((lens, raster, weight))
+    }
+
+    fn get_ray(&self, i: u32, j: u32, offset: &Vec3, sampler: &mut dyn Sampler) -> Ray {
+        // Construct a camera ray originating from the defocus disk and directed at the point
+        // `offset` away from the center of pixel i, j.
+
+        // The offset is a random point within the pixel area, used to perform supersampling (SSAA).
         // This stochastic sampling reduces aliasing by averaging multiple rays per pixel
         // with slightly jittered positions instead of just shooting through the pixel center.
 
-        let offset = Camera::sample_square();
         let pixel_sample = self.pixel00_loc
                           + (((i as f64) + offset.x()) * self.pixel_delta_u)
                           + (((j as f64) + offset.y()) * self.pixel_delta_v);
-        let ray_origin = self.center;
+        let ray_origin = if self.settings.defocus_angle <= 0.0 {
+            self.center
+        } else {
+            self.defocus_disk_sample(sampler)
+        };
         let ray_direction = pixel_sample - ray_origin;
 
         Ray::new(ray_origin, ray_direction)
+    }
 
+    fn defocus_disk_sample(&self, sampler: &mut dyn Sampler) -> Point3 {
+        // Returns a random point in the camera defocus disk.
+        let p = sampling::concentric_disk(sampler.get_2d());
+        self.center + (p.x() * self.defocus_disk_u) + (p.y() * self.defocus_disk_v)
     }
-    fn sample_square() -> Vec3 {
+
+    fn sample_square(sampler: &mut dyn Sampler) -> Vec3 {
         // Returns the vector to a random point in the [-.5,-.5]-[+.5,+.5] unit square.
-        Vec3::new(random_double() - 0.5, random_double() - 0.5, 0.0)
+        let (x, y) = sampler.get_2d();
+        Vec3::new(x - 0.5, y - 0.5, 0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_square_is_uniform_on_the_pixel() {
+        // Chi-square test on an 8 x 8 grid: 63 degrees of freedom, 113.6 is the critical value for p = 1e-4.
+        const BINS: usize = 8;
+        const SAMPLES: u32 = 100_000;
+        let mut sampler = SamplerKind::Independent.create(1, 9);
+        let mut counts = [0.0; BINS * BINS];
+        for s in 0..SAMPLES {
+            sampler.start_pixel_sample(0, 0, s);
+            let offset = Camera::sample_square(sampler.as_mut());
+            assert!((-0.5..0.5).contains(&offset.x()) && (-0.5..0.5).contains(&offset.y()) && offset.z() == 0.0);
+            let (i, j) = (((offset.x() + 0.5) * BINS as f64) as usize, ((offset.y() + 0.5) * BINS as f64) as usize);
+            counts[j * BINS + i] += 1.0;
+        }
+        let expected = SAMPLES as f64 / (BINS * BINS) as f64;
+        let chi_square: f64 = counts.iter().map(|&c| (c - expected) * (c - expected) / expected).sum();
+        assert!(chi_square < 113.6, "chi-square {:.1} over 63 degrees of freedom", chi_square);
+    }
+
+    #[test]
+    fn oversized_images_are_rejected() {
+        // 70000 x 700000000 pixels overflows a u32 pixel count.
+        let settings = CameraSettings { image_width: 70000, aspect_ratio: 0.0001, ..CameraSettings::default() };
+        assert!(matches!(Camera::new(settings), Err(Error::InvalidParameter { name: "image_width", .. })));
     }
 }
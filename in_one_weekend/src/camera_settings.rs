@@ -0,0 +1,240 @@
+use std::fs;
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::background::Background;
+use crate::error::{Error, Result};
+use crate::filter::FilterKind;
+use crate::integrator::IntegratorKind;
+use crate::mlt::MetropolisSettings;
+use crate::photon_mapping::PhotonSettings;
+use crate::sampler::SamplerKind;
+use crate::tonemap::ToneMapping;
+use crate::vec3::{Point3, Vec3};
+
+// Largest image a camera renders (16384 x 16384 pixels). Far below what would overflow the pixel
+// buffers, and already tens of gigabytes of accumulator.
+pub const MAX_PIXELS: u64 = 1 << 28;
+
+// Everything that decides how a Camera renders, apart from the scene itself.
+// Saved next to an image (as JSON or TOML) it is enough to render the same image again, since every
+// sampler is seeded from `seed`. Missing entries in a file take their default value.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraSettings {
+    pub aspect_ratio: f64,
+    pub image_width: u32,
+    pub samples_per_pixel: u32, // Count of random samples for each pixel
+    pub max_depth: u32,         // Maximum number of ray bounces into scene
+
+    pub vfov: f64,         // Vertical view angle (field of view), in degrees
+    pub look_from: Point3, // Point camera is looking from
+    pub look_at: Point3,   // Point camera is looking at
+    pub vup: Vec3,         // Camera-relative "up" direction
+
+    pub defocus_angle: f64, // Variation angle of rays through each pixel, in degrees; 0 is a pinhole
+    pub focus_dist: f64,    // Distance from the camera to the plane of perfect focus
+
+    pub sampler: SamplerKind,       // How sample positions are generated
+    pub seed: u64,                  // Seed for the sampler, the same seed gives the same image
+    pub filter: FilterKind,         // Pixel reconstruction filter
+    pub filter_radius: Option<f64>, // Filter radius in pixels, None for the filter's default
+
+    // Trace wavelengths instead of RGB, see spectrum.rs. Left out of files when off, which keeps the
+    // scene hashes of RGB renders (and so their checkpoints) as they were.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub spectral: bool,
+    // How the light through each pixel is estimated, also left out of files when it's path tracing.
+    #[serde(skip_serializing_if = "IntegratorKind::is_default")]
+    pub integrator: IntegratorKind,
+
+    // Tables last, TOML can't have plain values after them.
+    #[serde(skip_serializing_if = "PhotonSettings::is_default")]
+    pub photons: PhotonSettings, // For the photon mapping integrators
+    #[serde(skip_serializing_if = "MetropolisSettings::is_default")]
+    pub metropolis: MetropolisSettings, // For the Metropolis integrator
+    pub background: Background,    // Scene background color / sky, also sampled as a light
+    pub tone_mapping: ToneMapping, // Exposure and curve applied before writing pixels
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        // A 90 degree pinhole at the origin looking down -z, the view the renderer always had.
+        CameraSettings {
+            aspect_ratio: 1.0,
+            image_width: 100,
+            samples_per_pixel: 10,
+            max_depth: 10,
+
+            vfov: 90.0,
+            look_from: Point3::new(0.0, 0.0, 0.0),
+            look_at: Point3::new(0.0, 0.0, -1.0),
+            vup: Vec3::new(0.0, 1.0, 0.0),
+
+            defocus_angle: 0.0,
+            focus_dist: 10.0,
+
+            sampler: SamplerKind::default(),
+            seed: 0,
+            filter: FilterKind::default(),
+            filter_radius: None,
+
+            spectral: false,
+            integrator: IntegratorKind::default(),
+
+            photons: PhotonSettings::default(),
+            metropolis: MetropolisSettings::default(),
+            background: Background::default(),
+            tone_mapping: ToneMapping::default(),
+        }
+    }
+}
+
+impl CameraSettings {
+    // Image height for `image_width` and `aspect_ratio`, at least 1.
+    pub fn image_height(&self) -> u32 {
+        ((self.image_width as f64 / self.aspect_ratio) as u32).max(1)
+    }
+
+    // Reports the first setting that makes no sense.
+    pub fn validate(&self) -> Result<()> {
+        if !(self.aspect_ratio.is_finite() && self.aspect_ratio > 0.0) {
+            return Err(Error::invalid("aspect_ratio", format!("must be a positive number, got {}", self.aspect_ratio)));
+        }
+        if self.image_width == 0 {
+            return Err(Error::invalid("image_width", "must be at least 1 pixel"));
+        }
+        let pixels = self.image_width as u64 * self.image_height() as u64;
+        if pixels > MAX_PIXELS {
+            return Err(Error::invalid(
+                "image_width",
+                format!("{} x {} pixels is more than {} pixels", self.image_width, self.image_height(), MAX_PIXELS),
+            ));
+        }
+        if self.samples_per_pixel == 0 {
+            return Err(Error::invalid("samples_per_pixel", "must be at least 1"));
+        }
+        if self.max_depth == 0 {
+            return Err(Error::invalid("max_depth", "must be at least 1"));
+        }
+        if !(self.vfov > 0.0 && self.vfov < 180.0) {
+            return Err(Error::invalid("vfov", format!("must be between 0 and 180 degrees, got {}", self.vfov)));
+        }
+        let view = self.look_from - self.look_at;
+        if !view.length().is_finite() || view.near_zero() {
+            return Err(Error::invalid("look_at", "must be a finite point away from look_from"));
+        }
+        if Vec3::cross(&self.vup, &view).near_zero() {
+            return Err(Error::invalid("vup", "must not be parallel to the viewing direction"));
+        }
+        if !(self.defocus_angle >= 0.0 && self.defocus_angle < 180.0) {
+            return Err(Error::invalid(
+                "defocus_angle",
+                format!("must be between 0 and 180 degrees, got {}", self.defocus_angle),
+            ));
+        }
+        if !(self.focus_dist.is_finite() && self.focus_dist > 0.0) {
+            return Err(Error::invalid("focus_dist", format!("must be a positive distance, got {}", self.focus_dist)));
+        }
+        if let Some(radius) = self.filter_radius
+            && !(radius.is_finite() && radius >= 0.5)
+        {
+            return Err(Error::invalid("filter_radius", format!("must be at least 0.5 pixels, got {}", radius)));
+        }
+        if self.spectral && self.integrator != IntegratorKind::PathTracing {
+            return Err(Error::invalid(
+                "integrator",
+                format!("{} doesn't render spectrally, only path tracing does", self.integrator),
+            ));
+        }
+        self.photons.validate()?;
+        self.metropolis.validate()?;
+        if !self.tone_mapping.exposure.is_finite() {
+            return Err(Error::invalid("exposure", "must be a finite number of stops"));
+        }
+        if !(self.tone_mapping.white_point.is_finite() && self.tone_mapping.white_point > 0.0) {
+            return Err(Error::invalid("white_point", "must be a positive luminance"));
+        }
+        Ok(())
+    }
+}
+
+// serialisation
+impl CameraSettings {
+    pub fn from_json(json: &str) -> Result<Self> {
+        serde_json::from_str(json).map_err(|e| Error::Settings(e.to_string()))
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).map_err(|e| Error::Settings(e.to_string()))
+    }
+
+    pub fn from_toml(toml: &str) -> Result<Self> {
+        toml::from_str(toml).map_err(|e| Error::Settings(e.to_string()))
+    }
+
+    pub fn to_toml(&self) -> Result<String> {
+        toml::to_string(self).map_err(|e| Error::Settings(e.to_string()))
+    }
+
+    // Reads a .json or .toml settings file.
+    pub fn load(path: &Path) -> Result<Self> {
+        SettingsFormat::from_path(path)?.load(path)
+    }
+
+    // Writes the settings as .json or .toml, depending on the extension of `path`.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let text = match SettingsFormat::from_path(path)? {
+            SettingsFormat::Json => self.to_json()?,
+            SettingsFormat::Toml => self.to_toml()?,
+        };
+        fs::write(path, text).map_err(|e| Error::file(path, e))
+    }
+}
+
+// The text formats settings and scene files can be written in.
+pub(crate) enum SettingsFormat {
+    Json,
+    Toml,
+}
+
+impl SettingsFormat {
+    pub(crate) fn from_path(path: &Path) -> Result<SettingsFormat> {
+        let extension = path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("json") => Ok(SettingsFormat::Json),
+            Some("toml") => Ok(SettingsFormat::Toml),
+            _ => Err(Error::Settings(format!(
+                "unknown settings format for '{}', expected .json or .toml",
+                path.display()
+            ))),
+        }
+    }
+
+    // Guesses the format of text without a file name: JSON documents start with a brace.
+    pub(crate) fn detect(text: &str) -> SettingsFormat {
+        if text.trim_start().starts_with('{') {
+            SettingsFormat::Json
+        } else {
+            SettingsFormat::Toml
+        }
+    }
+
+    pub(crate) fn parse<T: DeserializeOwned>(&self, text: &str) -> Result<T> {
+        let value = match self {
+            SettingsFormat::Json => serde_json::from_str(text).map_err(|e| e.to_string()),
+            SettingsFormat::Toml => toml::from_str(text).map_err(|e| e.to_string()),
+        };
+        value.map_err(Error::Settings)
+    }
+
+    pub(crate) fn load<T: DeserializeOwned>(&self, path: &Path) -> Result<T> {
+        let text = fs::read_to_string(path).map_err(|e| Error::file(path, e))?;
+        self.parse(&text).map_err(|e| match e {
+            Error::Settings(reason) => Error::Settings(format!("{}: {}", path.display(), reason)),
+            e => e,
+        })
+    }
+}
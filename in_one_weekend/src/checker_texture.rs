@@ -0,0 +1,46 @@
+use std::sync::Arc;
+
+use crate::color::Color;
+use crate::scene_hash::SceneHasher;
+use crate::texture::Texture;
+use crate::vec3::Point3;
+
+// 3D checkerboard of cubes of side `scale`, alternating between two textures.
+pub struct CheckerTexture {
+    inv_scale: f64,
+    even: Arc<dyn Texture>,
+    odd: Arc<dyn Texture>,
+}
+
+impl CheckerTexture {
+    pub fn new(scale: f64, even: Arc<dyn Texture>, odd: Arc<dyn Texture>) -> Self {
+        CheckerTexture {
+            inv_scale: 1.0 / scale,
+            even,
+            odd,
+        }
+    }
+}
+
+impl Texture for CheckerTexture {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color {
+        let x_integer = (self.inv_scale * p.x()).floor() as i64;
+        let y_integer = (self.inv_scale * p.y()).floor() as i64;
+        let z_integer = (self.inv_scale * p.z()).floor() as i64;
+
+        let is_even = (x_integer + y_integer + z_integer) % 2 == 0;
+
+        if is_even {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+
+    fn hash_into(&self, hasher: &mut SceneHasher) {
+        hasher.write_str("checker");
+        hasher.write_f64(self.inv_scale);
+        self.even.hash_into(hasher);
+        self.odd.hash_into(hasher);
+    }
+}
@@ -0,0 +1,109 @@
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::accumulator::Accumulator;
+use crate::error::{Error, Result};
+
+const MAGIC: &[u8; 8] = b"RTWCKPT\0";
+const VERSION: u32 = 3;
+
+// The state of a progressive render that was saved to disk and can be continued from.
+// The samplers are counter based (every value is a hash of the seed, pixel, sample index and
+// dimension), so the sample count together with the seed stored in the camera settings and the
+// sample count the sampler was sized for (the strata of the stratified sampler depend on it) is the
+// complete random number state: a resumed render takes exactly the samples the original would have,
+// also when it is resumed with a higher max_samples.
+//
+// File layout, little endian: magic, version (u32), scene hash (u64), samples per pixel taken (u32),
+// samples per pixel of the sampler (u32), elapsed seconds (f64), width and height (u32), then the
+// accumulator sums (Accumulator::write_state).
+pub struct Checkpoint {
+    pub scene_hash: u64,     // scene_hash of the scene and camera settings being rendered
+    pub samples_taken: u32,  // Samples per pixel already in the accumulator
+    pub sampler_samples: u32, // Samples per pixel the sampler was created for
+    pub elapsed: Duration,   // Render time spent so far, counted against the time limit
+}
+
+impl Checkpoint {
+    // Writes the checkpoint and the accumulator next to `path` and renames it into place,
+    // so a crash while writing never destroys the previous checkpoint.
+    pub fn write(&self, path: &Path, accumulator: &Accumulator) -> Result<()> {
+        let tmp = path.with_extension("part");
+        let write = || {
+            let mut out = BufWriter::new(File::create(&tmp)?);
+            out.write_all(MAGIC)?;
+            out.write_all(&VERSION.to_le_bytes())?;
+            out.write_all(&self.scene_hash.to_le_bytes())?;
+            out.write_all(&self.samples_taken.to_le_bytes())?;
+            out.write_all(&self.sampler_samples.to_le_bytes())?;
+            out.write_all(&self.elapsed.as_secs_f64().to_le_bytes())?;
+            out.write_all(&accumulator.width().to_le_bytes())?;
+            out.write_all(&accumulator.height().to_le_bytes())?;
+            accumulator.write_state(&mut out)?;
+            out.flush()
+        };
+        write().map_err(|e| Error::file(&tmp, e))?;
+        fs::rename(&tmp, path).map_err(|e| Error::file(path, e))
+    }
+
+    // Reads a checkpoint into `accumulator`, refusing it if it was made for another scene
+    // (`scene_hash` differs) or another image size.
+    pub fn read(path: &Path, scene_hash: u64, accumulator: &mut Accumulator) -> Result<Checkpoint> {
+        let mut input = BufReader::new(File::open(path).map_err(|e| Error::file(path, e))?);
+        let invalid = |reason: &str| Error::Checkpoint(format!("{}: {}", path.display(), reason));
+
+        let mut magic = [0u8; 8];
+        input.read_exact(&mut magic).map_err(|_| invalid("not a checkpoint file"))?;
+        if &magic != MAGIC {
+            return Err(invalid("not a checkpoint file"));
+        }
+        let version = read_u32(&mut input).map_err(|e| Error::file(path, e))?;
+        if version != VERSION {
+            return Err(invalid(&format!("unsupported checkpoint version {}", version)));
+        }
+
+        let mut header = || -> std::io::Result<(u64, u32, u32, f64, u32, u32)> {
+            let mut hash = [0u8; 8];
+            input.read_exact(&mut hash)?;
+            let samples_taken = read_u32(&mut input)?;
+            let sampler_samples = read_u32(&mut input)?;
+            let mut elapsed = [0u8; 8];
+            input.read_exact(&mut elapsed)?;
+            let width = read_u32(&mut input)?;
+            let height = read_u32(&mut input)?;
+            let elapsed = f64::from_le_bytes(elapsed);
+            Ok((u64::from_le_bytes(hash), samples_taken, sampler_samples, elapsed, width, height))
+        };
+        let (hash, samples_taken, sampler_samples, elapsed, width, height) =
+            header().map_err(|e| Error::file(path, e))?;
+
+        if hash != scene_hash {
+            return Err(invalid("it was made for a different scene or camera settings"));
+        }
+        if width != accumulator.width() || height != accumulator.height() {
+            return Err(invalid(&format!(
+                "it holds a {}x{} image, the camera renders {}x{}",
+                width,
+                height,
+                accumulator.width(),
+                accumulator.height()
+            )));
+        }
+        accumulator.read_state(&mut input).map_err(|e| Error::file(path, e))?;
+
+        Ok(Checkpoint {
+            scene_hash,
+            samples_taken,
+            sampler_samples,
+            elapsed: Duration::try_from_secs_f64(elapsed).unwrap_or_default(),
+        })
+    }
+}
+
+fn read_u32(input: &mut dyn Read) -> std::io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    input.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
@@ -4,23 +4,47 @@ use std::io::Write;
 
 pub type Color = Vec3;
 
+// sRGB transfer function (IEC 61966-2-1): linear segment near black, 2.4 power curve above.
 #[inline]
-pub fn linear_to_gamma(linear_component: f64) -> f64 {
-    if(linear_component > 0.0){
-        return  linear_component.sqrt();
+pub fn linear_to_srgb(linear_component: f64) -> f64 {
+    if linear_component <= 0.0 {
+        return 0.0;
     }
-    return 0.0;
+    if linear_component <= 0.003_130_8 {
+        return 12.92 * linear_component;
+    }
+    1.055 * linear_component.powf(1.0 / 2.4) - 0.055
+}
+
+// Inverse of linear_to_srgb, for 8-bit colors read from images.
+#[inline]
+pub fn srgb_to_linear(srgb_component: f64) -> f64 {
+    if srgb_component <= 0.040_45 {
+        return srgb_component / 12.92;
+    }
+    ((srgb_component + 0.055) / 1.055).powf(2.4)
+}
+
+// Writes an (already tone mapped) linear color as sRGB encoded bytes.
+pub fn write_color<W: Write + ?Sized>(out: &mut W, pixel_color: &Color) -> std::io::Result<()> {
+    let [rbyte, gbyte, bbyte] = to_srgb_bytes(pixel_color);
+
+    // Write out the pixel color components.
+    writeln!(out, "{} {} {}", rbyte, gbyte, bbyte)?;
+
+    Ok(())
 }
-pub fn write_color<W: Write>(out: &mut W, pixel_color: &Color) -> std::io::Result<()> {
 
+// sRGB encodes a linear color and quantises it to 8 bits per channel.
+pub fn to_srgb_bytes(pixel_color: &Color) -> [u8; 3] {
     let mut r = pixel_color.x();
     let mut g = pixel_color.y();
     let mut b = pixel_color.z();
     
 
-    r = linear_to_gamma(r);
-    g = linear_to_gamma(g);
-    b = linear_to_gamma(b);
+    r = linear_to_srgb(r);
+    g = linear_to_srgb(g);
+    b = linear_to_srgb(b);
 
     // Translate the [0,1] component values to the byte range [0,255].
 
@@ -30,8 +54,12 @@ pub fn write_color<W: Write>(out: &mut W, pixel_color: &Color) -> std::io::Resul
     let gbyte: u8 = (255.999 * intensity.clamp(g)) as u8;
     let bbyte: u8 = (255.999 * intensity.clamp(b)) as u8;
 
-    // Write out the pixel color components.
-    writeln!(out, "{} {} {}", rbyte, gbyte, bbyte)?;
+    [rbyte, gbyte, bbyte]
+}
 
-    Ok(())
+// Relative luminance of a linear sRGB color (Rec. 709 weights).
+#[inline]
+pub fn luminance(color: &Color) -> f64 {
+    0.2126 * color.x() + 0.7152 * color.y() + 0.0722 * color.z()
 }
+
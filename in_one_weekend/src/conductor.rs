@@ -0,0 +1,154 @@
+use crate::color::Color;
+use crate::hittable::HitRecord;
+use crate::material::{Material, ScatterRecord};
+use crate::microfacet::{self, Ggx};
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::scene_hash::SceneHasher;
+use crate::thin_film::ThinFilm;
+use crate::vec3::Vec3;
+
+// Complex refractive index eta + i k of a conductor, at the red, green and blue wavelengths.
+#[derive(Debug, Clone, Copy)]
+pub struct ComplexIor {
+    pub eta: Color,
+    pub k: Color,
+}
+
+impl ComplexIor {
+    // Measured metals, sampled at 650, 550 and 450 nm.
+    pub const GOLD: ComplexIor = ComplexIor::new(Color::new(0.143, 0.374, 1.442), Color::new(3.983, 2.386, 1.603));
+    pub const COPPER: ComplexIor = ComplexIor::new(Color::new(0.200, 0.924, 1.102), Color::new(3.912, 2.452, 2.142));
+    pub const ALUMINIUM: ComplexIor = ComplexIor::new(Color::new(1.657, 0.880, 0.521), Color::new(9.224, 6.270, 4.837));
+    pub const SILVER: ComplexIor = ComplexIor::new(Color::new(0.155, 0.117, 0.138), Color::new(4.828, 3.122, 2.147));
+
+    pub const fn new(eta: Color, k: Color) -> Self {
+        ComplexIor { eta, k }
+    }
+
+    // (eta, k) at `wavelength` in nm, interpolated between the red, green and blue samples and
+    // constant beyond them; for the wavelength dependent effects of a thin film on top.
+    pub fn at(&self, wavelength: f64) -> (f64, f64) {
+        let lerp = |c: &Color| {
+            let t = ((650.0 - wavelength) / 100.0).clamp(0.0, 2.0);
+            if t <= 1.0 {
+                (1.0 - t) * c.x() + t * c.y()
+            } else {
+                (2.0 - t) * c.y() + (t - 1.0) * c.z()
+            }
+        };
+        (lerp(&self.eta), lerp(&self.k))
+    }
+}
+
+// Rough metal: a GGX microfacet conductor with the exact Fresnel reflectance of its complex index.
+// Reflections are sampled from the microfacet normals visible from the incoming direction, which
+// keeps the weights (F G / G1) close to the reflectance even at grazing angles.
+// `anisotropy` stretches the highlight along the bitangent of the hit's tangent frame.
+// With a thin `film` on top, the Fresnel reflectance is that of the coated metal.
+pub struct Conductor {
+    ior: ComplexIor,
+    roughness: f64,
+    anisotropy: f64,
+    film: Option<ThinFilm>, // Coating whose interference colors the reflection
+}
+
+impl Conductor {
+    pub fn new(ior: ComplexIor, roughness: f64, anisotropy: f64) -> Self {
+        Conductor {
+            ior,
+            roughness: roughness.clamp(0.0, 1.0),
+            anisotropy: anisotropy.clamp(0.0, 1.0),
+            film: None,
+        }
+    }
+
+    pub fn film(mut self, film: ThinFilm) -> Self {
+        self.film = Some(film);
+        self
+    }
+
+    fn distribution(&self) -> Ggx {
+        Ggx::from_roughness_anisotropic(self.roughness, self.anisotropy)
+    }
+
+    fn fresnel(&self, cos_theta: f64, rec: &HitRecord, wavelength: Option<f64>) -> Color {
+        match &self.film {
+            None => microfacet::fresnel_conductor(cos_theta, self.ior.eta, self.ior.k),
+            Some(film) => film.conductor_reflectance(rec, cos_theta, wavelength, |lambda| self.ior.at(lambda)),
+        }
+    }
+
+    // BSDF in the local frame; zero unless both directions are above the surface.
+    fn f(&self, wo: &Vec3, wi: &Vec3, rec: &HitRecord, wavelength: Option<f64>) -> Color {
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return Color::default();
+        }
+        let h = Vec3::unit_vector(*wo + *wi);
+        let ggx = self.distribution();
+        let fresnel = self.fresnel(Vec3::dot(wo, &h), rec, wavelength);
+        (ggx.d(&h) * ggx.g(wo, wi) / (4.0 * wo.z() * wi.z())) * fresnel
+    }
+
+    fn local_pdf(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return 0.0;
+        }
+        let h = Vec3::unit_vector(*wo + *wi);
+        self.distribution().pdf_visible(wo, &h) / (4.0 * Vec3::dot(wo, &h))
+    }
+}
+
+impl Material for Conductor {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, sampler: &mut dyn Sampler) -> Option<ScatterRecord> {
+        let frame = Onb::with_tangent(&rec.normal, &rec.tangent);
+        let wo = frame.to_local(&-Vec3::unit_vector(r_in.direction()));
+        if wo.z() <= 0.0 {
+            return None;
+        }
+
+        let h = self.distribution().sample_visible(&wo, sampler.get_2d());
+        let wi = microfacet::reflect(&wo, &h);
+        let pdf = self.local_pdf(&wo, &wi);
+        if pdf <= 0.0 {
+            return None;
+        }
+
+        Some(ScatterRecord {
+            attenuation: self.f(&wo, &wi, rec, r_in.wavelength()) * wi.z() / pdf,
+            ray: Ray::new(rec.p, frame.transform(&wi)),
+            pdf,
+            is_specular: false,
+        })
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> Color {
+        let frame = Onb::with_tangent(&rec.normal, &rec.tangent);
+        let wo = frame.to_local(&-Vec3::unit_vector(r_in.direction()));
+        let wi = frame.to_local(&Vec3::unit_vector(*direction));
+        self.f(&wo, &wi, rec, r_in.wavelength()) * wi.z().max(0.0)
+    }
+
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> f64 {
+        let frame = Onb::with_tangent(&rec.normal, &rec.tangent);
+        let wo = frame.to_local(&-Vec3::unit_vector(r_in.direction()));
+        let wi = frame.to_local(&Vec3::unit_vector(*direction));
+        self.local_pdf(&wo, &wi)
+    }
+
+    fn is_dispersive(&self) -> bool {
+        self.film.is_some()
+    }
+
+    fn hash_into(&self, hasher: &mut SceneHasher) {
+        hasher.write_str("conductor");
+        hasher.write_vec3(&self.ior.eta);
+        hasher.write_vec3(&self.ior.k);
+        hasher.write_f64(self.roughness);
+        hasher.write_f64(self.anisotropy);
+        if let Some(film) = &self.film {
+            film.hash_into(hasher);
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use serde::{Deserialize, Serialize};
+
+use crate::color::Color;
+use crate::hittable::HitRecord;
+use crate::material::{Material, ScatterRecord};
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::vec3::Vec3;
+use crate::scene_hash::SceneHasher;
+use crate::thin_film::ThinFilm;
+
+// Clear glass-like material that reflects or refracts, choosing between the two with the
+// Fresnel reflectance (Schlick's approximation).
+pub struct Dielectric {
+    // Refractive index in vacuum or air, or the ratio of the material's refractive index over
+    // the refractive index of the enclosing media
+    refraction_index: f64,
+    // How the index varies with wavelength, used by spectral renders; RGB renders use refraction_index
+    dispersion: Option<Dispersion>,
+    // Coating whose interference colors reflection and transmission, with the exact reflectance of
+    // the coated boundary in place of Schlick's
+    film: Option<ThinFilm>,
+}
+
+// Refractive index as a function of wavelength, for the rainbow edges of prisms and diamonds.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Dispersion {
+    // n = a + b / lambda^2, lambda in micrometres
+    Cauchy { a: f64, b: f64 },
+    // n^2 = 1 + sum of b lambda^2 / (lambda^2 - c), lambda in micrometres and c in square micrometres
+    Sellmeier { b: [f64; 3], c: [f64; 3] },
+}
+
+impl Dispersion {
+    // Schott N-BK7, the common crown glass of lenses.
+    pub const BK7: Dispersion = Dispersion::Sellmeier {
+        b: [1.03961212, 0.231792344, 1.01046945],
+        c: [0.00600069867, 0.0200179144, 103.560653],
+    };
+    // Fused silica (quartz glass), weakly dispersive.
+    pub const FUSED_SILICA: Dispersion = Dispersion::Sellmeier {
+        b: [0.6961663, 0.4079426, 0.8974794],
+        c: [0.00467914826, 0.0135120631, 97.9340025],
+    };
+    // Schott SF11, a dense flint glass with strong dispersion.
+    pub const DENSE_FLINT: Dispersion = Dispersion::Sellmeier {
+        b: [1.73759695, 0.313747346, 1.89878101],
+        c: [0.013188707, 0.0623068142, 155.23629],
+    };
+    // Diamond, n = 2.42 with a lot of fire.
+    pub const DIAMOND: Dispersion = Dispersion::Sellmeier {
+        b: [0.3306, 4.3356, 0.0],
+        c: [0.030625, 0.011236, 0.0],
+    };
+
+    // Wavelength (nm) of the sodium D line, where refractive indices are usually quoted.
+    pub const SODIUM_D: f64 = 589.3;
+
+    // Refractive index at `wavelength` in nm.
+    pub fn ior(&self, wavelength: f64) -> f64 {
+        let l = wavelength / 1000.0;
+        let l2 = l * l;
+        match self {
+            Dispersion::Cauchy { a, b } => a + b / l2,
+            Dispersion::Sellmeier { b, c } => {
+                let sum: f64 = (0..3).map(|i| b[i] * l2 / (l2 - c[i])).sum();
+                (1.0 + sum).sqrt()
+            }
+        }
+    }
+}
+
+impl Dielectric {
+    pub fn new(refraction_index: f64) -> Self {
+        Dielectric {
+            refraction_index,
+            dispersion: None,
+            film: None,
+        }
+    }
+
+    // Glass whose index follows `dispersion`, with its index at the sodium D line for RGB renders.
+    pub fn dispersive(dispersion: Dispersion) -> Self {
+        Dielectric {
+            refraction_index: dispersion.ior(Dispersion::SODIUM_D),
+            dispersion: Some(dispersion),
+            film: None,
+        }
+    }
+
+    pub fn film(mut self, film: ThinFilm) -> Self {
+        self.film = Some(film);
+        self
+    }
+
+    fn reflectance(cosine: f64, refraction_index: f64) -> f64 {
+        // Use Schlick's approximation for reflectance.
+        let r0 = (1.0 - refraction_index) / (1.0 + refraction_index);
+        let r0 = r0 * r0;
+        r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
+    }
+
+    fn refraction_index_at(&self, wavelength: Option<f64>) -> f64 {
+        match (self.dispersion, wavelength) {
+            (Some(dispersion), Some(wavelength)) => dispersion.ior(wavelength),
+            _ => self.refraction_index,
+        }
+    }
+}
+
+impl Material for Dielectric {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, sampler: &mut dyn Sampler) -> Option<ScatterRecord> {
+        let refraction_index = self.refraction_index_at(r_in.wavelength());
+        let ri = if rec.front_face {
+            1.0 / refraction_index
+        } else {
+            refraction_index
+        };
+
+        let unit_direction = Vec3::unit_vector(r_in.direction());
+        let cos_theta = Vec3::dot(&-unit_direction, &rec.normal).min(1.0);
+        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+
+        let cannot_refract = ri * sin_theta > 1.0;
+        let white = Color::new(1.0, 1.0, 1.0);
+        let (reflect, attenuation) = match &self.film {
+            _ if cannot_refract => (true, white),
+            None => (Dielectric::reflectance(cos_theta, ri) > sampler.get_1d(), white),
+            // Colored reflectance: reflect with its mean and weight the channels to match.
+            Some(film) => {
+                let reflectance = film.dielectric_reflectance(rec, cos_theta, r_in.wavelength(), refraction_index);
+                let p = (reflectance.x() + reflectance.y() + reflectance.z()) / 3.0;
+                if p > sampler.get_1d() {
+                    (true, reflectance / p)
+                } else {
+                    (false, (white - reflectance) / (1.0 - p))
+                }
+            }
+        };
+        let direction = if reflect {
+            Vec3::reflect(&unit_direction, &rec.normal)
+        } else {
+            Vec3::refract(&unit_direction, &rec.normal, ri)
+        };
+
+        Some(ScatterRecord {
+            attenuation,
+            ray: Ray::new(rec.p, direction),
+            pdf: 0.0,
+            is_specular: true,
+        })
+    }
+
+    fn eval(&self, _r_in: &Ray, _rec: &HitRecord, _direction: &Vec3) -> Color {
+        Color::default()
+    }
+
+    fn pdf(&self, _r_in: &Ray, _rec: &HitRecord, _direction: &Vec3) -> f64 {
+        0.0
+    }
+
+    fn is_dispersive(&self) -> bool {
+        self.dispersion.is_some() || self.film.is_some()
+    }
+
+    fn hash_into(&self, hasher: &mut SceneHasher) {
+        hasher.write_str("dielectric");
+        hasher.write_f64(self.refraction_index);
+        // Nothing more without dispersion or film, so hashes of existing scenes stay the same.
+        match self.dispersion {
+            None => {}
+            Some(Dispersion::Cauchy { a, b }) => {
+                hasher.write_str("cauchy");
+                hasher.write_f64(a);
+                hasher.write_f64(b);
+            }
+            Some(Dispersion::Sellmeier { b, c }) => {
+                hasher.write_str("sellmeier");
+                for v in b.iter().chain(&c) {
+                    hasher.write_f64(*v);
+                }
+            }
+        }
+        if let Some(film) = &self.film {
+            film.hash_into(hasher);
+        }
+    }
+}
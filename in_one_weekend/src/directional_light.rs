@@ -0,0 +1,39 @@
+use crate::color::Color;
+use crate::light::{Light, LightSample};
+use crate::sampler::Sampler;
+use crate::rtweekend::INFINITY;
+use crate::vec3::{Point3, Vec3};
+use crate::scene_hash::SceneHasher;
+
+// Distant light arriving from a single direction everywhere in the scene, like a sun without a disk.
+// `direction` is the direction the light travels in, `irradiance` what a surface facing it receives.
+pub struct DirectionalLight {
+    direction: Vec3,
+    irradiance: Color,
+}
+
+impl DirectionalLight {
+    pub fn new(direction: Vec3, irradiance: Color) -> Self {
+        DirectionalLight {
+            direction: Vec3::unit_vector(direction),
+            irradiance,
+        }
+    }
+}
+
+impl Light for DirectionalLight {
+    fn sample_li(&self, _p: &Point3, _sampler: &mut dyn Sampler) -> Option<LightSample> {
+        Some(LightSample {
+            direction: -self.direction,
+            distance: INFINITY,
+            radiance: self.irradiance,
+            pdf: 1.0,
+        })
+    }
+
+    fn hash_into(&self, hasher: &mut SceneHasher) {
+        hasher.write_str("directional");
+        hasher.write_vec3(&self.direction);
+        hasher.write_vec3(&self.irradiance);
+    }
+}
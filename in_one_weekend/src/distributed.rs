@@ -0,0 +1,419 @@
+use std::collections::VecDeque;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
+
+use indicatif::ProgressBar;
+
+use crate::camera::Camera;
+use crate::camera_settings::CameraSettings;
+use crate::error::{Error, Result};
+use crate::hittable::Hittable;
+use crate::image::Image;
+use crate::integrator::IntegratorKind;
+use crate::light_list::LightList;
+use crate::scene_hash;
+use crate::tile::Tile;
+
+// Settings for Camera::render_distributed.
+// The image is cut into tiles of `tile_size` pixels that are handed out to the `workers` (host:port
+// of processes running serve_worker with the same scene) one at a time. A worker that disconnects or
+// doesn't answer within `timeout` is dropped and its tile goes back in the queue; when no worker is
+// left, the coordinator renders the remaining tiles itself.
+pub struct DistributedSettings {
+    pub workers: Vec<String>,
+    pub tile_size: u32,
+    pub timeout: Duration,
+}
+
+impl Default for DistributedSettings {
+    fn default() -> Self {
+        DistributedSettings {
+            workers: Vec::new(),
+            tile_size: 32,
+            timeout: Duration::from_secs(120),
+        }
+    }
+}
+
+impl DistributedSettings {
+    pub fn validate(&self) -> Result<()> {
+        if self.tile_size == 0 {
+            return Err(Error::invalid("tile_size", "must be at least 1 pixel"));
+        }
+        if self.timeout.is_zero() {
+            return Err(Error::invalid("timeout", "must be longer than zero"));
+        }
+        Ok(())
+    }
+}
+
+// Protocol: every message is a kind byte, a little endian u32 payload length and the payload.
+// The coordinator sends a job, the worker answers with the hash of its scene (or an error), then
+// tiles and results alternate until the coordinator sends done.
+const MSG_JOB: u8 = 1; // Coordinator -> worker: camera settings as JSON
+const MSG_READY: u8 = 2; // Worker -> coordinator: scene_hash of the worker's scene (u64)
+const MSG_TILE: u8 = 3; // Coordinator -> worker: tile number, x0, y0, x1, y1 (u32)
+const MSG_RESULT: u8 = 4; // Worker -> coordinator: tile number (u32), then Accumulator::write_tile_state
+const MSG_DONE: u8 = 5; // Coordinator -> worker: no more tiles
+const MSG_ERROR: u8 = 6; // Worker -> coordinator: why the job can't be rendered, UTF-8
+
+// Largest job, handshake or tile message accepted, so a corrupt length can't make us allocate the world.
+// Tile results are only accepted at the size of the tile's state, see WorkerLink::render.
+const MAX_MESSAGE: usize = 1 << 16;
+
+// Largest image a worker takes a job for (8192 x 8192 pixels), which keeps its accumulator
+// at a few gigabytes whatever the coordinator sends.
+pub const MAX_JOB_PIXELS: u64 = 1 << 26;
+
+// A coordinator that sends or reads nothing for this long is given up on. Generous, since a
+// coordinator may keep a worker idle while it waits for tiles other workers might give back.
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(600);
+
+// Serves coordinators on `listener`, one connection at a time.
+// Every connection is one render of `world` and `lights` with the camera settings the coordinator sends.
+pub fn serve_worker(listener: &TcpListener, world: &dyn Hittable, lights: &LightList) -> Result<()> {
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                // Out of file descriptors and the like, which may pass; don't spin while it lasts.
+                eprintln!("cannot accept a connection: {}", e);
+                thread::sleep(Duration::from_millis(100));
+                continue;
+            }
+        };
+        let peer = stream.peer_addr().map(|a| a.to_string()).unwrap_or_else(|_| "?".to_string());
+        match serve_connection(stream, world, lights) {
+            Ok(()) => eprintln!("finished the job from {}", peer),
+            Err(e) => eprintln!("job from {} failed: {}", peer, e),
+        }
+    }
+    Ok(())
+}
+
+// Renders the tiles one coordinator asks for, until it sends done or hangs up.
+pub fn serve_connection(stream: TcpStream, world: &dyn Hittable, lights: &LightList) -> Result<()> {
+    stream.set_read_timeout(Some(CONNECTION_TIMEOUT))?;
+    stream.set_write_timeout(Some(CONNECTION_TIMEOUT))?;
+    stream.set_nodelay(true)?;
+    let mut input = BufReader::new(stream.try_clone()?);
+    let mut output = BufWriter::new(stream);
+
+    let (kind, payload) = receive(&mut input, MAX_MESSAGE)?;
+    if kind != MSG_JOB {
+        return Err(protocol_error("expected a job").into());
+    }
+    let camera = match parse_job(&payload) {
+        Ok(camera) => camera,
+        Err(e) => {
+            send(&mut output, MSG_ERROR, e.to_string().as_bytes())?;
+            return Err(e);
+        }
+    };
+    let hash = scene_hash::scene_hash(world, lights, camera.settings());
+    send(&mut output, MSG_READY, &hash.to_le_bytes())?;
+
+    let mut accumulator = camera.create_accumulator();
+    loop {
+        let (kind, payload) = receive(&mut input, MAX_MESSAGE)?;
+        match kind {
+            MSG_TILE => {
+                let (number, tile) = decode_tile(&payload)?;
+                if tile.x0 >= tile.x1 || tile.y0 >= tile.y1 || tile.x1 > camera.image_width() || tile.y1 > camera.image_height()
+                {
+                    return Err(protocol_error("tile outside the image").into());
+                }
+                camera.render_tile(world, lights, &tile, &mut accumulator);
+
+                let mut result = number.to_le_bytes().to_vec();
+                accumulator.write_tile_state(&tile, &mut result)?;
+                accumulator.clear_tile(&tile);
+                send(&mut output, MSG_RESULT, &result)?;
+            }
+            MSG_DONE => return Ok(()),
+            _ => return Err(protocol_error("unexpected message").into()),
+        }
+    }
+}
+
+pub(crate) fn coordinate(
+    camera: &Camera,
+    world: &dyn Hittable,
+    lights: &LightList,
+    settings: &DistributedSettings,
+) -> Result<Image> {
+    settings.validate()?;
+    if matches!(camera.settings().integrator, IntegratorKind::Bidirectional | IntegratorKind::Metropolis) {
+        // Light tracing adds to pixels outside the tile being sampled, which tiles don't carry,
+        // and Metropolis chains wander over the whole image.
+        return Err(Error::invalid(
+            "integrator",
+            format!("{} renders can't be distributed", camera.settings().integrator),
+        ));
+    }
+    let job = serde_json::to_vec(camera.settings()).map_err(|e| Error::Settings(e.to_string()))?;
+    let hash = scene_hash::scene_hash(world, lights, camera.settings());
+
+    let mut accumulator = camera.create_accumulator();
+    let tiles = Tile::grid(camera.image_width(), camera.image_height(), settings.tile_size);
+    let result_sizes: Vec<usize> = tiles.iter().map(|tile| accumulator.tile_state_size(tile)).collect();
+    let queue = TileQueue {
+        state: Mutex::new(QueueState {
+            pending: (0..tiles.len()).collect(),
+            finished: false,
+        }),
+        changed: Condvar::new(),
+    };
+
+    let bar = ProgressBar::new(tiles.len() as u64);
+    let mut done = vec![false; tiles.len()];
+    let mut remaining = tiles.len();
+
+    thread::scope(|scope| {
+        let (events_tx, events) = mpsc::channel();
+        for address in &settings.workers {
+            let link = WorkerLink {
+                address,
+                job: &job,
+                scene_hash: hash,
+                timeout: settings.timeout,
+                tiles: &tiles,
+                result_sizes: &result_sizes,
+                queue: &queue,
+                events: events_tx.clone(),
+            };
+            scope.spawn(move || link.run());
+        }
+        // Only the worker threads hold senders now, so receiving fails once all of them are gone.
+        drop(events_tx);
+        let mut workers_left = !settings.workers.is_empty();
+
+        let mut gather = || -> Result<()> {
+            while remaining > 0 {
+                let event = if workers_left { events.recv().ok() } else { None };
+                match event {
+                    Some(Event::Result { tile, data }) => {
+                        // A tile is only ever merged once, even if it was handed out twice.
+                        if !done[tile] {
+                            accumulator.merge_tile_state(&tiles[tile], &mut data.as_slice())?;
+                            done[tile] = true;
+                            remaining -= 1;
+                            bar.inc(1);
+                        }
+                    }
+                    Some(Event::Lost { address, reason }) => {
+                        bar.suspend(|| eprintln!("worker {} lost: {}", address, reason));
+                    }
+                    None => {
+                        // Nobody left to hand work to, render what's left here.
+                        workers_left = false;
+                        let Some(tile) = lock(&queue.state).pending.pop_front() else {
+                            return Err(Error::invalid("workers", "tiles went missing after losing the workers"));
+                        };
+                        if !done[tile] {
+                            camera.render_tile(world, lights, &tiles[tile], &mut accumulator);
+                            done[tile] = true;
+                            remaining -= 1;
+                            bar.inc(1);
+                        }
+                    }
+                }
+            }
+            Ok(())
+        };
+        let outcome = gather();
+        // Lets the worker threads tell their workers they're done (also after an error),
+        // the scope waits for them.
+        lock(&queue.state).finished = true;
+        queue.changed.notify_all();
+        outcome
+    })?;
+    bar.finish();
+
+    Ok(accumulator.to_image())
+}
+
+// The tiles no worker has taken yet, or that were given back by a worker that was lost.
+// `changed` is signalled when a tile is given back and when the render is finished.
+struct TileQueue {
+    state: Mutex<QueueState>,
+    changed: Condvar,
+}
+
+struct QueueState {
+    pending: VecDeque<usize>,
+    finished: bool,
+}
+
+enum Event {
+    Result { tile: usize, data: Vec<u8> },
+    Lost { address: String, reason: String },
+}
+
+// The coordinator's side of one worker connection, run on its own thread.
+struct WorkerLink<'a> {
+    address: &'a str,
+    job: &'a [u8],
+    scene_hash: u64,
+    timeout: Duration,
+    tiles: &'a [Tile],
+    result_sizes: &'a [usize],
+    queue: &'a TileQueue,
+    events: Sender<Event>,
+}
+
+impl WorkerLink<'_> {
+    fn run(&self) {
+        let (mut input, mut output) = match self.connect() {
+            Ok(streams) => streams,
+            Err(e) => return self.lost(e),
+        };
+        while let Some(tile) = self.next_tile() {
+            match self.render(tile, &mut input, &mut output) {
+                Ok(data) => {
+                    let _ = self.events.send(Event::Result { tile, data });
+                }
+                Err(e) => {
+                    lock(&self.queue.state).pending.push_front(tile);
+                    self.queue.changed.notify_one();
+                    return self.lost(e);
+                }
+            }
+        }
+        let _ = send(&mut output, MSG_DONE, &[]);
+    }
+
+    fn connect(&self) -> io::Result<(BufReader<TcpStream>, BufWriter<TcpStream>)> {
+        let address = self
+            .address
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| protocol_error("address doesn't resolve"))?;
+        let stream = TcpStream::connect_timeout(&address, self.timeout)?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+        stream.set_nodelay(true)?;
+        let mut input = BufReader::new(stream.try_clone()?);
+        let mut output = BufWriter::new(stream);
+
+        send(&mut output, MSG_JOB, self.job)?;
+        match receive(&mut input, MAX_MESSAGE)? {
+            (MSG_READY, payload) if payload.len() == 8 => {
+                let hash = u64::from_le_bytes(read_array(&payload)?);
+                if hash != self.scene_hash {
+                    return Err(protocol_error("the worker renders a different scene"));
+                }
+            }
+            (MSG_ERROR, payload) => return Err(protocol_error(&String::from_utf8_lossy(&payload))),
+            _ => return Err(protocol_error("unexpected answer to the job")),
+        }
+        Ok((input, output))
+    }
+
+    fn render(&self, tile: usize, input: &mut dyn Read, output: &mut dyn Write) -> io::Result<Vec<u8>> {
+        send(output, MSG_TILE, &encode_tile(tile as u32, &self.tiles[tile]))?;
+        let (kind, mut payload) = receive(input, 4 + self.result_sizes[tile])?;
+        if kind != MSG_RESULT || payload.len() != 4 + self.result_sizes[tile] {
+            return Err(protocol_error("malformed tile result"));
+        }
+        if u32::from_le_bytes(read_array(&payload)?) as usize != tile {
+            return Err(protocol_error("result for the wrong tile"));
+        }
+        payload.drain(..4);
+        Ok(payload)
+    }
+
+    // The next tile to render, waiting while other workers may still give tiles back.
+    fn next_tile(&self) -> Option<usize> {
+        let mut state = lock(&self.queue.state);
+        loop {
+            if let Some(tile) = state.pending.pop_front() {
+                return Some(tile);
+            }
+            if state.finished {
+                return None;
+            }
+            state = self.queue.changed.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    fn lost(&self, e: io::Error) {
+        let _ = self.events.send(Event::Lost {
+            address: self.address.to_string(),
+            reason: e.to_string(),
+        });
+    }
+}
+
+fn parse_job(payload: &[u8]) -> Result<Camera> {
+    let settings: CameraSettings = serde_json::from_slice(payload).map_err(|e| Error::Settings(e.to_string()))?;
+    let camera = Camera::new(settings)?;
+    let pixels = camera.image_width() as u64 * camera.image_height() as u64;
+    if pixels > MAX_JOB_PIXELS {
+        return Err(Error::invalid(
+            "image_width",
+            format!("{} x {} pixels is more than a worker renders", camera.image_width(), camera.image_height()),
+        ));
+    }
+    Ok(camera)
+}
+
+fn send(output: &mut dyn Write, kind: u8, payload: &[u8]) -> io::Result<()> {
+    let len = u32::try_from(payload.len()).map_err(|_| protocol_error("message too large"))?;
+    output.write_all(&[kind])?;
+    output.write_all(&len.to_le_bytes())?;
+    output.write_all(payload)?;
+    output.flush()
+}
+
+// Reads a message with a payload of at most `max_len` bytes.
+fn receive(input: &mut dyn Read, max_len: usize) -> io::Result<(u8, Vec<u8>)> {
+    let mut header = [0u8; 5];
+    input.read_exact(&mut header)?;
+    let len = u32::from_le_bytes(read_array(&header[1..])?);
+    if len as usize > max_len {
+        return Err(protocol_error("message too large"));
+    }
+    let mut payload = vec![0u8; len as usize];
+    input.read_exact(&mut payload)?;
+    Ok((header[0], payload))
+}
+
+fn encode_tile(number: u32, tile: &Tile) -> Vec<u8> {
+    [number, tile.x0, tile.y0, tile.x1, tile.y1]
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect()
+}
+
+fn decode_tile(payload: &[u8]) -> io::Result<(u32, Tile)> {
+    if payload.len() != 20 {
+        return Err(protocol_error("malformed tile"));
+    }
+    let mut values = [0u32; 5];
+    for (value, bytes) in values.iter_mut().zip(payload.chunks_exact(4)) {
+        *value = u32::from_le_bytes(read_array(bytes)?);
+    }
+    Ok((values[0], Tile::new(values[1], values[2], values[3], values[4])))
+}
+
+// The first N bytes of `bytes` as an array.
+fn read_array<const N: usize>(bytes: &[u8]) -> io::Result<[u8; N]> {
+    bytes
+        .get(..N)
+        .and_then(|b| b.try_into().ok())
+        .ok_or_else(|| protocol_error("message too short"))
+}
+
+fn protocol_error(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+// The queue stays usable even if a worker thread panicked while holding it.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
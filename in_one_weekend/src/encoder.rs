@@ -0,0 +1,52 @@
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::enum_names::enum_names;
+use crate::hdr_encoder::HdrEncoder;
+use crate::image::Image;
+use crate::png_encoder::PngEncoder;
+use crate::ppm_encoder::PpmEncoder;
+use crate::tonemap::ToneMapping;
+
+// Turns an Image into the bytes of an image file.
+pub trait Encoder {
+    fn encode(&self, image: &Image, out: &mut dyn Write) -> io::Result<()>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    PpmAscii,  // P3, the format the renderer always produced
+    PpmBinary, // P6
+    Png,
+    Hdr, // Radiance RGBE, keeps the linear values
+}
+
+enum_names!(ImageFormat, "image format", {
+    PpmAscii => "ppm",
+    PpmBinary => "ppm-binary",
+    Png => "png",
+    Hdr => "hdr",
+});
+
+impl ImageFormat {
+    // Guesses the format from the file extension; .ppm means P3.
+    pub fn from_path(path: &Path) -> Option<ImageFormat> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "ppm" => Some(ImageFormat::PpmAscii),
+            "png" => Some(ImageFormat::Png),
+            "hdr" => Some(ImageFormat::Hdr),
+            _ => None,
+        }
+    }
+
+    pub fn encoder(&self, tone_mapping: ToneMapping) -> Box<dyn Encoder> {
+        match self {
+            ImageFormat::PpmAscii => Box::new(PpmEncoder::new(false, tone_mapping)),
+            ImageFormat::PpmBinary => Box::new(PpmEncoder::new(true, tone_mapping)),
+            ImageFormat::Png => Box::new(PngEncoder::new(tone_mapping)),
+            ImageFormat::Hdr => Box::new(HdrEncoder::new()),
+        }
+    }
+}
+
@@ -0,0 +1,42 @@
+// Names for the values of the option enums (samplers, filters, integrators, ...), as used on the
+// command line, in settings files and in messages:
+//
+//     enum_names!(FilterKind, "filter", {
+//         Box => "box",
+//         Tent => "tent",
+//     });
+//
+// gives the enum `ALL` (its values in order), `name()`, a Display that writes the name, and a FromStr
+// that parses it and lists the known names when it doesn't know one.
+macro_rules! enum_names {
+    ($kind:ident, $what:literal, { $($value:ident => $name:literal),+ $(,)? }) => {
+        impl $kind {
+            pub const ALL: [$kind; [$($name),+].len()] = [$($kind::$value),+];
+
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $($kind::$value => $name),+
+                }
+            }
+        }
+
+        impl std::fmt::Display for $kind {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(f, "{}", self.name())
+            }
+        }
+
+        impl std::str::FromStr for $kind {
+            type Err = String;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                $kind::ALL.into_iter().find(|value| value.name() == s).ok_or_else(|| {
+                    let names: Vec<&str> = $kind::ALL.iter().map(|value| value.name()).collect();
+                    format!("unknown {} '{}', expected one of: {}", $what, s, names.join(", "))
+                })
+            }
+        }
+    };
+}
+
+pub(crate) use enum_names;
@@ -0,0 +1,73 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+// Errors returned by the renderer's public API.
+#[derive(Debug)]
+pub enum Error {
+    // Reading or writing a file (or stdout) failed.
+    Io(io::Error),
+    // Like Io, but for a named file, so the message can say which one.
+    File { path: PathBuf, source: io::Error },
+    // A setting is outside the range the renderer can work with.
+    InvalidParameter { name: &'static str, reason: String },
+    // The output file extension doesn't name a supported image format.
+    UnknownImageFormat(PathBuf),
+    // A settings file (or string) couldn't be read or written as JSON / TOML.
+    Settings(String),
+    // A checkpoint file is damaged or belongs to another render.
+    Checkpoint(String),
+    // The render was stopped through its RenderStatus.
+    Cancelled,
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub fn file(path: impl Into<PathBuf>, source: io::Error) -> Self {
+        Error::File {
+            path: path.into(),
+            source,
+        }
+    }
+
+    pub fn invalid(name: &'static str, reason: impl Into<String>) -> Self {
+        Error::InvalidParameter {
+            name,
+            reason: reason.into(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::File { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::InvalidParameter { name, reason } => write!(f, "invalid {}: {}", name, reason),
+            Error::UnknownImageFormat(path) => write!(
+                f,
+                "unknown image format for '{}', expected .ppm, .png or .hdr",
+                path.display()
+            ),
+            Error::Settings(reason) => write!(f, "invalid settings: {}", reason),
+            Error::Checkpoint(reason) => write!(f, "cannot resume: {}", reason),
+            Error::Cancelled => write!(f, "the render was cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) | Error::File { source: e, .. } => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
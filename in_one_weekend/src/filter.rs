@@ -0,0 +1,206 @@
+use serde::{Deserialize, Serialize};
+
+use crate::enum_names::enum_names;
+use crate::rtweekend::PI;
+
+// Pixel reconstruction filter.
+// Every sample is splatted into all pixels whose filter footprint covers it, weighted by
+// `evaluate` at the offset between the sample and the pixel center; each pixel is then
+// normalised by the sum of the weights it received.
+pub trait Filter {
+    // Half-width of the filter support, in pixels.
+    fn radius(&self) -> f64;
+    // Weight of a sample at offset x, y (in pixels) from the pixel center.
+    fn evaluate(&self, x: f64, y: f64) -> f64;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FilterKind {
+    #[default]
+    Box,
+    Tent,
+    Gaussian,
+    Mitchell,
+    Lanczos,
+}
+
+enum_names!(FilterKind, "filter", {
+    Box => "box",
+    Tent => "tent",
+    Gaussian => "gaussian",
+    Mitchell => "mitchell",
+    Lanczos => "lanczos",
+});
+
+impl FilterKind {
+    // Builds the filter with the given radius, or its usual radius when None.
+    pub fn create(&self, radius: Option<f64>) -> Box<dyn Filter> {
+        let radius = radius.unwrap_or(self.default_radius()).max(0.5);
+        match self {
+            FilterKind::Box => Box::new(BoxFilter { radius }),
+            FilterKind::Tent => Box::new(TentFilter { radius }),
+            FilterKind::Gaussian => Box::new(GaussianFilter::new(radius)),
+            FilterKind::Mitchell => Box::new(MitchellFilter::new(radius)),
+            FilterKind::Lanczos => Box::new(LanczosFilter { radius }),
+        }
+    }
+
+    pub fn default_radius(&self) -> f64 {
+        match self {
+            FilterKind::Box => 0.5,
+            FilterKind::Tent => 1.0,
+            FilterKind::Gaussian => 1.5,
+            FilterKind::Mitchell => 2.0,
+            FilterKind::Lanczos => 3.0,
+        }
+    }
+}
+
+// Equal weight over the whole support. With radius 0.5 this is a plain average of the pixel's own samples:
+// the support is half open like the pixel, a sample on the edge between two pixels only counts in one.
+pub struct BoxFilter {
+    radius: f64,
+}
+
+impl Filter for BoxFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+    fn evaluate(&self, x: f64, y: f64) -> f64 {
+        let inside = |t: f64| -self.radius <= t && t < self.radius;
+        if inside(x) && inside(y) { 1.0 } else { 0.0 }
+    }
+}
+
+// Linear falloff to zero at the radius (a.k.a. triangle filter).
+pub struct TentFilter {
+    radius: f64,
+}
+
+impl Filter for TentFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+    fn evaluate(&self, x: f64, y: f64) -> f64 {
+        (self.radius - x.abs()).max(0.0) * (self.radius - y.abs()).max(0.0)
+    }
+}
+
+// Gaussian with sigma = radius / 3, shifted down so it reaches exactly zero at the radius.
+pub struct GaussianFilter {
+    radius: f64,
+    alpha: f64,
+    edge: f64,
+}
+
+impl GaussianFilter {
+    pub fn new(radius: f64) -> Self {
+        let sigma = radius / 3.0;
+        let alpha = 1.0 / (2.0 * sigma * sigma);
+        GaussianFilter {
+            radius,
+            alpha,
+            edge: (-alpha * radius * radius).exp(),
+        }
+    }
+
+    fn gaussian(&self, d: f64) -> f64 {
+        ((-self.alpha * d * d).exp() - self.edge).max(0.0)
+    }
+}
+
+impl Filter for GaussianFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+    fn evaluate(&self, x: f64, y: f64) -> f64 {
+        self.gaussian(x) * self.gaussian(y)
+    }
+}
+
+// Mitchell-Netravali cubic with B = C = 1/3, a good trade-off between blurring and ringing.
+// The cubic is defined on [-2, 2] and stretched to the filter radius.
+pub struct MitchellFilter {
+    radius: f64,
+    b: f64,
+    c: f64,
+}
+
+impl MitchellFilter {
+    pub fn new(radius: f64) -> Self {
+        MitchellFilter {
+            radius,
+            b: 1.0 / 3.0,
+            c: 1.0 / 3.0,
+        }
+    }
+
+    fn mitchell_1d(&self, x: f64) -> f64 {
+        let x = (2.0 * x / self.radius).abs();
+        let (b, c) = (self.b, self.c);
+        if x > 2.0 {
+            0.0
+        } else if x > 1.0 {
+            ((-b - 6.0 * c) * x * x * x + (6.0 * b + 30.0 * c) * x * x + (-12.0 * b - 48.0 * c) * x
+                + (8.0 * b + 24.0 * c))
+                / 6.0
+        } else {
+            ((12.0 - 9.0 * b - 6.0 * c) * x * x * x + (-18.0 + 12.0 * b + 6.0 * c) * x * x + (6.0 - 2.0 * b)) / 6.0
+        }
+    }
+}
+
+impl Filter for MitchellFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+    fn evaluate(&self, x: f64, y: f64) -> f64 {
+        self.mitchell_1d(x) * self.mitchell_1d(y)
+    }
+}
+
+// Sinc windowed by a wider sinc; the radius is also the number of lobes.
+pub struct LanczosFilter {
+    radius: f64,
+}
+
+impl LanczosFilter {
+    fn lanczos_1d(&self, x: f64) -> f64 {
+        if x.abs() >= self.radius {
+            return 0.0;
+        }
+        sinc(x) * sinc(x / self.radius)
+    }
+}
+
+impl Filter for LanczosFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+    fn evaluate(&self, x: f64, y: f64) -> f64 {
+        self.lanczos_1d(x) * self.lanczos_1d(y)
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-5 {
+        return 1.0;
+    }
+    (PI * x).sin() / (PI * x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn box_filter_covers_only_its_own_pixel() {
+        // A sample on the corner of pixel 1, 1 is at -0.5 from its center and +0.5 from its neighbours'.
+        let filter = FilterKind::Box.create(None);
+        assert_eq!(filter.evaluate(-0.5, -0.5), 1.0);
+        assert_eq!(filter.evaluate(0.5, -0.5), 0.0);
+        assert_eq!(filter.evaluate(-0.5, 0.5), 0.0);
+        assert_eq!(filter.evaluate(0.5, 0.5), 0.0);
+    }
+}
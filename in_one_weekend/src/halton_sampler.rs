@@ -0,0 +1,75 @@
+use crate::sampler::{self, Sampler};
+
+const PRIMES: [u32; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97, 101, 103,
+    107, 109, 113, 127, 131,
+];
+
+// Halton sequence: dimension d is the radical inverse of the sample index in the d-th prime base.
+// Each pixel gets its own random (Cranley-Patterson) rotation per dimension so neighbouring pixels
+// don't reuse the same points. Dimensions past the table wrap around to the first primes, relying
+// on the rotation to decorrelate them.
+pub struct HaltonSampler {
+    seed: u64,
+    pixel: (u32, u32),
+    sample_index: u32,
+    dimension: u32,
+}
+
+impl HaltonSampler {
+    pub fn new(seed: u64) -> Self {
+        HaltonSampler {
+            seed,
+            pixel: (0, 0),
+            sample_index: 0,
+            dimension: 0,
+        }
+    }
+
+    fn sample_dimension(&mut self) -> f64 {
+        let base = PRIMES[self.dimension as usize % PRIMES.len()];
+        let value = radical_inverse(base, self.sample_index as u64);
+        let offset = sampler::hash_to_unit(sampler::hash_pixel(self.pixel.0, self.pixel.1, self.dimension, self.seed));
+        self.dimension += 1;
+        (value + offset).fract()
+    }
+}
+
+impl Default for HaltonSampler {
+    fn default() -> Self {
+        HaltonSampler::new(0)
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn start_pixel_sample(&mut self, i: u32, j: u32, sample_index: u32) {
+        self.pixel = (i, j);
+        self.sample_index = sample_index;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        self.sample_dimension()
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let x = self.sample_dimension();
+        let y = self.sample_dimension();
+        (x, y)
+    }
+}
+
+// Mirrors the digits of `a` written in `base` around the radix point.
+pub fn radical_inverse(base: u32, mut a: u64) -> f64 {
+    let inv_base = 1.0 / base as f64;
+    let mut inv_base_m = 1.0;
+    let mut reversed: u64 = 0;
+    while a > 0 {
+        let next = a / base as u64;
+        let digit = a - next * base as u64;
+        reversed = reversed * base as u64 + digit;
+        inv_base_m *= inv_base;
+        a = next;
+    }
+    (reversed as f64 * inv_base_m).min(1.0 - f64::EPSILON)
+}
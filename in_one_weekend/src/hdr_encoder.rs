@@ -0,0 +1,103 @@
+use std::io::{self, Write};
+
+use crate::encoder::Encoder;
+use crate::image::Image;
+
+// Radiance HDR (RGBE) with run-length encoded scanlines.
+// Values are written linear and untouched by tone mapping.
+pub struct HdrEncoder;
+
+impl HdrEncoder {
+    pub fn new() -> Self {
+        HdrEncoder
+    }
+}
+
+impl Default for HdrEncoder {
+    fn default() -> Self {
+        HdrEncoder::new()
+    }
+}
+
+impl Encoder for HdrEncoder {
+    fn encode(&self, image: &Image, out: &mut dyn Write) -> io::Result<()> {
+        write!(out, "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n")?;
+        writeln!(out, "-Y {} +X {}", image.height(), image.width())?;
+
+        let width = image.width() as usize;
+        // The RLE scheme only exists for these widths; other scanlines are written flat.
+        let run_length = (8..=0x7fff).contains(&width);
+
+        let mut scanline = vec![[0u8; 4]; width];
+        for j in 0..image.height() {
+            for (i, rgbe) in scanline.iter_mut().enumerate() {
+                let [r, g, b] = image.pixels()[j as usize * width + i];
+                *rgbe = to_rgbe(r, g, b);
+            }
+
+            if !run_length {
+                for rgbe in &scanline {
+                    out.write_all(rgbe)?;
+                }
+                continue;
+            }
+
+            out.write_all(&[2, 2, (width >> 8) as u8, (width & 0xff) as u8])?;
+            for channel in 0..4 {
+                let bytes: Vec<u8> = scanline.iter().map(|rgbe| rgbe[channel]).collect();
+                write_rle(out, &bytes)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Shared exponent encoding: the mantissas of the three channels use the largest channel's exponent.
+fn to_rgbe(r: f32, g: f32, b: f32) -> [u8; 4] {
+    let (r, g, b) = (r.max(0.0), g.max(0.0), b.max(0.0));
+    let v = r.max(g).max(b);
+    if v < 1e-32 {
+        return [0, 0, 0, 0];
+    }
+    // v = m * 2^e with m in [0.5, 1)
+    let e = v.log2().floor() as i32 + 1;
+    let scale = 256.0 / 2f32.powi(e);
+    [
+        (r * scale).min(255.0) as u8,
+        (g * scale).min(255.0) as u8,
+        (b * scale).min(255.0) as u8,
+        (e + 128) as u8,
+    ]
+}
+
+// One channel of a scanline: runs of 4 or more equal bytes become (128 + count, value),
+// everything else is copied as (count, bytes...). Both kinds hold at most 127/128 bytes.
+fn write_rle(out: &mut dyn Write, bytes: &[u8]) -> io::Result<()> {
+    const MIN_RUN: usize = 4;
+    let run_at = |pos: usize| {
+        let mut len = 1;
+        while pos + len < bytes.len() && bytes[pos + len] == bytes[pos] && len < 127 {
+            len += 1;
+        }
+        len
+    };
+
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let run = run_at(pos);
+        if run >= MIN_RUN {
+            out.write_all(&[128 + run as u8, bytes[pos]])?;
+            pos += run;
+            continue;
+        }
+
+        // Literal block up to the next run worth encoding.
+        let start = pos;
+        while pos < bytes.len() && pos - start < 128 && run_at(pos) < MIN_RUN {
+            pos += 1;
+        }
+        out.write_all(&[(pos - start) as u8])?;
+        out.write_all(&bytes[start..pos])?;
+    }
+    Ok(())
+}
@@ -1,11 +1,19 @@
+use std::sync::Arc;
+
+use crate::material::Material;
+use crate::scene_hash::SceneHasher;
 use crate::vec3::{Point3, Vec3};
-use crate::{Ray, interval::Interval};
+use crate::{ray::Ray, interval::Interval};
 
 #[derive(Clone)]
 pub struct HitRecord {
     pub p: Point3,
     pub normal: Vec3,
+    pub mat: Option<Arc<dyn Material>>,
     pub t: f64,
+    pub u: f64, // Surface coordinates of the hit point, in [0, 1], for textures
+    pub v: f64,
+    pub tangent: Vec3, // Unit direction of increasing u in the tangent plane, for anisotropic materials; zero if there is none
     pub front_face: bool,
 }
 impl HitRecord {
@@ -14,7 +22,7 @@ impl HitRecord {
         // NOTE: the parameter `outward_normal` is assumed to have unit length.
 
         self.front_face = Vec3::dot(&r.direction(), outward_normal) < 0.0;
-        if self.front_face == true {
+        if self.front_face {
             self.normal = *outward_normal;
         } else {
             self.normal = -*outward_normal;
@@ -26,12 +34,22 @@ impl Default for HitRecord {
         HitRecord {
             p: Point3::default(),
             normal: Vec3::default(),
+            mat: None,
             t: 0.0,
+            u: 0.0,
+            v: 0.0,
+            tangent: Vec3::default(),
             front_face: false,
         }
     }
 }
 
-pub trait Hittable {
+pub trait Hittable: Send + Sync {
     fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool;
+
+    // Feeds everything that affects how the object looks (geometry and material) into `hasher`,
+    // see scene_hash. The default only tells types apart.
+    fn hash_into(&self, hasher: &mut SceneHasher) {
+        hasher.write_str(std::any::type_name::<Self>());
+    }
 }
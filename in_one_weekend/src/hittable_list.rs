@@ -1,6 +1,8 @@
+use std::hash::Hasher;
+
 use crate::hittable::{HitRecord, Hittable};
-use crate::hittable_list;
 use crate::{ray::Ray, interval::Interval};
+use crate::scene_hash::SceneHasher;
 
 pub struct HittableList {
     pub objects: Vec<Box<dyn Hittable>>,
@@ -12,17 +14,17 @@ impl HittableList {
             objects: Vec::new(),
         }
     }
-    pub fn new_list(&mut self, object: Box<dyn Hittable>) {
-        self.add(object)
-    }
-    pub fn clear(&mut self) {
-        self.objects.clear();
-    }
     pub fn add(&mut self, object: Box<dyn Hittable>) {
         self.objects.push(object);
     }
 }
 
+impl Default for HittableList {
+    fn default() -> Self {
+        HittableList::new()
+    }
+}
+
 impl Hittable for HittableList {
     fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
         let mut temp_rec = HitRecord::default();
@@ -38,4 +40,11 @@ impl Hittable for HittableList {
         }
         hit_anything
     }
+
+    fn hash_into(&self, hasher: &mut SceneHasher) {
+        hasher.write_usize(self.objects.len());
+        for object in &self.objects {
+            object.hash_into(hasher);
+        }
+    }
 }
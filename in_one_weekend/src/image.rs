@@ -0,0 +1,83 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::color::Color;
+use crate::encoder::ImageFormat;
+use crate::error::{Error, Result};
+use crate::tonemap::ToneMapping;
+
+// Framebuffer of linear RGB pixels, stored as f32 and addressed by column i and row j
+// (row 0 is the top of the image).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    width: u32,
+    height: u32,
+    pixels: Vec<[f32; 3]>,
+}
+
+impl Image {
+    // A black image.
+    pub fn new(width: u32, height: u32) -> Self {
+        Image {
+            width,
+            height,
+            pixels: vec![[0.0; 3]; width as usize * height as usize],
+        }
+    }
+}
+
+//getters
+impl Image {
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+    pub fn pixel(&self, i: u32, j: u32) -> Color {
+        let [r, g, b] = self.pixels[self.index(i, j)];
+        Color::new(r as f64, g as f64, b as f64)
+    }
+    // Raw pixel data, row major from the top left.
+    pub fn pixels(&self) -> &[[f32; 3]] {
+        &self.pixels
+    }
+}
+
+//utils
+impl Image {
+    pub fn set_pixel(&mut self, i: u32, j: u32, color: &Color) {
+        let idx = self.index(i, j);
+        self.pixels[idx] = [color.x() as f32, color.y() as f32, color.z() as f32];
+    }
+
+    // Encodes the image in the format matching the file extension and writes it to `path`.
+    // `tone_mapping` is used by the 8-bit formats; HDR output stays linear.
+    pub fn save(&self, path: &Path, tone_mapping: &ToneMapping) -> Result<()> {
+        let format = ImageFormat::from_path(path).ok_or_else(|| Error::UnknownImageFormat(path.to_path_buf()))?;
+        self.save_as(path, format, tone_mapping)
+    }
+
+    pub fn save_as(&self, path: &Path, format: ImageFormat, tone_mapping: &ToneMapping) -> Result<()> {
+        let write = || {
+            let mut out = BufWriter::new(File::create(path)?);
+            format.encoder(*tone_mapping).encode(self, &mut out)?;
+            out.flush()
+        };
+        write().map_err(|e| Error::file(path, e))
+    }
+
+    // Color of pixel i, j, or None outside the image.
+    pub fn get_pixel(&self, i: u32, j: u32) -> Option<Color> {
+        if i >= self.width || j >= self.height {
+            return None;
+        }
+        Some(self.pixel(i, j))
+    }
+
+    fn index(&self, i: u32, j: u32) -> usize {
+        assert!(i < self.width && j < self.height, "pixel ({}, {}) outside {}x{} image", i, j, self.width, self.height);
+        (j * self.width + i) as usize
+    }
+}
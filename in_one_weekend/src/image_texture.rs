@@ -0,0 +1,72 @@
+use std::fs::File;
+use std::hash::Hasher;
+use std::io::{self, BufReader};
+use std::path::Path;
+
+use crate::color::{self, Color};
+use crate::error::{Error, Result};
+use crate::scene_hash::SceneHasher;
+use crate::texture::Texture;
+use crate::vec3::Point3;
+
+// Texture read from a PNG file, mapped over u, v with v = 1 at the top row of the image.
+// Color images are sRGB encoded and converted to linear values when loaded; pass `srgb = false`
+// for data stored directly, like roughness or metallic maps.
+pub struct ImageTexture {
+    width: u32,
+    height: u32,
+    pixels: Vec<Color>,
+}
+
+impl ImageTexture {
+    pub fn load(path: &Path, srgb: bool) -> Result<Self> {
+        let file = File::open(path).map_err(|e| Error::file(path, e))?;
+        let mut decoder = png::Decoder::new(BufReader::new(file));
+        // Palettes and low bit depths become 8-bit channels, 16-bit channels are cut to 8.
+        decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+        let mut reader = decoder.read_info().map_err(|e| Error::file(path, io::Error::other(e)))?;
+        let mut data = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut data).map_err(|e| Error::file(path, io::Error::other(e)))?;
+
+        let channels = info.color_type.samples();
+        let decode = |byte: u8| {
+            let value = byte as f64 / 255.0;
+            if srgb { color::srgb_to_linear(value) } else { value }
+        };
+        let pixels = data[..info.buffer_size()]
+            .chunks_exact(channels)
+            .map(|pixel| match channels {
+                // Grey (and grey with alpha) or RGB (and RGBA); alpha is ignored.
+                1 | 2 => Color::new(decode(pixel[0]), decode(pixel[0]), decode(pixel[0])),
+                _ => Color::new(decode(pixel[0]), decode(pixel[1]), decode(pixel[2])),
+            })
+            .collect();
+
+        Ok(ImageTexture {
+            width: info.width,
+            height: info.height,
+            pixels,
+        })
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: &Point3) -> Color {
+        // Clamp input texture coordinates to [0,1] x [1,0]
+        let u = u.clamp(0.0, 1.0);
+        let v = 1.0 - v.clamp(0.0, 1.0); // Flip V to image coordinates
+
+        let i = ((u * self.width as f64) as u32).min(self.width - 1);
+        let j = ((v * self.height as f64) as u32).min(self.height - 1);
+        self.pixels[(j * self.width + i) as usize]
+    }
+
+    fn hash_into(&self, hasher: &mut SceneHasher) {
+        hasher.write_str("image");
+        hasher.write_u32(self.width);
+        hasher.write_u32(self.height);
+        for pixel in &self.pixels {
+            hasher.write_vec3(pixel);
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use crate::sampler::{self, Sampler};
+
+// Uniform random numbers for every dimension, the behaviour of the original renderer.
+// The numbers are hashed from the pixel, sample and dimension rather than drawn from a
+// generator, so a render can be repeated exactly from its seed.
+pub struct IndependentSampler {
+    seed: u64,
+    pixel: (u32, u32),
+    sample_index: u32,
+    dimension: u32,
+}
+
+impl IndependentSampler {
+    pub fn new(seed: u64) -> Self {
+        IndependentSampler {
+            seed,
+            pixel: (0, 0),
+            sample_index: 0,
+            dimension: 0,
+        }
+    }
+}
+
+impl Default for IndependentSampler {
+    fn default() -> Self {
+        IndependentSampler::new(0)
+    }
+}
+
+impl Sampler for IndependentSampler {
+    fn start_pixel_sample(&mut self, i: u32, j: u32, sample_index: u32) {
+        self.pixel = (i, j);
+        self.sample_index = sample_index;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let value = sampler::sample_hash_to_unit(self.pixel.0, self.pixel.1, self.sample_index, self.dimension, self.seed);
+        self.dimension += 1;
+        value
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let x = self.get_1d();
+        let y = self.get_1d();
+        (x, y)
+    }
+}
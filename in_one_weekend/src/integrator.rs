@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+
+use crate::enum_names::enum_names;
+
+// How a Camera estimates the light arriving through each pixel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IntegratorKind {
+    // Paths from the camera, sampling the lights at every diffuse or glossy bounce.
+    #[default]
+    PathTracing,
+    // Paths from the camera and from a light, connected in every possible way, see bdpt.rs.
+    Bidirectional,
+    // Path tracing, with the caustics of point and spot lights from a photon map, see photon_mapping.rs.
+    PhotonMapping,
+    // Photon mapping with a new photon map and a smaller radius every few samples, which converges.
+    ProgressivePhotonMapping,
+    // Path tracing with the paths found by Markov chains that mutate bright paths, see mlt.rs.
+    Metropolis,
+}
+
+enum_names!(IntegratorKind, "integrator", {
+    PathTracing => "path-tracing",
+    Bidirectional => "bidirectional",
+    PhotonMapping => "photon-mapping",
+    ProgressivePhotonMapping => "progressive-photon-mapping",
+    Metropolis => "metropolis",
+});
+
+impl IntegratorKind {
+    pub fn is_default(&self) -> bool {
+        *self == IntegratorKind::default()
+    }
+}
+
@@ -0,0 +1,53 @@
+use crate::color::Color;
+use crate::hittable::HitRecord;
+use crate::material::{Material, ScatterRecord};
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::rtweekend::PI;
+use crate::sampler::Sampler;
+use crate::sampling;
+use crate::vec3::Vec3;
+use crate::scene_hash::SceneHasher;
+
+// Ideal diffuse reflector, sampled with a cosine weighted distribution.
+pub struct Lambertian {
+    albedo: Color,
+}
+
+impl Lambertian {
+    pub fn new(albedo: Color) -> Self {
+        Lambertian { albedo }
+    }
+}
+
+impl Material for Lambertian {
+    fn scatter(&self, _r_in: &Ray, rec: &HitRecord, sampler: &mut dyn Sampler) -> Option<ScatterRecord> {
+        let uvw = Onb::new(&rec.normal);
+        let direction = uvw.transform(&sampling::cosine_hemisphere(sampler.get_2d()));
+        let cos_theta = Vec3::dot(&Vec3::unit_vector(direction), &rec.normal);
+
+        Some(ScatterRecord {
+            attenuation: self.albedo, // f * cos / pdf = (albedo / pi) * cos / (cos / pi)
+            ray: Ray::new(rec.p, direction),
+            pdf: cos_theta.max(0.0) / PI,
+            is_specular: false,
+        })
+    }
+
+    fn eval(&self, _r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> Color {
+        let cos_theta = Vec3::dot(&Vec3::unit_vector(*direction), &rec.normal);
+        if cos_theta <= 0.0 {
+            return Color::default();
+        }
+        (cos_theta / PI) * self.albedo
+    }
+
+    fn pdf(&self, _r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> f64 {
+        Vec3::dot(&Vec3::unit_vector(*direction), &rec.normal).max(0.0) / PI
+    }
+
+    fn hash_into(&self, hasher: &mut SceneHasher) {
+        hasher.write_str("lambertian");
+        hasher.write_vec3(&self.albedo);
+    }
+}
@@ -0,0 +1,123 @@
+//! Ray tracer from the "Ray Tracing in One Weekend" series.
+//!
+//! Build a scene from `Hittable` objects with `Material`s, add `Light`s, configure a `Camera`
+//! with `Camera::builder()` and call one of its render methods.
+
+pub mod color;
+pub mod ray;
+pub mod rtweekend;
+pub mod scene_hash;
+pub mod vec3;
+pub mod camera;
+pub mod camera_settings;
+pub mod integrator;
+pub mod bdpt;
+pub mod mlt;
+pub mod scene_file;
+pub mod checkpoint;
+pub mod enum_names;
+pub mod error;
+pub mod interval;
+
+pub mod accumulator;
+pub mod image;
+pub mod encoder;
+pub mod hdr_encoder;
+pub mod png_encoder;
+pub mod ppm_encoder;
+pub mod filter;
+pub mod tonemap;
+pub mod adaptive;
+pub mod distributed;
+pub mod preview;
+pub mod progressive;
+pub mod render_server;
+pub mod render_status;
+pub mod tile;
+
+pub mod hittable;
+pub mod hittable_list;
+pub mod sphere;
+
+pub mod material;
+pub mod conductor;
+pub mod dielectric;
+pub mod lambertian;
+pub mod metal;
+pub mod microfacet;
+pub mod principled;
+pub mod rough_dielectric;
+pub mod subsurface;
+pub mod thin_film;
+
+pub mod texture;
+pub mod checker_texture;
+pub mod image_texture;
+
+pub mod background;
+pub mod light;
+pub mod light_list;
+pub mod onb;
+pub mod photon_map;
+pub mod photon_mapping;
+pub mod sky;
+pub mod spectrum;
+
+pub mod sampler;
+pub mod sampling;
+pub mod blue_noise_sampler;
+pub mod halton_sampler;
+pub mod independent_sampler;
+pub mod metropolis_sampler;
+pub mod sobol_sampler;
+pub mod stratified_sampler;
+
+pub mod directional_light;
+pub mod point_light;
+pub mod spot_light;
+
+pub use crate::adaptive::AdaptiveSettings;
+pub use crate::background::Background;
+pub use crate::camera::{Camera, CameraBuilder};
+pub use crate::camera_settings::CameraSettings;
+pub use crate::distributed::DistributedSettings;
+pub use crate::checker_texture::CheckerTexture;
+pub use crate::color::Color;
+pub use crate::conductor::{ComplexIor, Conductor};
+pub use crate::dielectric::{Dielectric, Dispersion};
+pub use crate::directional_light::DirectionalLight;
+pub use crate::encoder::{Encoder, ImageFormat};
+pub use crate::error::{Error, Result};
+pub use crate::filter::{Filter, FilterKind};
+pub use crate::hittable::{HitRecord, Hittable};
+pub use crate::hittable_list::HittableList;
+pub use crate::image::Image;
+pub use crate::image_texture::ImageTexture;
+pub use crate::integrator::IntegratorKind;
+pub use crate::interval::Interval;
+pub use crate::lambertian::Lambertian;
+pub use crate::light::{Light, LightEmission, LightSample};
+pub use crate::light_list::LightList;
+pub use crate::material::{Material, ScatterRecord};
+pub use crate::metal::Metal;
+pub use crate::mlt::MetropolisSettings;
+pub use crate::photon_map::{Photon, PhotonMap};
+pub use crate::photon_mapping::PhotonSettings;
+pub use crate::point_light::PointLight;
+pub use crate::principled::Principled;
+pub use crate::preview::{PreviewMode, TerminalPreview};
+pub use crate::progressive::ProgressiveSettings;
+pub use crate::render_status::RenderStatus;
+pub use crate::scene_file::{Scene, SceneFile};
+pub use crate::ray::Ray;
+pub use crate::rough_dielectric::RoughDielectric;
+pub use crate::sampler::{Sampler, SamplerKind};
+pub use crate::sky::Sky;
+pub use crate::spectrum::{SampledSpectrum, SampledWavelengths};
+pub use crate::sphere::Sphere;
+pub use crate::subsurface::Subsurface;
+pub use crate::spot_light::SpotLight;
+pub use crate::texture::{SolidColor, Texture};
+pub use crate::thin_film::ThinFilm;
+pub use crate::tonemap::{ToneMapOperator, ToneMapping};
+pub use crate::vec3::{Point3, Vec3};
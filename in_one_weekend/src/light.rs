@@ -0,0 +1,61 @@
+use crate::color::Color;
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::scene_hash::SceneHasher;
+use crate::vec3::{Point3, Vec3};
+
+// Result of sampling a light from a shading point.
+// `direction` is the unit vector from the shading point towards the light, `distance` how far the
+// shadow ray has to travel (INFINITY for lights at infinity), and `radiance / pdf` is the estimate
+// of the incident light along that direction.
+// Delta lights (point, spot, directional) can only be reached through this one direction,
+// so they report a pdf of 1 and the light arriving at `p` as `radiance`.
+pub struct LightSample {
+    pub direction: Vec3,
+    pub distance: f64,
+    pub radiance: Color,
+    pub pdf: f64,
+}
+
+// Ray leaving a light, for paths traced from the lights (see bdpt.rs).
+// Only lights at a point emit rays, from `ray.origin()`; `radiance` is the intensity along the ray
+// and `pdf` the solid angle density of its direction.
+pub struct LightEmission {
+    pub ray: Ray,
+    pub radiance: Color,
+    pub pdf: f64,
+}
+
+// Lights cannot be hit by camera rays; they only contribute through shadow rays traced at each hit.
+// The exception is the sun of a sky background, which camera rays also see.
+pub trait Light: Send + Sync {
+    // Picks a direction towards the light as seen from `p`, or None if the light cannot reach `p`.
+    fn sample_li(&self, p: &Point3, sampler: &mut dyn Sampler) -> Option<LightSample>;
+
+    // Solid angle density with which sample_li picks `direction` from `p`.
+    // Delta lights give 0, a ray never finds their single direction by chance.
+    fn pdf_li(&self, _p: &Point3, _direction: &Vec3) -> f64 {
+        0.0
+    }
+
+    // Whether the light can only be reached through sample_li: it sits at a point or shines from
+    // a single direction.
+    fn is_delta(&self) -> bool {
+        true
+    }
+
+    // Picks a ray leaving the light, or None for lights paths can't start from (those at infinity).
+    fn sample_le(&self, _sampler: &mut dyn Sampler) -> Option<LightEmission> {
+        None
+    }
+
+    // Solid angle density with which sample_le emits along `direction`.
+    fn pdf_le(&self, _direction: &Vec3) -> f64 {
+        0.0
+    }
+
+    // Feeds the light's parameters into `hasher`, see scene_hash. The default only tells types apart.
+    fn hash_into(&self, hasher: &mut SceneHasher) {
+        hasher.write_str(std::any::type_name::<Self>());
+    }
+}
@@ -0,0 +1,33 @@
+use std::hash::Hasher;
+
+use crate::light::Light;
+use crate::scene_hash::SceneHasher;
+
+pub struct LightList {
+    pub lights: Vec<Box<dyn Light>>,
+}
+
+impl LightList {
+    pub fn new() -> Self {
+        LightList {
+            lights: Vec::new(),
+        }
+    }
+    pub fn add(&mut self, light: Box<dyn Light>) {
+        self.lights.push(light);
+    }
+
+    // Feeds every light into `hasher`, see scene_hash.
+    pub fn hash_into(&self, hasher: &mut SceneHasher) {
+        hasher.write_usize(self.lights.len());
+        for light in &self.lights {
+            light.hash_into(hasher);
+        }
+    }
+}
+
+impl Default for LightList {
+    fn default() -> Self {
+        LightList::new()
+    }
+}
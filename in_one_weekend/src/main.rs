@@ -1,32 +1,338 @@
-mod color;
-mod ray;
-mod rtweekend;
-mod vec3;
-mod camera;
-mod interval;
+use std::io::{self, BufWriter, Write};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
 
-mod hittable;
-mod hittable_list;
-mod sphere;
+use clap::Parser;
 
-use crate::hittable_list::HittableList;
-use crate::ray::Ray;
-use crate::sphere::Sphere;
-use crate::vec3::{Point3, Vec3};
+use in_one_weekend::distributed::serve_worker;
+use in_one_weekend::render_server;
+use in_one_weekend::{
+    AdaptiveSettings, Background, Camera, CameraSettings, Color, DirectionalLight, DistributedSettings, Error,
+    FilterKind, HittableList, ImageFormat, IntegratorKind, Lambertian, LightList, Point3, PointLight, PreviewMode,
+    ProgressiveSettings, Result, SamplerKind, SceneFile, Sky, Sphere, SpotLight, ToneMapOperator, Vec3,
+};
 
+#[derive(Parser)]
+#[command(about = "Ray Tracing in One Weekend")]
+struct Args {
+    /// Write the image to this file (.ppm, .png or .hdr) instead of a P3 PPM on stdout
+    #[arg(long, short, value_name = "FILE")]
+    output: Option<PathBuf>,
 
+    /// Image format, overriding the one implied by the output file extension: ppm, ppm-binary, png or hdr
+    #[arg(long)]
+    format: Option<ImageFormat>,
 
-fn main() {
-    
+    /// Read the camera settings from this .json or .toml file; the options below override it
+    #[arg(long, value_name = "FILE")]
+    settings: Option<PathBuf>,
 
-    // World
+    /// Render the objects, lights and camera of this .json or .toml scene file instead of the built-in scene
+    #[arg(long, value_name = "FILE", conflicts_with = "settings")]
+    scene: Option<PathBuf>,
+
+    /// Write the camera settings used for this render to a .json or .toml file, to replay it later
+    #[arg(long, value_name = "FILE")]
+    save_settings: Option<PathBuf>,
+
+    /// Sample generator: independent, stratified, halton, sobol or blue-noise [default: independent]
+    #[arg(long)]
+    sampler: Option<SamplerKind>,
+
+    /// Seed for the sample generator, the same seed renders the same image [default: 0]
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Pixel reconstruction filter: box, tent, gaussian, mitchell or lanczos [default: box]
+    #[arg(long)]
+    filter: Option<FilterKind>,
+
+    /// Filter radius in pixels (defaults to the filter's usual radius)
+    #[arg(long)]
+    filter_radius: Option<f64>,
+
+    /// Trace wavelengths instead of RGB, for dispersion in glass with a dispersion curve
+    #[arg(long)]
+    spectral: bool,
+
+    /// Light transport algorithm: path-tracing, bidirectional, photon-mapping, progressive-photon-mapping or metropolis [default: path-tracing]
+    #[arg(long)]
+    integrator: Option<IntegratorKind>,
+
+    /// Photons traced for each photon map of the photon mapping integrators [default: 100000]
+    #[arg(long)]
+    photons: Option<u32>,
+
+    /// Radius of the photon density estimate in scene units, the first one when progressive [default: 0.05]
+    #[arg(long)]
+    photon_radius: Option<f64>,
+
+    /// Markov chains of the metropolis integrator, each rendered on its own thread [default: 8]
+    #[arg(long)]
+    chains: Option<u32>,
+
+    /// Tone mapping operator: clamp, reinhard, hable, aces or agx [default: clamp]
+    #[arg(long)]
+    tonemap: Option<ToneMapOperator>,
+
+    /// Exposure adjustment in stops, applied before tone mapping [default: 0]
+    #[arg(long, allow_negative_numbers = true)]
+    exposure: Option<f64>,
+
+    /// Luminance that maps to white with the reinhard operator [default: 4]
+    #[arg(long)]
+    white_point: Option<f64>,
+
+    /// Render progressively, rewriting the output file after every pass
+    #[arg(long, requires = "output")]
+    progressive: bool,
+
+    /// Samples per pixel added in each progressive or adaptive pass
+    #[arg(long, default_value_t = 4)]
+    samples_per_pass: u32,
+
+    /// Stop the progressive or adaptive render after this many samples per pixel
+    #[arg(long, default_value_t = 1024)]
+    max_samples: u32,
+
+    /// Stop the progressive render after this many seconds
+    #[arg(long, value_name = "SECONDS")]
+    time_limit: Option<f64>,
+
+    /// Stop the progressive render once the average relative pixel error drops below this value
+    #[arg(long)]
+    target_noise: Option<f64>,
+
+    /// Draw the image in the terminal after every progressive pass instead of a progress bar:
+    /// auto, half-block (24-bit ANSI colors) or sixel
+    #[arg(long, value_name = "MODE", num_args = 0..=1, default_missing_value = "auto", requires = "progressive")]
+    preview: Option<PreviewMode>,
+
+    /// Save the progressive render state to this file, to resume it after a crash or with more samples
+    #[arg(long, value_name = "FILE", requires = "progressive")]
+    checkpoint: Option<PathBuf>,
+
+    /// Seconds between checkpoint writes (a checkpoint is also written when the render ends)
+    #[arg(long, value_name = "SECONDS", default_value_t = 60.0)]
+    checkpoint_interval: f64,
+
+    /// Continue the progressive render from --checkpoint instead of starting over
+    #[arg(long, requires = "checkpoint")]
+    resume: bool,
+
+    /// Run as a render worker listening on this address (e.g. 0.0.0.0:7878) for a coordinator's tiles
+    #[arg(long, value_name = "ADDR", conflicts_with_all = ["output", "progressive", "adaptive", "workers"])]
+    worker: Option<String>,
+
+    /// Coordinate a render across these workers (host:port, comma separated) instead of rendering here
+    #[arg(long, value_name = "ADDRS", value_delimiter = ',', conflicts_with_all = ["progressive", "adaptive"])]
+    workers: Vec<String>,
+
+    /// Side of the square tiles handed out to workers, in pixels
+    #[arg(long, default_value_t = 32)]
+    tile_size: u32,
+
+    /// Seconds a worker may take for one tile before its work is given to another
+    #[arg(long, value_name = "SECONDS", default_value_t = 120.0)]
+    worker_timeout: f64,
+
+    /// Run an HTTP render server on this address (e.g. 127.0.0.1:8080) that renders posted scene files
+    #[arg(long, value_name = "ADDR", conflicts_with_all = ["output", "progressive", "adaptive", "workers", "worker", "scene"])]
+    serve: Option<String>,
+
+    /// Render with adaptive sampling, concentrating samples on noisy pixels
+    #[arg(long, conflicts_with = "progressive")]
+    adaptive: bool,
+
+    /// Samples every pixel receives before adaptive sampling starts
+    #[arg(long, default_value_t = 16)]
+    min_samples: u32,
+
+    /// Relative pixel error below which adaptive sampling stops sampling a pixel
+    #[arg(long, default_value_t = 0.01)]
+    error_threshold: f64,
+
+    /// Also write a heatmap of the samples taken per pixel to this PPM file
+    #[arg(long, value_name = "FILE", requires = "adaptive")]
+    heatmap: Option<PathBuf>,
+}
+
+fn main() -> ExitCode {
+    match run(Args::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: Args) -> Result<()> {
+    if let Some(address) = &args.serve {
+        return render_server::serve(address);
+    }
+
+    let scene_file = args.scene.as_deref().map(SceneFile::load).transpose()?;
+    let (world, lights) = match &scene_file {
+        Some(scene_file) => {
+            let scene = scene_file.build()?;
+            (scene.world, scene.lights)
+        }
+        None => scene(),
+    };
+
+    if let Some(address) = &args.worker {
+        // Workers render whatever camera the coordinator sends, over their own copy of the scene.
+        let listener = TcpListener::bind(address).map_err(|e| Error::invalid("worker", format!("{}: {}", address, e)))?;
+        eprintln!("worker listening on {}", listener.local_addr()?);
+        return serve_worker(&listener, &world, &lights);
+    }
+
+    // Catch a bad output name before spending minutes on the render.
+    if let Some(path) = &args.output
+        && args.format.is_none()
+        && ImageFormat::from_path(path).is_none()
+    {
+        return Err(Error::UnknownImageFormat(path.clone()));
+    }
+
+    let mut settings = match (&args.settings, scene_file) {
+        (Some(path), _) => CameraSettings::load(path)?,
+        (None, Some(scene_file)) => scene_file.camera,
+        (None, None) => CameraSettings {
+            aspect_ratio: 16.0 / 9.0,
+            image_width: 400,
+            samples_per_pixel: 100,
+            max_depth: 50,
+            // Afternoon sun, a little to the right of the view.
+            background: Background::Sky(Box::new(Sky::new(35.0, 30.0, 3.0))),
+            ..CameraSettings::default()
+        },
+    };
+    if let Some(sampler) = args.sampler {
+        settings.sampler = sampler;
+    }
+    if let Some(seed) = args.seed {
+        settings.seed = seed;
+    }
+    if let Some(filter) = args.filter {
+        settings.filter = filter;
+    }
+    if args.filter_radius.is_some() {
+        settings.filter_radius = args.filter_radius;
+    }
+    if args.spectral {
+        settings.spectral = true;
+    }
+    if let Some(integrator) = args.integrator {
+        settings.integrator = integrator;
+    }
+    if let Some(count) = args.photons {
+        settings.photons.count = count;
+    }
+    if let Some(radius) = args.photon_radius {
+        settings.photons.radius = radius;
+    }
+    if let Some(chains) = args.chains {
+        settings.metropolis.chains = chains;
+    }
+    if let Some(operator) = args.tonemap {
+        settings.tone_mapping.operator = operator;
+    }
+    if let Some(exposure) = args.exposure {
+        settings.tone_mapping.exposure = exposure;
+    }
+    if let Some(white_point) = args.white_point {
+        settings.tone_mapping.white_point = white_point;
+    }
+
+    let cam = Camera::new(settings)?;
+    if let Some(path) = &args.save_settings {
+        cam.settings().save(path)?;
+    }
+
+    let image = if args.adaptive {
+        let settings = AdaptiveSettings {
+            min_samples: args.min_samples,
+            max_samples: args.max_samples,
+            samples_per_pass: args.samples_per_pass,
+            error_threshold: args.error_threshold,
+            heatmap: args.heatmap,
+        };
+        cam.render_adaptive(&world, &lights, &settings)?
+    } else if args.progressive {
+        // clap enforces `requires = "output"`, this only guards against that changing
+        let output = args.output.as_ref().ok_or(Error::invalid("output", "--progressive needs an output file"))?;
+        // The format the final image is saved in, also for the snapshots.
+        let format = match args.format {
+            Some(format) => format,
+            None => ImageFormat::from_path(output).ok_or_else(|| Error::UnknownImageFormat(output.clone()))?,
+        };
+        let time_limit = args.time_limit.map(|s| seconds("time_limit", s)).transpose()?;
+        let settings = ProgressiveSettings {
+            samples_per_pass: args.samples_per_pass,
+            max_samples: args.max_samples,
+            time_limit,
+            target_noise: args.target_noise,
+            checkpoint: args.checkpoint,
+            checkpoint_interval: seconds("checkpoint_interval", args.checkpoint_interval)?,
+            resume: args.resume,
+            preview: args.preview,
+        };
+        cam.render_progressive(&world, &lights, &settings, output, format)?
+    } else if !args.workers.is_empty() {
+        let settings = DistributedSettings {
+            workers: args.workers.clone(),
+            tile_size: args.tile_size,
+            timeout: seconds("worker_timeout", args.worker_timeout)?,
+        };
+        cam.render_distributed(&world, &lights, &settings)?
+    } else {
+        cam.render(&world, &lights)?
+    };
+
+    match (&args.output, args.format) {
+        (Some(path), Some(format)) => image.save_as(path, format, cam.tone_mapping()),
+        (Some(path), None) => image.save(path, cam.tone_mapping()),
+        (None, format) => {
+            let stdout = io::stdout();
+            let mut out = BufWriter::new(stdout.lock());
+            format
+                .unwrap_or(ImageFormat::PpmAscii)
+                .encoder(*cam.tone_mapping())
+                .encode(&image, &mut out)?;
+            out.flush()?;
+            Ok(())
+        }
+    }
+}
+
+fn seconds(name: &'static str, seconds: f64) -> Result<Duration> {
+    Duration::try_from_secs_f64(seconds)
+        .map_err(|_| Error::invalid(name, format!("{} is not a valid number of seconds", seconds)))
+}
+
+// The scene rendered by this binary, the same for a local render, a coordinator and its workers.
+fn scene() -> (HittableList, LightList) {
     let mut world = HittableList::new();
 
-    world.add(Box::new(Sphere::new(Point3::new(0.0, 0.0, -1.0), 0.5)));
-    world.add(Box::new(Sphere::new(Point3::new(0.0, -100.5, -1.0), 100.0)));
+    let material = Arc::new(Lambertian::new(Color::new(0.4, 0.4, 0.4)));
+    world.add(Box::new(Sphere::new(Point3::new(0.0, 0.0, -1.0), 0.5, material.clone())));
+    world.add(Box::new(Sphere::new(Point3::new(0.0, -100.5, -1.0), 100.0, material)));
 
-    let mut cam = camera::Camera::init(16.0 / 9.0, 400, 100, 50);
-    
-    cam.render(&world);
+    let mut lights = LightList::new();
+    lights.add(Box::new(PointLight::new(Point3::new(-1.5, 1.5, 0.0), Color::new(4.0, 3.0, 2.0))));
+    lights.add(Box::new(SpotLight::new(
+        Point3::new(1.5, 2.0, -0.5),
+        Vec3::new(-1.5, -2.0, -0.5),
+        Color::new(4.0, 4.0, 5.0),
+        15.0,
+        25.0,
+    )));
+    lights.add(Box::new(DirectionalLight::new(Vec3::new(0.0, -1.0, -0.5), Color::new(0.2, 0.2, 0.25))));
 
+    (world, lights)
 }
@@ -0,0 +1,39 @@
+use crate::color::Color;
+use crate::hittable::HitRecord;
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::scene_hash::SceneHasher;
+use crate::vec3::Vec3;
+
+// Outcome of sampling a material.
+// `attenuation` is the path throughput weight of the sample, i.e. f * |cos| / pdf for diffuse and
+// glossy materials, and the reflectance / transmittance for specular ones.
+pub struct ScatterRecord {
+    pub attenuation: Color,
+    pub ray: Ray,
+    pub pdf: f64,
+    pub is_specular: bool, // Delta distribution: `eval` and `pdf` are 0 and lights can't be sampled
+}
+
+pub trait Material: Send + Sync {
+    // Samples an outgoing direction for a ray hitting the surface, or None if the ray is absorbed.
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, sampler: &mut dyn Sampler) -> Option<ScatterRecord>;
+
+    // BSDF times the cosine term, f(wo, wi) * |cos(theta_i)|, for scattering towards `direction`.
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> Color;
+
+    // Density with which `scatter` picks `direction` (solid angle measure).
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> f64;
+
+    // Whether `scatter` or `eval` depends on the wavelength of `r_in` (Ray::wavelength). Spectral
+    // renders follow only the hero wavelength through such a material, the others would scatter
+    // differently or be weighted by what the material gives at the hero.
+    fn is_dispersive(&self) -> bool {
+        false
+    }
+
+    // Feeds the material's parameters into `hasher`, see scene_hash. The default only tells types apart.
+    fn hash_into(&self, hasher: &mut SceneHasher) {
+        hasher.write_str(std::any::type_name::<Self>());
+    }
+}
@@ -0,0 +1,57 @@
+use crate::color::Color;
+use crate::hittable::HitRecord;
+use crate::material::{Material, ScatterRecord};
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::sampling;
+use crate::vec3::Vec3;
+use crate::scene_hash::SceneHasher;
+
+// Mirror whose reflected direction is perturbed by a random vector of length `fuzz`.
+// Treated as specular: the perturbation has no closed form pdf.
+pub struct Metal {
+    albedo: Color,
+    fuzz: f64,
+}
+
+impl Metal {
+    pub fn new(albedo: Color, fuzz: f64) -> Self {
+        Metal {
+            albedo,
+            fuzz: fuzz.clamp(0.0, 1.0),
+        }
+    }
+}
+
+impl Material for Metal {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, sampler: &mut dyn Sampler) -> Option<ScatterRecord> {
+        let reflected = Vec3::reflect(&r_in.direction(), &rec.normal);
+        let direction = Vec3::unit_vector(reflected) + self.fuzz * sampling::uniform_sphere(sampler.get_2d());
+
+        // Fuzzed below the surface: absorb.
+        if Vec3::dot(&direction, &rec.normal) <= 0.0 {
+            return None;
+        }
+
+        Some(ScatterRecord {
+            attenuation: self.albedo,
+            ray: Ray::new(rec.p, direction),
+            pdf: 0.0,
+            is_specular: true,
+        })
+    }
+
+    fn eval(&self, _r_in: &Ray, _rec: &HitRecord, _direction: &Vec3) -> Color {
+        Color::default()
+    }
+
+    fn pdf(&self, _r_in: &Ray, _rec: &HitRecord, _direction: &Vec3) -> f64 {
+        0.0
+    }
+
+    fn hash_into(&self, hasher: &mut SceneHasher) {
+        hasher.write_str("metal");
+        hasher.write_vec3(&self.albedo);
+        hasher.write_f64(self.fuzz);
+    }
+}
@@ -0,0 +1,149 @@
+use crate::rtweekend::PI;
+use crate::sampler::{self, Sampler};
+
+// The random numbers of one Metropolis chain (Kelemen et al., "A Simple and Robust Mutation
+// Strategy for the Metropolis Light Transport Algorithm"; see mlt.rs).
+// A path is a point in primary sample space: the vector of every number the path tracer drew for
+// it. Each iteration proposes a new vector, either a large step (all new uniform numbers) or a
+// small step (each number moved a little, wrapping around [0, 1)). The proposal is kept with
+// `accept` or undone with `reject`.
+// Numbers are only mutated when the path asks for them: a number that sat out n small steps is
+// moved by their sum at once, a normal step with n times the variance.
+pub struct MetropolisSampler {
+    seed: u64,
+    draws: u64, // Random numbers drawn from the seed so far
+    sigma: f64,
+    large_step_probability: f64,
+    x: Vec<PrimarySample>,
+    iteration: u64,
+    large_step: bool,
+    last_large_step_iteration: u64,
+    dimension: usize,
+}
+
+#[derive(Clone, Copy)]
+struct PrimarySample {
+    value: f64,
+    last_modified: u64, // Iteration the value was last brought up to date in
+    // Before the iteration in progress touched it, to undo a rejected proposal.
+    value_backup: f64,
+    modified_backup: u64,
+}
+
+impl MetropolisSampler {
+    // A chain whose first path is all uniform numbers, the same for the same `seed`.
+    // `sigma` is the standard deviation of a small step, `large_step_probability` the share of
+    // proposals that are large steps.
+    pub fn new(seed: u64, sigma: f64, large_step_probability: f64) -> Self {
+        MetropolisSampler {
+            seed: sampler::mix_bits(seed),
+            draws: 0,
+            sigma,
+            large_step_probability,
+            x: Vec::new(),
+            iteration: 0,
+            large_step: true,
+            last_large_step_iteration: 0,
+            dimension: 0,
+        }
+    }
+}
+
+//utils
+impl MetropolisSampler {
+    // Starts the next proposal; the path asks for its numbers from the first dimension again.
+    pub fn start_iteration(&mut self) {
+        self.iteration += 1;
+        self.large_step = self.uniform() < self.large_step_probability;
+        self.dimension = 0;
+    }
+
+    // Keeps the proposal as the chain's current path.
+    pub fn accept(&mut self) {
+        if self.large_step {
+            self.last_large_step_iteration = self.iteration;
+        }
+    }
+
+    // Goes back to the path before the proposal.
+    pub fn reject(&mut self) {
+        for x in &mut self.x {
+            if x.last_modified == self.iteration {
+                x.value = x.value_backup;
+                x.last_modified = x.modified_backup;
+            }
+        }
+        self.iteration -= 1;
+    }
+
+    // Next uniform number in [0, 1) of the chain's stream, for its decisions as well as new numbers.
+    pub fn uniform(&mut self) -> f64 {
+        self.draws += 1;
+        sampler::hash_to_unit(sampler::mix_bits(self.seed ^ sampler::mix_bits(self.draws)))
+    }
+
+    // Brings dimension `i` up to date for the iteration in progress and returns it.
+    fn next(&mut self, i: usize) -> f64 {
+        if i >= self.x.len() {
+            // A dimension no path asked for before is uniform whatever the chain did so far.
+            let value = self.uniform();
+            self.x.push(PrimarySample {
+                value,
+                last_modified: self.iteration,
+                value_backup: value,
+                modified_backup: self.iteration,
+            });
+            return value;
+        }
+
+        let mut x = self.x[i];
+        if x.last_modified < self.iteration {
+            // Left behind by a large step that was accepted since: a fresh number.
+            if x.last_modified < self.last_large_step_iteration {
+                x.value = self.uniform();
+                x.last_modified = self.last_large_step_iteration;
+            }
+            x.value_backup = x.value;
+            x.modified_backup = x.last_modified;
+            if self.large_step {
+                x.value = self.uniform();
+            } else {
+                let small_steps = (self.iteration - x.last_modified) as f64;
+                x.value += self.normal() * self.sigma * small_steps.sqrt();
+                x.value -= x.value.floor();
+                if x.value >= 1.0 {
+                    x.value = 0.0;
+                }
+            }
+            x.last_modified = self.iteration;
+            self.x[i] = x;
+        }
+        x.value
+    }
+
+    // Standard normal number (Box-Muller).
+    fn normal(&mut self) -> f64 {
+        let u1 = 1.0 - self.uniform();
+        let u2 = self.uniform();
+        (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos()
+    }
+}
+
+impl Sampler for MetropolisSampler {
+    // The pixel is one of the path's numbers too, only the dimensions start over.
+    fn start_pixel_sample(&mut self, _i: u32, _j: u32, _sample_index: u32) {
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let value = self.next(self.dimension);
+        self.dimension += 1;
+        value
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let x = self.get_1d();
+        let y = self.get_1d();
+        (x, y)
+    }
+}
@@ -0,0 +1,229 @@
+use crate::color::Color;
+use crate::rtweekend::PI;
+use crate::vec3::Vec3;
+
+// Microfacet building blocks for glossy materials.
+// Everything works in a local shading frame with the surface normal along +z, see Onb::to_local.
+
+// GGX / Trowbridge-Reitz distribution of microfacet normals, with roughness alpha_x along the
+// tangent (+x) and alpha_y along the bitangent (+y).
+pub struct Ggx {
+    alpha_x: f64,
+    alpha_y: f64,
+}
+
+impl Ggx {
+    // Below this the distribution is so peaked that its values overflow; such surfaces are mirrors anyway.
+    const MIN_ALPHA: f64 = 1e-3;
+
+    pub fn new(alpha: f64) -> Self {
+        Ggx::anisotropic(alpha, alpha)
+    }
+
+    pub fn anisotropic(alpha_x: f64, alpha_y: f64) -> Self {
+        Ggx {
+            alpha_x: alpha_x.max(Ggx::MIN_ALPHA),
+            alpha_y: alpha_y.max(Ggx::MIN_ALPHA),
+        }
+    }
+
+    // Perceptually linear roughness in [0, 1], alpha = roughness^2.
+    pub fn from_roughness(roughness: f64) -> Self {
+        Ggx::new(roughness * roughness)
+    }
+
+    // Roughness stretched by `anisotropy` in [0, 1]: 0 is isotropic, towards 1 the highlight is
+    // drawn out along the bitangent (Burley's mapping).
+    pub fn from_roughness_anisotropic(roughness: f64, anisotropy: f64) -> Self {
+        let aspect = (1.0 - 0.9 * anisotropy.clamp(0.0, 1.0)).sqrt();
+        let alpha = roughness * roughness;
+        Ggx::anisotropic(alpha / aspect, alpha * aspect)
+    }
+
+    // Density of microfacet normal h, normalised so that D(h) cos(theta_h) integrates to one.
+    pub fn d(&self, h: &Vec3) -> f64 {
+        if h.z() <= 0.0 {
+            return 0.0;
+        }
+        let (x, y) = (h.x() / self.alpha_x, h.y() / self.alpha_y);
+        let t = x * x + y * y + h.z() * h.z();
+        1.0 / (PI * self.alpha_x * self.alpha_y * t * t)
+    }
+
+    // Smith's Lambda, the ratio of hidden to visible microfacet area seen from w.
+    pub fn lambda(&self, w: &Vec3) -> f64 {
+        let cos2 = w.z() * w.z();
+        if cos2 == 0.0 {
+            return f64::INFINITY;
+        }
+        let (x, y) = (w.x() * self.alpha_x, w.y() * self.alpha_y);
+        ((1.0 + (x * x + y * y) / cos2).sqrt() - 1.0) / 2.0
+    }
+
+    // Fraction of microfacets visible from w.
+    pub fn g1(&self, w: &Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    // Height-correlated masking-shadowing for the pair of directions.
+    pub fn g(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    // Samples a microfacet normal with density D(h) cos(theta_h).
+    pub fn sample_h(&self, u: (f64, f64)) -> Vec3 {
+        // Sample the unit hemisphere as seen straight on and stretch it, like sample_visible from +z.
+        let r = (u.0 / (1.0 - u.0).max(1e-12)).sqrt();
+        let phi = 2.0 * PI * u.1;
+        Vec3::unit_vector(Vec3::new(r * phi.cos() * self.alpha_x, r * phi.sin() * self.alpha_y, 1.0))
+    }
+
+    pub fn pdf_h(&self, h: &Vec3) -> f64 {
+        self.d(h) * h.z().max(0.0)
+    }
+
+    // Samples a microfacet normal visible from w (w.z > 0) with density pdf_visible (Heitz 2018):
+    // no samples are wasted on facets turned away from w, which matters at grazing angles.
+    pub fn sample_visible(&self, w: &Vec3, u: (f64, f64)) -> Vec3 {
+        // Stretch to the configuration where the roughness is one.
+        let wh = Vec3::unit_vector(Vec3::new(self.alpha_x * w.x(), self.alpha_y * w.y(), w.z()));
+        let len2 = wh.x() * wh.x() + wh.y() * wh.y();
+        let t1 = if len2 > 0.0 {
+            Vec3::new(-wh.y(), wh.x(), 0.0) / len2.sqrt()
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let t2 = Vec3::cross(&wh, &t1);
+
+        // A point on the projected hemisphere: a disk, half of it squashed towards w.
+        let r = u.0.sqrt();
+        let phi = 2.0 * PI * u.1;
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + wh.z());
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).max(0.0).sqrt() + s * r * phi.sin();
+        let nh = p1 * t1 + p2 * t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * wh;
+
+        Vec3::unit_vector(Vec3::new(self.alpha_x * nh.x(), self.alpha_y * nh.y(), nh.z().max(1e-6)))
+    }
+
+    // Density of sample_visible: the visible normals G1(w) max(0, w.h) D(h) / w.z.
+    pub fn pdf_visible(&self, w: &Vec3, h: &Vec3) -> f64 {
+        if w.z() <= 0.0 {
+            return 0.0;
+        }
+        self.g1(w) * Vec3::dot(w, h).max(0.0) * self.d(h) / w.z()
+    }
+}
+
+// Berry's distribution (GTR with gamma = 1), the long tailed lobe of the Disney clearcoat.
+pub struct Gtr1 {
+    alpha: f64,
+}
+
+impl Gtr1 {
+    pub fn new(alpha: f64) -> Self {
+        Gtr1 {
+            alpha: alpha.clamp(Ggx::MIN_ALPHA, 0.999),
+        }
+    }
+
+    pub fn d(&self, h: &Vec3) -> f64 {
+        if h.z() <= 0.0 {
+            return 0.0;
+        }
+        let a2 = self.alpha * self.alpha;
+        let t = 1.0 + (a2 - 1.0) * h.z() * h.z();
+        (a2 - 1.0) / (PI * a2.ln() * t)
+    }
+
+    pub fn sample_h(&self, u: (f64, f64)) -> Vec3 {
+        let a2 = self.alpha * self.alpha;
+        let cos2 = ((1.0 - a2.powf(1.0 - u.0)) / (1.0 - a2)).clamp(0.0, 1.0);
+        let cos_theta = cos2.sqrt();
+        let sin_theta = (1.0 - cos2).sqrt();
+        let phi = 2.0 * PI * u.1;
+        Vec3::new(phi.cos() * sin_theta, phi.sin() * sin_theta, cos_theta)
+    }
+
+    pub fn pdf_h(&self, h: &Vec3) -> f64 {
+        self.d(h) * h.z().max(0.0)
+    }
+}
+
+// (1 - cos)^5, the angular falloff of Schlick's Fresnel approximation.
+pub fn schlick_weight(cos_theta: f64) -> f64 {
+    (1.0 - cos_theta).clamp(0.0, 1.0).powi(5)
+}
+
+pub fn schlick(f0: Color, cos_theta: f64) -> Color {
+    f0 + schlick_weight(cos_theta) * (Color::new(1.0, 1.0, 1.0) - f0)
+}
+
+// Exact unpolarised Fresnel reflectance of a dielectric boundary, for light arriving at
+// cos_theta_i from the side where eta = eta_other_side / eta_this_side.
+pub fn fresnel_dielectric(cos_theta_i: f64, eta: f64) -> f64 {
+    let cos_i = cos_theta_i.clamp(-1.0, 1.0);
+    let (cos_i, eta) = if cos_i < 0.0 { (-cos_i, 1.0 / eta) } else { (cos_i, eta) };
+
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return 1.0; // Total internal reflection
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let r_parallel = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let r_perpendicular = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    (r_parallel * r_parallel + r_perpendicular * r_perpendicular) / 2.0
+}
+
+// Unpolarised Fresnel reflectance of a conductor with complex index eta + i k (relative to the
+// outside), per channel.
+pub fn fresnel_conductor(cos_theta_i: f64, eta: Color, k: Color) -> Color {
+    let cos_i = cos_theta_i.clamp(0.0, 1.0);
+    let channel = |eta: f64, k: f64| {
+        let cos2 = cos_i * cos_i;
+        let sin2 = 1.0 - cos2;
+        let t0 = eta * eta - k * k - sin2;
+        let a2_plus_b2 = (t0 * t0 + 4.0 * eta * eta * k * k).sqrt();
+        let a = ((a2_plus_b2 + t0) / 2.0).max(0.0).sqrt();
+
+        let t1 = a2_plus_b2 + cos2;
+        let t2 = 2.0 * a * cos_i;
+        let r_perpendicular = (t1 - t2) / (t1 + t2);
+        let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+        let t4 = t2 * sin2;
+        let r_parallel = r_perpendicular * (t3 - t4) / (t3 + t4);
+        (r_parallel + r_perpendicular) / 2.0
+    };
+    Color::new(channel(eta.x(), k.x()), channel(eta.y(), k.y()), channel(eta.z(), k.z()))
+}
+
+// Mirror direction of wo about the microfacet normal h.
+pub fn reflect(wo: &Vec3, h: &Vec3) -> Vec3 {
+    -*wo + 2.0 * Vec3::dot(wo, h) * *h
+}
+
+// Refracted direction of wo (on the side h points to) through a facet with normal h,
+// or None on total internal reflection. eta is the index of the far side over the near side.
+pub fn refract(wo: &Vec3, h: &Vec3, eta: f64) -> Option<Vec3> {
+    let cos_i = Vec3::dot(wo, h);
+    let sin2_t = (1.0 - cos_i * cos_i).max(0.0) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return None;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    Some(-*wo / eta + (cos_i / eta - cos_t) * *h)
+}
+
+// Microfacet normal (on the side of wo, the +z side) that refracts wo into wi, with wo.h and wi.h;
+// None when the pair can't be a refraction. eta is the index of the far side over the near side.
+pub fn refraction_half_vector(wo: &Vec3, wi: &Vec3, eta: f64) -> Option<(Vec3, f64, f64)> {
+    let mut h = Vec3::unit_vector(*wo + eta * *wi);
+    if h.z() < 0.0 {
+        h = -h;
+    }
+    let (o_h, i_h) = (Vec3::dot(wo, &h), Vec3::dot(wi, &h));
+    if h.near_zero() || o_h <= 0.0 || i_h >= 0.0 {
+        return None;
+    }
+    Some((h, o_h, i_h))
+}
@@ -0,0 +1,275 @@
+use std::ops::Range;
+use std::panic;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+
+use indicatif::{ProgressBar, ProgressDrawTarget};
+use serde::{Deserialize, Serialize};
+
+use crate::camera::Camera;
+use crate::color::{self, Color};
+use crate::error::{Error, Result};
+use crate::hittable::Hittable;
+use crate::image::Image;
+use crate::light_list::LightList;
+use crate::metropolis_sampler::MetropolisSampler;
+use crate::render_status::RenderStatus;
+use crate::sampler;
+
+// Metropolis light transport in primary sample space (Kelemen et al. 2002, following pbrt's
+// MLTIntegrator with the path tracer in place of the bidirectional one).
+//
+// Paths are sampled in proportion to their brightness instead of pixel by pixel: a Markov chain
+// moves from path to path by mutating the random numbers the path tracer drew for it (see
+// metropolis_sampler.rs), accepting a proposal with probability min(1, y' / y) for the luminances
+// y and y'. Once a chain has found a bright, hard to reach path its small steps explore the paths
+// around it, where independent samples would hardly ever come back. The image position is part of
+// the path, so the chains wander over the whole image.
+//
+// A chain visits a path in proportion to its luminance, so every path adds its color over its
+// luminance: that gives the image up to the scale b, the integral of the luminance over all paths.
+// b is estimated from `bootstrap_samples` independent paths, which the chains also start from (picked
+// in proportion to their luminance, so they start out at their stationary distribution and need no
+// burn-in). Each mutation records both the proposed and the current path, weighted with their
+// chances of being the next one, which wastes none of the rejected proposals.
+//
+// The chains share a thread per core, with `samples_per_pixel` times the pixel count mutations in all.
+// Every chain only depends on the seed and its index, so the image doesn't depend on the machine.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetropolisSettings {
+    pub bootstrap_samples: u32,      // Independent paths that estimate the image brightness and start the chains
+    pub chains: u32,                 // Independent Markov chains, each with an image of its own
+    pub large_step_probability: f64, // Share of the mutations that draw a whole new path
+    pub sigma: f64,                  // Standard deviation of a small step's change to each random number
+}
+
+impl Default for MetropolisSettings {
+    fn default() -> Self {
+        MetropolisSettings {
+            bootstrap_samples: 100_000,
+            chains: 8,
+            large_step_probability: 0.3,
+            sigma: 0.01,
+        }
+    }
+}
+
+impl MetropolisSettings {
+    pub fn is_default(&self) -> bool {
+        *self == MetropolisSettings::default()
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.bootstrap_samples == 0 {
+            return Err(Error::invalid("metropolis.bootstrap_samples", "must be at least 1"));
+        }
+        if !(1..=MAX_CHAINS).contains(&self.chains) {
+            return Err(Error::invalid(
+                "metropolis.chains",
+                format!("must be between 1 and {}, got {}", MAX_CHAINS, self.chains),
+            ));
+        }
+        if !(self.large_step_probability > 0.0 && self.large_step_probability <= 1.0) {
+            return Err(Error::invalid(
+                "metropolis.large_step_probability",
+                format!("must be above 0 and at most 1, got {}", self.large_step_probability),
+            ));
+        }
+        if !(self.sigma > 0.0 && self.sigma < 1.0) {
+            return Err(Error::invalid("metropolis.sigma", format!("must be between 0 and 1, got {}", self.sigma)));
+        }
+        Ok(())
+    }
+}
+
+// More chains than this are no use: every chain needs bootstrap paths to start from and enough
+// mutations to explore.
+const MAX_CHAINS: u32 = 256;
+
+// Mutations (or bootstrap paths) a chain takes between looks at the status.
+const STEPS_PER_UPDATE: u64 = 4096;
+
+pub(crate) fn render(
+    camera: &Camera,
+    world: &dyn Hittable,
+    lights: &LightList,
+    status: &RenderStatus,
+    draw_target: ProgressDrawTarget,
+) -> Result<Image> {
+    let settings = camera.settings().metropolis;
+    let (width, height) = (camera.image_width(), camera.image_height());
+    let bootstrap_samples = settings.bootstrap_samples as u64;
+    let chains = settings.chains as u64;
+    let mutations = camera.samples_per_pixel() as u64 * width as u64 * height as u64;
+
+    let bar = ProgressBar::with_draw_target(Some(bootstrap_samples + mutations), draw_target);
+    status.start(bootstrap_samples + mutations);
+    let progress = Progress { bar: &bar, status };
+    let seed = camera.settings().seed;
+    // The sampler of bootstrap path `index`, which a chain starting from that path continues.
+    let path_sampler = |index: u64| {
+        MetropolisSampler::new(sampler::mix_bits(seed) ^ index, settings.sigma, settings.large_step_probability)
+    };
+
+    // The luminance of every bootstrap path, in order.
+    let weights: Vec<f64> = run_chains(0..chains, |chain| {
+        let mut weights = Vec::new();
+        for (step, index) in share(bootstrap_samples, chains, chain).enumerate() {
+            progress.update(step as u64)?;
+            let (_, l) = camera.sample_image(&mut path_sampler(index), world, lights);
+            weights.push(weight(&l));
+        }
+        progress.finish(weights.len() as u64);
+        Ok(weights)
+    })?
+    .concat();
+
+    let mut cdf = Vec::with_capacity(weights.len() + 1);
+    cdf.push(0.0);
+    for w in &weights {
+        cdf.push(cdf[cdf.len() - 1] + w);
+    }
+    let total = cdf[cdf.len() - 1];
+    let mut image = Image::new(width, height);
+    if total <= 0.0 {
+        // Not a single path found any light, the chains would have nowhere to start.
+        bar.finish();
+        return Ok(image);
+    }
+    let b = total / bootstrap_samples as f64;
+
+    let chain_film = |chain: u64| {
+        // The bootstrap path the chain starts from, picked in proportion to its luminance.
+        let u = sampler::hash_to_unit(sampler::mix_bits(sampler::mix_bits(seed ^ 0x6d6c_7463_6861_696e) ^ chain));
+        let index = cdf.partition_point(|&c| c <= u * total).clamp(1, weights.len()) - 1;
+        let mut sampler = path_sampler(index as u64);
+        let mut current = camera.sample_image(&mut sampler, world, lights);
+        let mut current_y = weight(&current.1);
+
+        let mut film = vec![Color::default(); width as usize * height as usize];
+        let mut splat = |(x, y): (f64, f64), l: Color| {
+            let i = (x.max(0.0) as u32).min(width - 1);
+            let j = (y.max(0.0) as u32).min(height - 1);
+            film[(j * width + i) as usize] += l;
+        };
+        let steps = share(mutations, chains, chain).count() as u64;
+        for step in 0..steps {
+            progress.update(step)?;
+            sampler.start_iteration();
+            let proposed = camera.sample_image(&mut sampler, world, lights);
+            let proposed_y = weight(&proposed.1);
+
+            let accept = (proposed_y / current_y).min(1.0);
+            if accept > 0.0 {
+                splat(proposed.0, (accept / proposed_y) * proposed.1);
+            }
+            if accept < 1.0 {
+                splat(current.0, ((1.0 - accept) / current_y) * current.1);
+            }
+            if sampler.uniform() < accept {
+                sampler.accept();
+                current = proposed;
+                current_y = proposed_y;
+            } else {
+                sampler.reject();
+            }
+        }
+        progress.finish(steps);
+        Ok(film)
+    };
+
+    // Every chain splats into a film of its own. The chains run a thread's worth at a time, and their
+    // films are added up in chain order, so there are never more films than threads and the sum
+    // doesn't depend on how the chains were scheduled.
+    let mut sum = vec![Color::default(); width as usize * height as usize];
+    let batch = threads(chains);
+    for first in (0..chains).step_by(batch as usize) {
+        for film in run_chains(first..(first + batch).min(chains), chain_film)? {
+            for (total, l) in sum.iter_mut().zip(film) {
+                *total += l;
+            }
+        }
+    }
+    bar.finish();
+
+    // Every pixel got samples_per_pixel mutations on average, each recording one path's worth.
+    let scale = b / camera.samples_per_pixel() as f64;
+    for j in 0..height {
+        for i in 0..width {
+            image.set_pixel(i, j, &(scale * sum[j as usize * width as usize + i as usize]));
+        }
+    }
+    Ok(image)
+}
+
+// Luminance a chain samples paths in proportion to; paths that went wrong count as black.
+fn weight(l: &Color) -> f64 {
+    let y = color::luminance(l);
+    if y.is_finite() && y > 0.0 { y } else { 0.0 }
+}
+
+// The part of 0..count that `chain` of `chains` takes, in order.
+fn share(count: u64, chains: u64, chain: u64) -> Range<u64> {
+    (count * chain / chains)..(count * (chain + 1) / chains)
+}
+
+// Threads to run `chains` chains on: one per core, but no more than there are chains.
+fn threads(chains: u64) -> u64 {
+    thread::available_parallelism().map_or(1, |n| n.get() as u64).min(chains)
+}
+
+// Runs `chain` for every chain index in `chains` on a thread per core, returning their results in order.
+fn run_chains<T: Send>(chains: Range<u64>, chain: impl Fn(u64) -> Result<T> + Sync) -> Result<Vec<T>> {
+    let threads = threads(chains.end - chains.start);
+    let next = AtomicU64::new(chains.start);
+    let mut results: Vec<(u64, Result<T>)> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads).map(|_| {
+            let (chain, next) = (&chain, &next);
+            scope.spawn(move || {
+                let mut results = Vec::new();
+                loop {
+                    let c = next.fetch_add(1, Ordering::Relaxed);
+                    if c >= chains.end {
+                        return results;
+                    }
+                    results.push((c, chain(c)));
+                }
+            })
+        }).collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap_or_else(|e| panic::resume_unwind(e)))
+            .collect()
+    });
+    results.sort_by_key(|&(c, _)| c);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+// Reports the chains' steps to the progress bar and status, and stops them when it is cancelled.
+struct Progress<'a> {
+    bar: &'a ProgressBar,
+    status: &'a RenderStatus,
+}
+
+impl Progress<'_> {
+    // Called before every step, with the number of steps taken so far.
+    fn update(&self, steps: u64) -> Result<()> {
+        if steps > 0 && steps.is_multiple_of(STEPS_PER_UPDATE) {
+            self.bar.inc(STEPS_PER_UPDATE);
+            self.status.advance(STEPS_PER_UPDATE);
+            if self.status.is_cancelled() {
+                self.bar.abandon();
+                return Err(Error::Cancelled);
+            }
+        }
+        Ok(())
+    }
+
+    // Reports the steps since the last update once all `steps` are taken.
+    fn finish(&self, steps: u64) {
+        let rest = if steps == 0 { 0 } else { (steps - 1) % STEPS_PER_UPDATE + 1 };
+        self.bar.inc(rest);
+        self.status.advance(rest);
+    }
+}
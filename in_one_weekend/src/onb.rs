@@ -0,0 +1,63 @@
+use crate::vec3::Vec3;
+
+// Orthonormal basis built around a single direction (the `w` axis).
+// Used to turn samples generated around +Z into samples around an arbitrary normal or light direction.
+pub struct Onb {
+    axis: [Vec3; 3],
+}
+
+impl Onb {
+    pub fn new(n: &Vec3) -> Self {
+        let w = Vec3::unit_vector(*n);
+        // Pick a helper axis that is not (nearly) parallel to w.
+        let a = if w.x().abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let v = Vec3::unit_vector(Vec3::cross(&w, &a));
+        let u = Vec3::cross(&w, &v);
+
+        Onb { axis: [u, v, w] }
+    }
+
+    // Basis around `n` with `u` along the part of `tangent` perpendicular to it, for anisotropic
+    // materials; falls back to Onb::new when the tangent is zero or along n.
+    pub fn with_tangent(n: &Vec3, tangent: &Vec3) -> Self {
+        let w = Vec3::unit_vector(*n);
+        let t = *tangent - Vec3::dot(tangent, &w) * w;
+        if t.near_zero() {
+            return Onb::new(n);
+        }
+        let u = Vec3::unit_vector(t);
+        let v = Vec3::cross(&w, &u);
+
+        Onb { axis: [u, v, w] }
+    }
+}
+
+//getters
+impl Onb {
+    pub fn u(&self) -> Vec3 {
+        self.axis[0]
+    }
+    pub fn v(&self) -> Vec3 {
+        self.axis[1]
+    }
+    pub fn w(&self) -> Vec3 {
+        self.axis[2]
+    }
+}
+
+//utils
+impl Onb {
+    // Transform from basis coordinates to world space.
+    pub fn transform(&self, v: &Vec3) -> Vec3 {
+        (v.x() * self.u()) + (v.y() * self.v()) + (v.z() * self.w())
+    }
+
+    // Transform from world space to basis coordinates.
+    pub fn to_local(&self, v: &Vec3) -> Vec3 {
+        Vec3::new(Vec3::dot(v, &self.axis[0]), Vec3::dot(v, &self.axis[1]), Vec3::dot(v, &self.axis[2]))
+    }
+}
@@ -0,0 +1,92 @@
+use crate::color::Color;
+use crate::vec3::{Point3, Vec3};
+
+// A packet of light traced from a light and stored where it landed, see photon_mapping.rs.
+#[derive(Debug, Clone, Copy)]
+pub struct Photon {
+    pub p: Point3,
+    pub wi: Vec3,      // Unit direction the photon arrived from, pointing away from the surface
+    pub power: Color,  // Flux it carries, already divided by the number of photons traced
+}
+
+// Photons in a balanced kd-tree, for finding the ones near a point.
+// The tree is implicit: a range of the array is a subtree whose root is its middle photon, split
+// along `axes` of that photon (the axis the range is widest in), with the photons below the split
+// before it and the ones above after it.
+pub struct PhotonMap {
+    photons: Vec<Photon>,
+    axes: Vec<u8>,
+}
+
+impl PhotonMap {
+    pub fn new(mut photons: Vec<Photon>) -> Self {
+        let mut axes = vec![0; photons.len()];
+        PhotonMap::build(&mut photons, &mut axes);
+        PhotonMap { photons, axes }
+    }
+
+    fn build(photons: &mut [Photon], axes: &mut [u8]) {
+        if photons.len() <= 1 {
+            return;
+        }
+        let mut min = photons[0].p;
+        let mut max = photons[0].p;
+        for photon in photons.iter() {
+            for a in 0..3 {
+                min[a] = min[a].min(photon.p[a]);
+                max[a] = max[a].max(photon.p[a]);
+            }
+        }
+        let extent = max - min;
+        let axis = (0..3).max_by(|&a, &b| extent[a].total_cmp(&extent[b])).unwrap_or(0);
+
+        let mid = photons.len() / 2;
+        photons.select_nth_unstable_by(mid, |a, b| a.p[axis].total_cmp(&b.p[axis]));
+        axes[mid] = axis as u8;
+        let (below, above) = photons.split_at_mut(mid);
+        let (below_axes, above_axes) = axes.split_at_mut(mid);
+        PhotonMap::build(below, below_axes);
+        PhotonMap::build(&mut above[1..], &mut above_axes[1..]);
+    }
+}
+
+//getters
+impl PhotonMap {
+    pub fn len(&self) -> usize {
+        self.photons.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.photons.is_empty()
+    }
+}
+
+//utils
+impl PhotonMap {
+    // Calls `f` with every photon closer to `p` than `radius`.
+    pub fn for_each_within(&self, p: &Point3, radius: f64, mut f: impl FnMut(&Photon)) {
+        self.visit(0, self.photons.len(), p, radius * radius, &mut f);
+    }
+
+    fn visit(&self, start: usize, end: usize, p: &Point3, radius_squared: f64, f: &mut impl FnMut(&Photon)) {
+        if start >= end {
+            return;
+        }
+        let mid = start + (end - start) / 2;
+        let photon = &self.photons[mid];
+        if (photon.p - *p).length_squared() < radius_squared {
+            f(photon);
+        }
+
+        // The side of the split `p` is on first; the other only if the sphere reaches over the split.
+        let offset = p[self.axes[mid] as usize] - photon.p[self.axes[mid] as usize];
+        let (near, far) = if offset < 0.0 {
+            ((start, mid), (mid + 1, end))
+        } else {
+            ((mid + 1, end), (start, mid))
+        };
+        self.visit(near.0, near.1, p, radius_squared, f);
+        if offset * offset < radius_squared {
+            self.visit(far.0, far.1, p, radius_squared, f);
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use serde::{Deserialize, Serialize};
+
+use crate::color::Color;
+use crate::error::{Error, Result};
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::light_list::LightList;
+use crate::material::Material;
+use crate::photon_map::{Photon, PhotonMap};
+use crate::ray::Ray;
+use crate::rtweekend::{INFINITY, PI};
+use crate::sampler::{self, SamplerKind};
+use crate::vec3::Vec3;
+
+// Caustics for the path tracer (Jensen's caustic photon map).
+//
+// Light that reaches a diffuse surface through glass or a mirror from a point or spot light can't
+// be found by paths from the camera: their specular bounces never hit the light. Instead photons
+// are traced from the lights through specular bounces, and stored where they land on the first
+// surface that isn't specular. The path tracer adds, at every surface it samples the lights at,
+// the radiance these photons reflect: their flux times the BSDF, over the area of the disk of
+// `radius` around the hit. Paths through glass to the sun are still found by the path tracer,
+// which keeps the two apart.
+//
+// Density estimation blurs the caustics over the radius, and darkens them where they end within
+// it. The progressive variant (Knaus and Zwicker's probabilistic progressive photon mapping)
+// traces a new photon map every `samples_per_pass` samples per pixel, with a radius that shrinks
+// as r_(i+1)^2 = r_i^2 (i + alpha) / (i + 1): averaged over the passes the blur goes away, the
+// estimate converges to the exact caustics.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PhotonSettings {
+    pub count: u32,            // Photons traced from the lights for each photon map
+    pub radius: f64,           // Radius of the density estimate in scene units; the first pass's when progressive
+    pub alpha: f64,            // Progressive: share of the photons each pass keeps, in (0, 1), lower shrinks faster
+    pub samples_per_pass: u32, // Progressive: samples per pixel that share a photon map
+}
+
+impl Default for PhotonSettings {
+    fn default() -> Self {
+        PhotonSettings {
+            count: 100_000,
+            radius: 0.05,
+            alpha: 0.7,
+            samples_per_pass: 4,
+        }
+    }
+}
+
+impl PhotonSettings {
+    pub fn is_default(&self) -> bool {
+        *self == PhotonSettings::default()
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.count == 0 {
+            return Err(Error::invalid("photons.count", "must be at least 1"));
+        }
+        if !(self.radius.is_finite() && self.radius > 0.0) {
+            return Err(Error::invalid("photons.radius", format!("must be a positive distance, got {}", self.radius)));
+        }
+        if !(self.alpha > 0.0 && self.alpha < 1.0) {
+            return Err(Error::invalid("photons.alpha", format!("must be between 0 and 1, got {}", self.alpha)));
+        }
+        if self.samples_per_pass == 0 {
+            return Err(Error::invalid("photons.samples_per_pass", "must be at least 1"));
+        }
+        Ok(())
+    }
+
+    // Radius of the progressive pass `pass` (counted from 0).
+    pub fn radius_at(&self, pass: u32) -> f64 {
+        let mut radius_squared = self.radius * self.radius;
+        for i in 1..=pass {
+            radius_squared *= (i as f64 + self.alpha) / (i as f64 + 1.0);
+        }
+        radius_squared.sqrt()
+    }
+}
+
+// Photon maps of one scene, traced the first time a sample needs them. Renders of the whole image
+// take their samples pass by pass and forget the maps of the passes they are done with; tiles
+// rendered on their own (see Camera::render_tile) keep the maps of all the passes their pixels need.
+#[derive(Default)]
+pub(crate) struct PhotonMaps {
+    cache: Mutex<Cache>,
+}
+
+#[derive(Default)]
+struct Cache {
+    scene_hash: Option<u64>,             // scene_hash of the scene the maps were traced in, if known
+    maps: BTreeMap<u32, Arc<PhotonMap>>, // By pass
+}
+
+impl PhotonMaps {
+    pub(crate) fn clear(&self) {
+        *self.lock() = Cache::default();
+    }
+
+    // Forgets the maps unless they were traced in the scene with `scene_hash`.
+    pub(crate) fn use_scene(&self, scene_hash: u64) {
+        let mut cache = self.lock();
+        if cache.scene_hash != Some(scene_hash) {
+            *cache = Cache {
+                scene_hash: Some(scene_hash),
+                maps: BTreeMap::new(),
+            };
+        }
+    }
+
+    // Forgets the maps of the passes before `pass`, once no sample left to take needs them.
+    pub(crate) fn forget_before(&self, pass: u32) {
+        let mut cache = self.lock();
+        cache.maps = cache.maps.split_off(&pass);
+    }
+
+    // The map of `pass`, traced with `trace` if there is none yet.
+    pub(crate) fn get(&self, pass: u32, trace: impl FnOnce() -> PhotonMap) -> Arc<PhotonMap> {
+        self.lock().maps.entry(pass).or_insert_with(|| Arc::new(trace())).clone()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Cache> {
+        // The maps are only ever added whole, a panic elsewhere can't leave them half built.
+        self.cache.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+// Traces `settings.count` photons from the lights (those that can start paths, see
+// Light::sample_le) and keeps the ones that reached a surface through specular bounces only.
+// Every pass draws different photons, all derived from `seed`.
+pub(crate) fn trace_photons(
+    world: &dyn Hittable,
+    lights: &LightList,
+    settings: &PhotonSettings,
+    max_depth: u32,
+    seed: u64,
+    pass: u32,
+) -> PhotonMap {
+    let mut photons = Vec::new();
+    if lights.lights.is_empty() {
+        return PhotonMap::new(photons);
+    }
+    // Not the camera's seed, or the photons would repeat the random numbers of some pixels.
+    let mut sampler = SamplerKind::Independent.create(1, sampler::mix_bits(seed ^ 0x7068_6f74_6f6e));
+    let light_pdf = 1.0 / lights.lights.len() as f64;
+
+    for n in 0..settings.count {
+        sampler.start_pixel_sample(n, pass, 0);
+        let index = ((sampler.get_1d() * lights.lights.len() as f64) as usize).min(lights.lights.len() - 1);
+        let Some(emission) = lights.lights[index].sample_le(sampler.as_mut()) else {
+            continue;
+        };
+        if emission.pdf <= 0.0 {
+            continue;
+        }
+        let mut power = emission.radiance / (light_pdf * emission.pdf * settings.count as f64);
+        let mut ray = emission.ray;
+        let mut specular = false;
+
+        for _ in 0..max_depth {
+            let mut rec = HitRecord::default();
+            if !world.hit(&ray, Interval::new(0.001, INFINITY), &mut rec) {
+                break;
+            }
+            let Some(mat) = rec.mat.clone() else {
+                break;
+            };
+            let Some(srec) = mat.scatter(&ray, &rec, sampler.as_mut()) else {
+                break;
+            };
+            // The random walk of a subsurface material isn't a mirror, its light is diffuse.
+            if !srec.is_specular || srec.ray.origin() != rec.p {
+                if specular {
+                    let wi = -Vec3::unit_vector(ray.direction());
+                    photons.push(Photon { p: rec.p, wi, power });
+                }
+                break;
+            }
+            specular = true;
+            power = power * srec.attenuation;
+            if power.near_zero() {
+                break;
+            }
+            ray = srec.ray;
+        }
+    }
+    PhotonMap::new(photons)
+}
+
+// A photon map with the radius to look it up with.
+pub(crate) struct Caustics {
+    pub(crate) map: Arc<PhotonMap>,
+    pub(crate) radius: f64,
+}
+
+impl Caustics {
+    // Radiance of the caustics that `mat` reflects back along `r_in` at the hit.
+    pub(crate) fn radiance(&self, r_in: &Ray, rec: &HitRecord, mat: &dyn Material) -> Color {
+        let mut sum = Color::default();
+        self.map.for_each_within(&rec.p, self.radius, |photon| {
+            // eval includes the cosine, which the photon's flux already accounts for.
+            let cos_theta = Vec3::dot(&photon.wi, &rec.normal).abs();
+            if cos_theta > 1e-9 {
+                sum += (1.0 / cos_theta) * (mat.eval(r_in, rec, &photon.wi) * photon.power);
+            }
+        });
+        sum / (PI * self.radius * self.radius)
+    }
+}
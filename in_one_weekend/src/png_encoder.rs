@@ -0,0 +1,36 @@
+use std::io::{self, Write};
+
+use crate::color;
+use crate::encoder::Encoder;
+use crate::image::Image;
+use crate::tonemap::ToneMapping;
+
+// 8-bit RGB PNG, sRGB encoded.
+pub struct PngEncoder {
+    tone_mapping: ToneMapping,
+}
+
+impl PngEncoder {
+    pub fn new(tone_mapping: ToneMapping) -> Self {
+        PngEncoder { tone_mapping }
+    }
+}
+
+impl Encoder for PngEncoder {
+    fn encode(&self, image: &Image, out: &mut dyn Write) -> io::Result<()> {
+        let mut data = Vec::with_capacity(image.width() as usize * image.height() as usize * 3);
+        for j in 0..image.height() {
+            for i in 0..image.width() {
+                data.extend_from_slice(&color::to_srgb_bytes(&self.tone_mapping.apply(&image.pixel(i, j))));
+            }
+        }
+
+        let mut encoder = png::Encoder::new(out, image.width(), image.height());
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+        let mut writer = encoder.write_header().map_err(io::Error::other)?;
+        writer.write_image_data(&data).map_err(io::Error::other)?;
+        writer.finish().map_err(io::Error::other)
+    }
+}
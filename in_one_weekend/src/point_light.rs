@@ -0,0 +1,58 @@
+use crate::color::Color;
+use crate::light::{Light, LightEmission, LightSample};
+use crate::ray::Ray;
+use crate::rtweekend::PI;
+use crate::sampler::Sampler;
+use crate::sampling;
+use crate::vec3::{Point3, Vec3};
+use crate::scene_hash::SceneHasher;
+
+// Infinitely small light emitting `intensity` (radiant intensity, W/sr) equally in every direction.
+// Received light falls off with the inverse square of the distance.
+pub struct PointLight {
+    position: Point3,
+    intensity: Color,
+}
+
+impl PointLight {
+    pub fn new(position: Point3, intensity: Color) -> Self {
+        PointLight { position, intensity }
+    }
+}
+
+impl Light for PointLight {
+    fn sample_li(&self, p: &Point3, _sampler: &mut dyn Sampler) -> Option<LightSample> {
+        let to_light = self.position - *p;
+        let distance_squared = to_light.length_squared();
+        if distance_squared == 0.0 {
+            return None;
+        }
+        let distance = distance_squared.sqrt();
+
+        Some(LightSample {
+            direction: to_light / distance,
+            distance,
+            radiance: self.intensity / distance_squared,
+            pdf: 1.0, // Delta light: there is only one direction to pick
+        })
+    }
+
+    fn sample_le(&self, sampler: &mut dyn Sampler) -> Option<LightEmission> {
+        Some(LightEmission {
+            ray: Ray::new(self.position, sampling::uniform_sphere(sampler.get_2d())),
+            radiance: self.intensity,
+            pdf: 1.0 / (4.0 * PI),
+        })
+    }
+
+    fn pdf_le(&self, _direction: &Vec3) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+
+    fn hash_into(&self, hasher: &mut SceneHasher) {
+        hasher.write_str("point");
+        hasher.write_vec3(&self.position);
+        hasher.write_vec3(&self.intensity);
+    }
+}
+
@@ -0,0 +1,38 @@
+use std::io::{self, Write};
+
+use crate::color;
+use crate::encoder::Encoder;
+use crate::image::Image;
+use crate::tonemap::ToneMapping;
+
+// Netpbm PPM, either plain text (P3) or binary (P6), 8 bits per channel, sRGB encoded.
+pub struct PpmEncoder {
+    binary: bool,
+    tone_mapping: ToneMapping,
+}
+
+impl PpmEncoder {
+    pub fn new(binary: bool, tone_mapping: ToneMapping) -> Self {
+        PpmEncoder { binary, tone_mapping }
+    }
+}
+
+impl Encoder for PpmEncoder {
+    fn encode(&self, image: &Image, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "{}", if self.binary { "P6" } else { "P3" })?;
+        writeln!(out, "{} {}", image.width(), image.height())?;
+        writeln!(out, "255")?;
+
+        for j in 0..image.height() {
+            for i in 0..image.width() {
+                let mapped = self.tone_mapping.apply(&image.pixel(i, j));
+                if self.binary {
+                    out.write_all(&color::to_srgb_bytes(&mapped))?;
+                } else {
+                    color::write_color(out, &mapped)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
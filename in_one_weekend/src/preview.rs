@@ -0,0 +1,342 @@
+use std::env;
+use std::io::{self, Write};
+
+use console::Term;
+
+use crate::color::{self, Color};
+use crate::enum_names::enum_names;
+use crate::image::Image;
+use crate::tonemap::ToneMapping;
+
+// How TerminalPreview draws the image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PreviewMode {
+    #[default]
+    Auto, // Sixel when the terminal looks like it supports it, half blocks otherwise
+    HalfBlock, // Two pixels per character cell with 24-bit ANSI colors, works in most terminals
+    Sixel,     // Real pixels, for terminals with sixel graphics
+}
+
+enum_names!(PreviewMode, "preview mode", {
+    Auto => "auto",
+    HalfBlock => "half-block",
+    Sixel => "sixel",
+});
+
+impl PreviewMode {
+    // Replaces Auto with the mode the terminal we run in supports.
+    // Terminals can only be asked about sixel support by reading their answer from stdin, so this
+    // goes by the environment instead; pass Sixel explicitly for a terminal it doesn't know.
+    pub fn resolve(self) -> PreviewMode {
+        if self != PreviewMode::Auto {
+            return self;
+        }
+        let term = env::var("TERM").unwrap_or_default();
+        let program = env::var("TERM_PROGRAM").unwrap_or_default();
+        let sixel_terms = ["foot", "mlterm", "yaft", "contour"];
+        if term.contains("sixel")
+            || sixel_terms.iter().any(|name| term.starts_with(name))
+            || program == "WezTerm"
+            || program == "iTerm.app"
+        {
+            PreviewMode::Sixel
+        } else {
+            PreviewMode::HalfBlock
+        }
+    }
+}
+
+// Largest sixel preview, in pixels. Terminals don't say how big their cells are, so unlike the
+// half block preview it can't be fitted to the window.
+const SIXEL_MAX_WIDTH: u32 = 800;
+const SIXEL_MAX_HEIGHT: u32 = 600;
+
+// Draws an image (and a status line below it) on stderr, over the previous drawing.
+// Half blocks are redrawn in place by moving the cursor back up, sixels are drawn at the top of the screen.
+// When stderr isn't a terminal only the status lines are written.
+pub struct TerminalPreview {
+    mode: PreviewMode,
+    term: Term,
+    drawn_lines: usize, // Lines the last half block drawing took
+    started: bool,
+}
+
+impl TerminalPreview {
+    pub fn new(mode: PreviewMode) -> Self {
+        TerminalPreview {
+            mode: mode.resolve(),
+            term: Term::stderr(),
+            drawn_lines: 0,
+            started: false,
+        }
+    }
+}
+
+// public
+impl TerminalPreview {
+    pub fn draw(&mut self, image: &Image, tone_mapping: &ToneMapping, status: &str) -> io::Result<()> {
+        if !self.term.is_term() {
+            return writeln!(self.term, "{}", status);
+        }
+
+        let mut out = Vec::new();
+        if !self.started {
+            out.extend_from_slice(b"\x1b[?25l"); // Hide the cursor while drawing
+            if self.mode == PreviewMode::Sixel {
+                out.extend_from_slice(b"\x1b[2J");
+            }
+            self.started = true;
+        }
+
+        match self.mode {
+            PreviewMode::Sixel => {
+                let (width, height) = fit(image, SIXEL_MAX_WIDTH, SIXEL_MAX_HEIGHT);
+                out.extend_from_slice(b"\x1b[H");
+                write_sixel(&mut out, &resample(image, width, height, tone_mapping), width, height)?;
+                write!(out, "\r\n\x1b[K{}", status)?;
+            }
+            PreviewMode::HalfBlock | PreviewMode::Auto => {
+                // Keep a line for the status and one for the shell prompt afterwards.
+                let (rows, columns) = self.term.size();
+                let max_height = 2 * (rows as u32).saturating_sub(2).max(1);
+                let (width, height) = fit(image, columns as u32, max_height);
+
+                if self.drawn_lines > 0 {
+                    write!(out, "\x1b[{}F", self.drawn_lines)?;
+                }
+                out.extend_from_slice(b"\x1b[J");
+                write_half_blocks(&mut out, &resample(image, width, height, tone_mapping), width, height)?;
+                write!(out, "{}\r\n", status)?;
+                self.drawn_lines = height.div_ceil(2) as usize + 1;
+            }
+        }
+
+        self.term.write_all(&out)?;
+        self.term.flush()
+    }
+
+    // Gives the cursor back, leaving the last drawing on screen.
+    pub fn finish(&mut self) -> io::Result<()> {
+        if !self.started {
+            return Ok(());
+        }
+        self.started = false;
+        if self.mode == PreviewMode::Sixel {
+            self.term.write_all(b"\r\n")?;
+        }
+        self.term.write_all(b"\x1b[?25h")?;
+        self.term.flush()
+    }
+}
+
+impl Drop for TerminalPreview {
+    fn drop(&mut self) {
+        // Also reached when the render fails half way; there is nobody to report a failure to.
+        let _ = self.finish();
+    }
+}
+
+// private
+
+// Size of the image scaled down (never up) to fit in max_width x max_height, keeping its aspect ratio.
+fn fit(image: &Image, max_width: u32, max_height: u32) -> (u32, u32) {
+    let scale = (max_width as f64 / image.width() as f64)
+        .min(max_height as f64 / image.height() as f64)
+        .min(1.0);
+    let width = ((image.width() as f64 * scale).round() as u32).max(1);
+    let height = ((image.height() as f64 * scale).round() as u32).max(1);
+    (width, height)
+}
+
+// Box filters the image down to width x height and encodes it as sRGB bytes, row by row.
+fn resample(image: &Image, width: u32, height: u32, tone_mapping: &ToneMapping) -> Vec<[u8; 3]> {
+    let mut pixels = Vec::with_capacity(width as usize * height as usize);
+    for y in 0..height {
+        let (j0, j1) = span(y, height, image.height());
+        for x in 0..width {
+            let (i0, i1) = span(x, width, image.width());
+            let mut sum = Color::default();
+            for j in j0..j1 {
+                for i in i0..i1 {
+                    sum += image.pixel(i, j);
+                }
+            }
+            let mean = sum / ((i1 - i0) * (j1 - j0)) as f64;
+            pixels.push(color::to_srgb_bytes(&tone_mapping.apply(&mean)));
+        }
+    }
+    pixels
+}
+
+// Source pixels [start, end) covered by target pixel `index` of `count`; never empty.
+fn span(index: u32, count: u32, source: u32) -> (u32, u32) {
+    let start = (index as u64 * source as u64 / count as u64) as u32;
+    let end = ((index as u64 + 1) * source as u64 / count as u64) as u32;
+    (start, end.max(start + 1).min(source))
+}
+
+// Each character is an upper half block with the upper pixel as foreground and the lower one as background.
+fn write_half_blocks(out: &mut Vec<u8>, pixels: &[[u8; 3]], width: u32, height: u32) -> io::Result<()> {
+    let width = width as usize;
+    for y in (0..height as usize).step_by(2) {
+        for x in 0..width {
+            let [r, g, b] = pixels[y * width + x];
+            write!(out, "\x1b[38;2;{};{};{}m", r, g, b)?;
+            match pixels.get((y + 1) * width + x).filter(|_| y + 1 < height as usize) {
+                Some([r, g, b]) => write!(out, "\x1b[48;2;{};{};{}m", r, g, b)?,
+                None => out.extend_from_slice(b"\x1b[49m"),
+            }
+            out.extend_from_slice("▀".as_bytes());
+        }
+        out.extend_from_slice(b"\x1b[0m\r\n");
+    }
+    Ok(())
+}
+
+// Sixel graphics: bands of six pixel rows, drawn once per color with a bit per row in each character.
+// Colors are quantised to a 6x6x6 cube, which fits the 256 color registers every sixel terminal has.
+fn write_sixel(out: &mut Vec<u8>, pixels: &[[u8; 3]], width: u32, height: u32) -> io::Result<()> {
+    let width = width as usize;
+    let height = height as usize;
+    let level = |c: u8| (c as usize * 5 + 127) / 255;
+    let indices: Vec<usize> = pixels.iter().map(|&[r, g, b]| 36 * level(r) + 6 * level(g) + level(b)).collect();
+
+    // Raster attributes: 1:1 pixel aspect ratio and the image size.
+    write!(out, "\x1bPq\"1;1;{};{}", width, height)?;
+    let mut used = [false; 216];
+    for &index in &indices {
+        used[index] = true;
+    }
+    for (index, _) in used.iter().enumerate().filter(|(_, used)| **used) {
+        let percent = |l: usize| l * 100 / 5;
+        write!(out, "#{};2;{};{};{}", index, percent(index / 36), percent(index / 6 % 6), percent(index % 6))?;
+    }
+
+    for band in (0..height).step_by(6) {
+        let rows = (height - band).min(6);
+        let mut in_band = [false; 216];
+        for j in band..band + rows {
+            for &index in &indices[j * width..(j + 1) * width] {
+                in_band[index] = true;
+            }
+        }
+        let mut first = true;
+        for (index, _) in in_band.iter().enumerate().filter(|(_, used)| **used) {
+            if !first {
+                out.push(b'$'); // Back to the start of the band for the next color
+            }
+            first = false;
+            write!(out, "#{}", index)?;
+
+            let mut run: Option<(u8, usize)> = None;
+            for i in 0..width {
+                let mut bits = 0u8;
+                for row in 0..rows {
+                    if indices[(band + row) * width + i] == index {
+                        bits |= 1 << row;
+                    }
+                }
+                let c = 63 + bits;
+                run = match run {
+                    Some((last, count)) if last == c => Some((c, count + 1)),
+                    Some((last, count)) => {
+                        write_sixel_run(out, last, count)?;
+                        Some((c, 1))
+                    }
+                    None => Some((c, 1)),
+                };
+            }
+            if let Some((last, count)) = run {
+                write_sixel_run(out, last, count)?;
+            }
+        }
+        out.push(b'-'); // Next band
+    }
+    out.extend_from_slice(b"\x1b\\");
+    Ok(())
+}
+
+fn write_sixel_run(out: &mut Vec<u8>, c: u8, count: usize) -> io::Result<()> {
+    if count > 3 {
+        write!(out, "!{}{}", count, c as char)
+    } else {
+        out.extend(std::iter::repeat_n(c, count));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK: [u8; 3] = [0, 0, 0];
+    const WHITE: [u8; 3] = [255, 255, 255];
+
+    #[test]
+    fn fit_never_upscales() {
+        assert_eq!(fit(&Image::new(4, 3), 800, 600), (4, 3));
+        assert_eq!(fit(&Image::new(1000, 500), 100, 100), (100, 50));
+        assert_eq!(fit(&Image::new(500, 1000), 100, 100), (50, 100));
+        // Too thin to keep its aspect ratio, still a pixel wide.
+        assert_eq!(fit(&Image::new(10, 1000), 80, 40), (1, 40));
+    }
+
+    #[test]
+    fn spans_are_never_empty_and_cover_the_source() {
+        for source in 1..20 {
+            for count in 1..25 {
+                let mut covered = vec![false; source as usize];
+                for index in 0..count {
+                    let (start, end) = span(index, count, source);
+                    assert!(start < end && end <= source, "{}/{} of {}: {}..{}", index, count, source, start, end);
+                    covered[start as usize..end as usize].fill(true);
+                }
+                assert!(covered.iter().all(|&c| c), "{} spans leave pixels of {} out", count, source);
+            }
+        }
+    }
+
+    #[test]
+    fn half_blocks_pair_rows() {
+        // 2 x 3: the last row has no pixel below it and keeps the default background.
+        let pixels = [BLACK, WHITE, [1, 2, 3], [4, 5, 6], WHITE, BLACK];
+        let mut out = Vec::new();
+        write_half_blocks(&mut out, &pixels, 2, 3).expect("writes to memory");
+        let expected = concat!(
+            "\x1b[38;2;0;0;0m\x1b[48;2;1;2;3m▀",
+            "\x1b[38;2;255;255;255m\x1b[48;2;4;5;6m▀",
+            "\x1b[0m\r\n",
+            "\x1b[38;2;255;255;255m\x1b[49m▀",
+            "\x1b[38;2;0;0;0m\x1b[49m▀",
+            "\x1b[0m\r\n",
+        );
+        assert_eq!(String::from_utf8(out).expect("UTF-8"), expected);
+    }
+
+    #[test]
+    fn sixel_bands_draw_each_color_once() {
+        // 2 x 3, one band: the bits of a column are its rows that have the color, '?' (63) is none.
+        let pixels = [BLACK, WHITE, BLACK, BLACK, WHITE, WHITE];
+        let mut out = Vec::new();
+        write_sixel(&mut out, &pixels, 2, 3).expect("writes to memory");
+        let expected = "\x1bPq\"1;1;2;3#0;2;0;0;0#215;2;100;100;100#0BA$#215CD-\x1b\\";
+        assert_eq!(String::from_utf8(out).expect("ASCII"), expected);
+    }
+
+    #[test]
+    fn sixel_runs_of_four_or_more_are_counted() {
+        let run = |count: usize| {
+            let mut out = Vec::new();
+            write_sixel_run(&mut out, b'?', count).expect("writes to memory");
+            String::from_utf8(out).expect("ASCII")
+        };
+        assert_eq!(run(1), "?");
+        assert_eq!(run(3), "???");
+        assert_eq!(run(4), "!4?");
+        assert_eq!(run(12), "!12?");
+
+        let mut out = Vec::new();
+        write_sixel(&mut out, &[WHITE; 5], 5, 1).expect("writes to memory");
+        assert_eq!(String::from_utf8(out).expect("ASCII"), "\x1bPq\"1;1;5;1#215;2;100;100;100#215!5@-\x1b\\");
+    }
+}
@@ -0,0 +1,359 @@
+use std::sync::Arc;
+
+use crate::color::{self, Color};
+use crate::hittable::HitRecord;
+use crate::material::{Material, ScatterRecord};
+use crate::microfacet::{self, Ggx, Gtr1};
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::rtweekend::PI;
+use crate::sampler::Sampler;
+use crate::sampling;
+use crate::scene_hash::SceneHasher;
+use crate::texture::{self, Texture};
+use crate::vec3::Vec3;
+
+// Disney's principled BSDF (Burley 2012, with the transmission of Burley 2015): one material whose
+// parameters, all textured and all but `ior` in [0, 1], blend between the usual looks:
+//     base_color        diffuse albedo, or the reflectance of a metal
+//     metallic          dielectric (0) to conductor (1)
+//     roughness         smooth (0) to rough (1), for the diffuse, specular and transmission lobes
+//     specular          reflectance of a dielectric at normal incidence, 0.5 is 4%
+//     specular_tint     tints that reflectance towards the base color
+//     sheen             extra retro-reflection at grazing angles, for cloth
+//     sheen_tint        tints the sheen towards the base color
+//     clearcoat         strength of a second, colorless specular layer
+//     clearcoat_gloss   its glossiness
+//     transmission      opaque (0) to rough glass with index `ior` (1), tinted by the base color
+//     ior               index of refraction of that glass, 1.5 by default; not clamped to [0, 1]
+//
+// Lobes are picked with probabilities that follow their expected contribution and the returned
+// pdf is that of the whole mixture, so eval / pdf is the exact sample weight.
+// Like Dielectric, refraction doesn't scale radiance by 1 / eta^2.
+pub struct Principled {
+    base_color: Arc<dyn Texture>,
+    metallic: Arc<dyn Texture>,
+    roughness: Arc<dyn Texture>,
+    specular: Arc<dyn Texture>,
+    specular_tint: Arc<dyn Texture>,
+    sheen: Arc<dyn Texture>,
+    sheen_tint: Arc<dyn Texture>,
+    clearcoat: Arc<dyn Texture>,
+    clearcoat_gloss: Arc<dyn Texture>,
+    transmission: Arc<dyn Texture>,
+    ior: Arc<dyn Texture>,
+}
+
+impl Principled {
+    // A rough, white-ish plastic; set the other parameters with the methods below.
+    pub fn new(base_color: Arc<dyn Texture>) -> Self {
+        Principled {
+            base_color,
+            metallic: texture::constant(0.0),
+            roughness: texture::constant(0.5),
+            specular: texture::constant(0.5),
+            specular_tint: texture::constant(0.0),
+            sheen: texture::constant(0.0),
+            sheen_tint: texture::constant(0.5),
+            clearcoat: texture::constant(0.0),
+            clearcoat_gloss: texture::constant(1.0),
+            transmission: texture::constant(0.0),
+            ior: texture::constant(1.5),
+        }
+    }
+
+    pub fn metallic(mut self, metallic: Arc<dyn Texture>) -> Self {
+        self.metallic = metallic;
+        self
+    }
+    pub fn roughness(mut self, roughness: Arc<dyn Texture>) -> Self {
+        self.roughness = roughness;
+        self
+    }
+    pub fn specular(mut self, specular: Arc<dyn Texture>) -> Self {
+        self.specular = specular;
+        self
+    }
+    pub fn specular_tint(mut self, specular_tint: Arc<dyn Texture>) -> Self {
+        self.specular_tint = specular_tint;
+        self
+    }
+    pub fn sheen(mut self, sheen: Arc<dyn Texture>) -> Self {
+        self.sheen = sheen;
+        self
+    }
+    pub fn sheen_tint(mut self, sheen_tint: Arc<dyn Texture>) -> Self {
+        self.sheen_tint = sheen_tint;
+        self
+    }
+    pub fn clearcoat(mut self, clearcoat: Arc<dyn Texture>) -> Self {
+        self.clearcoat = clearcoat;
+        self
+    }
+    pub fn clearcoat_gloss(mut self, clearcoat_gloss: Arc<dyn Texture>) -> Self {
+        self.clearcoat_gloss = clearcoat_gloss;
+        self
+    }
+    pub fn transmission(mut self, transmission: Arc<dyn Texture>) -> Self {
+        self.transmission = transmission;
+        self
+    }
+    pub fn ior(mut self, ior: Arc<dyn Texture>) -> Self {
+        self.ior = ior;
+        self
+    }
+}
+
+// Smallest index of refraction the `ior` texture is taken to give.
+const MIN_IOR: f64 = 1e-3;
+
+// The parameters looked up at one hit point, and the lobes they make.
+struct Lobes {
+    base_color: Color,
+    roughness: f64,
+    specular_f0: Color, // Normal incidence reflectance of the specular lobe
+    sheen: Color,
+    clearcoat: f64,
+    clearcoat_gloss: f64,
+    diffuse_weight: f64, // (1 - metallic) (1 - transmission)
+    glass_weight: f64,   // (1 - metallic) transmission
+    eta: f64,            // Index below the surface over the index above it, as seen by the ray
+}
+
+impl Principled {
+    fn lobes(&self, rec: &HitRecord) -> Lobes {
+        let (u, v, p) = (rec.u, rec.v, &rec.p);
+        let unit = |t: &Arc<dyn Texture>| t.scalar(u, v, p).clamp(0.0, 1.0);
+
+        let base_color = self.base_color.value(u, v, p);
+        let metallic = unit(&self.metallic);
+        let transmission = unit(&self.transmission);
+        // A texture can go to zero or below, where refraction means nothing.
+        let ior = self.ior.scalar(u, v, p).max(MIN_IOR);
+
+        // Hue and saturation of the base color without its brightness.
+        let white = Color::new(1.0, 1.0, 1.0);
+        let luminance = color::luminance(&base_color);
+        let tint = if luminance > 0.0 { base_color / luminance } else { white };
+
+        let specular_tint = unit(&self.specular_tint);
+        let dielectric_f0 = 0.08 * unit(&self.specular) * ((1.0 - specular_tint) * white + specular_tint * tint);
+        let sheen_tint = unit(&self.sheen_tint);
+
+        Lobes {
+            base_color,
+            roughness: unit(&self.roughness),
+            specular_f0: (1.0 - metallic) * dielectric_f0 + metallic * base_color,
+            sheen: unit(&self.sheen) * ((1.0 - sheen_tint) * white + sheen_tint * tint),
+            clearcoat: unit(&self.clearcoat),
+            clearcoat_gloss: unit(&self.clearcoat_gloss),
+            diffuse_weight: (1.0 - metallic) * (1.0 - transmission),
+            glass_weight: (1.0 - metallic) * transmission,
+            eta: if rec.front_face { ior } else { 1.0 / ior },
+        }
+    }
+}
+
+impl Lobes {
+    fn specular(&self) -> Ggx {
+        Ggx::from_roughness(self.roughness)
+    }
+
+    fn clearcoat_lobe(&self) -> Gtr1 {
+        Gtr1::new(0.1 + (0.001 - 0.1) * self.clearcoat_gloss)
+    }
+
+    // Probabilities of sampling the diffuse, specular, glass and clearcoat lobes.
+    fn probabilities(&self, cos_o: f64) -> [f64; 4] {
+        let diffuse = self.diffuse_weight * (color::luminance(&self.base_color) + color::luminance(&self.sheen));
+        // At least 10% for the specular lobe, its highlights are small but bright.
+        let specular = (1.0 - self.glass_weight) * color::luminance(&microfacet::schlick(self.specular_f0, cos_o)).max(0.1);
+        let clearcoat = 0.25 * self.clearcoat;
+        let weights = [diffuse.max(0.0), specular, self.glass_weight, clearcoat];
+
+        let total: f64 = weights.iter().sum();
+        if total <= 0.0 {
+            return [0.0; 4];
+        }
+        weights.map(|w| w / total)
+    }
+
+    // BSDF for light arriving from wi and leaving towards wo, both in the local frame.
+    fn f(&self, wo: &Vec3, wi: &Vec3) -> Color {
+        let (cos_o, cos_i) = (wo.z(), wi.z());
+        if cos_o <= 0.0 || cos_i == 0.0 {
+            return Color::default();
+        }
+        let ggx = self.specular();
+
+        if cos_i < 0.0 {
+            // Refraction through the rough glass.
+            if self.glass_weight <= 0.0 {
+                return Color::default();
+            }
+            let Some((h, o_h, i_h)) = microfacet::refraction_half_vector(wo, wi, self.eta) else {
+                return Color::default();
+            };
+            let fresnel = microfacet::fresnel_dielectric(o_h, self.eta);
+            let denominator = o_h + self.eta * i_h;
+            let ft = ggx.d(&h) * ggx.g(wo, wi) * (1.0 - fresnel) * self.eta * self.eta * (i_h * o_h).abs()
+                / (cos_i.abs() * cos_o * denominator * denominator);
+            return (self.glass_weight * ft) * self.base_color;
+        }
+
+        let h = Vec3::unit_vector(*wo + *wi);
+        let cos_d = Vec3::dot(wi, &h);
+        let mut f = Color::default();
+
+        if self.diffuse_weight > 0.0 {
+            // Burley's diffuse with its roughness dependent retro-reflection, plus sheen.
+            let fd90 = 0.5 + 2.0 * self.roughness * cos_d * cos_d;
+            let fd = (1.0 + (fd90 - 1.0) * microfacet::schlick_weight(cos_i))
+                * (1.0 + (fd90 - 1.0) * microfacet::schlick_weight(cos_o));
+            f += self.diffuse_weight
+                * ((fd / PI) * self.base_color + microfacet::schlick_weight(cos_d) * self.sheen);
+        }
+
+        let specular = ggx.d(&h) * ggx.g(wo, wi) / (4.0 * cos_i * cos_o);
+        f += ((1.0 - self.glass_weight) * specular) * microfacet::schlick(self.specular_f0, cos_d);
+        if self.glass_weight > 0.0 {
+            let fresnel = microfacet::fresnel_dielectric(Vec3::dot(wo, &h), self.eta);
+            f += Color::new(1.0, 1.0, 1.0) * (self.glass_weight * specular * fresnel);
+        }
+
+        if self.clearcoat > 0.0 {
+            let coat = self.clearcoat_lobe();
+            let g = Ggx::new(0.25).g(wo, wi);
+            let fresnel = 0.04 + 0.96 * microfacet::schlick_weight(cos_d);
+            f += Color::new(1.0, 1.0, 1.0) * (0.25 * self.clearcoat * coat.d(&h) * g * fresnel / (4.0 * cos_i * cos_o));
+        }
+        f
+    }
+
+    fn pdf(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+        let (cos_o, cos_i) = (wo.z(), wi.z());
+        if cos_o <= 0.0 || cos_i == 0.0 {
+            return 0.0;
+        }
+        let [p_diffuse, p_specular, p_glass, p_clearcoat] = self.probabilities(cos_o);
+        let ggx = self.specular();
+
+        if cos_i < 0.0 {
+            if p_glass <= 0.0 {
+                return 0.0;
+            }
+            let Some((h, o_h, i_h)) = microfacet::refraction_half_vector(wo, wi, self.eta) else {
+                return 0.0;
+            };
+            let fresnel = microfacet::fresnel_dielectric(o_h, self.eta);
+            let denominator = o_h + self.eta * i_h;
+            let dh_dwi = self.eta * self.eta * i_h.abs() / (denominator * denominator);
+            return p_glass * (1.0 - fresnel) * ggx.pdf_h(&h) * dh_dwi;
+        }
+
+        let h = Vec3::unit_vector(*wo + *wi);
+        let o_h = Vec3::dot(wo, &h);
+        if o_h <= 0.0 {
+            return p_diffuse * cos_i / PI;
+        }
+        let reflection = ggx.pdf_h(&h) / (4.0 * o_h);
+        let fresnel = if p_glass > 0.0 { microfacet::fresnel_dielectric(o_h, self.eta) } else { 0.0 };
+        let clearcoat = if p_clearcoat > 0.0 { self.clearcoat_lobe().pdf_h(&h) / (4.0 * o_h) } else { 0.0 };
+
+        p_diffuse * cos_i / PI + p_specular * reflection + p_glass * fresnel * reflection + p_clearcoat * clearcoat
+    }
+
+    fn sample(&self, wo: &Vec3, sampler: &mut dyn Sampler) -> Option<Vec3> {
+        let probabilities = self.probabilities(wo.z());
+        let mut u = sampler.get_1d();
+        let mut lobe = 0;
+        while lobe < 3 && u >= probabilities[lobe] {
+            u -= probabilities[lobe];
+            lobe += 1;
+        }
+        if probabilities[lobe] <= 0.0 {
+            return None;
+        }
+
+        let u2 = sampler.get_2d();
+        let (wi, refracted) = match lobe {
+            0 => (sampling::cosine_hemisphere(u2), false),
+            1 => (microfacet::reflect(wo, &self.specular().sample_h(u2)), false),
+            2 => {
+                let h = self.specular().sample_h(u2);
+                let o_h = Vec3::dot(wo, &h);
+                if o_h <= 0.0 {
+                    return None; // A facet turned away from wo
+                }
+                let fresnel = microfacet::fresnel_dielectric(o_h, self.eta);
+                if sampler.get_1d() < fresnel {
+                    (microfacet::reflect(wo, &h), false)
+                } else {
+                    (microfacet::refract(wo, &h, self.eta)?, true)
+                }
+            }
+            _ => (microfacet::reflect(wo, &self.clearcoat_lobe().sample_h(u2)), false),
+        };
+        // f and pdf tell reflection from refraction by the side of wi, so a reflection off a steep
+        // facet that ends up below the surface is no sample of this lobe (and is shadowed anyway).
+        if (wi.z() < 0.0) != refracted || wi.z() == 0.0 {
+            return None;
+        }
+        Some(wi)
+    }
+}
+
+impl Material for Principled {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, sampler: &mut dyn Sampler) -> Option<ScatterRecord> {
+        let lobes = self.lobes(rec);
+        let frame = Onb::new(&rec.normal);
+        let wo = frame.to_local(&-Vec3::unit_vector(r_in.direction()));
+
+        let wi = lobes.sample(&wo, sampler)?;
+        let pdf = lobes.pdf(&wo, &wi);
+        if pdf <= 0.0 {
+            return None;
+        }
+        let f = lobes.f(&wo, &wi) * wi.z().abs();
+
+        Some(ScatterRecord {
+            attenuation: f / pdf,
+            ray: Ray::new(rec.p, frame.transform(&wi)),
+            pdf,
+            is_specular: false,
+        })
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> Color {
+        let frame = Onb::new(&rec.normal);
+        let wo = frame.to_local(&-Vec3::unit_vector(r_in.direction()));
+        let wi = frame.to_local(&Vec3::unit_vector(*direction));
+        self.lobes(rec).f(&wo, &wi) * wi.z().abs()
+    }
+
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> f64 {
+        let frame = Onb::new(&rec.normal);
+        let wo = frame.to_local(&-Vec3::unit_vector(r_in.direction()));
+        let wi = frame.to_local(&Vec3::unit_vector(*direction));
+        self.lobes(rec).pdf(&wo, &wi)
+    }
+
+    fn hash_into(&self, hasher: &mut SceneHasher) {
+        hasher.write_str("principled");
+        for texture in [
+            &self.base_color,
+            &self.metallic,
+            &self.roughness,
+            &self.specular,
+            &self.specular_tint,
+            &self.sheen,
+            &self.sheen_tint,
+            &self.clearcoat,
+            &self.clearcoat_gloss,
+            &self.transmission,
+            &self.ior,
+        ] {
+            texture.hash_into(hasher);
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::error::{Error, Result};
+use crate::preview::PreviewMode;
+
+// Settings for Camera::render_progressive.
+// The render runs in passes of `samples_per_pass` samples per pixel and stops at whichever comes first:
+// `max_samples` samples per pixel, `time_limit` wall-clock time, or the image noise dropping below `target_noise`.
+// With `checkpoint` set the render state is saved every `checkpoint_interval` (and when the render ends),
+// and `resume` continues from that file instead of starting over; resuming with a higher `max_samples`
+// refines a finished render further. With `preview` set the image is drawn in the terminal after every
+// pass in place of the progress bar.
+pub struct ProgressiveSettings {
+    pub samples_per_pass: u32,
+    pub max_samples: u32,
+    pub time_limit: Option<Duration>,
+    pub target_noise: Option<f64>, // Average relative standard error of the pixels, e.g. 0.01 for 1%
+    pub checkpoint: Option<PathBuf>,
+    pub checkpoint_interval: Duration,
+    pub resume: bool,
+    pub preview: Option<PreviewMode>,
+}
+
+impl Default for ProgressiveSettings {
+    fn default() -> Self {
+        ProgressiveSettings {
+            samples_per_pass: 4,
+            max_samples: 1024,
+            time_limit: None,
+            target_noise: None,
+            checkpoint: None,
+            checkpoint_interval: Duration::from_secs(60),
+            resume: false,
+            preview: None,
+        }
+    }
+}
+
+impl ProgressiveSettings {
+    pub fn validate(&self) -> Result<()> {
+        if self.samples_per_pass == 0 {
+            return Err(Error::invalid("samples_per_pass", "must be at least 1"));
+        }
+        if self.max_samples == 0 {
+            return Err(Error::invalid("max_samples", "must be at least 1"));
+        }
+        if let Some(target) = self.target_noise
+            && !(target.is_finite() && target > 0.0)
+        {
+            return Err(Error::invalid("target_noise", format!("must be a positive number, got {}", target)));
+        }
+        if self.resume && self.checkpoint.is_none() {
+            return Err(Error::invalid("resume", "needs a checkpoint file to resume from"));
+        }
+        Ok(())
+    }
+}
@@ -1,10 +1,11 @@
 use crate::vec3::Point3;
 use crate::vec3::Vec3;
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Default)]
 pub struct Ray {
     org: Point3,
     dir: Vec3,
+    wavelength: Option<f64>, // Hero wavelength in nm of a spectral render, see spectrum.rs
 }
 
 impl Ray {
@@ -12,15 +13,15 @@ impl Ray {
         Ray {
             org: origin,
             dir: direction,
+            wavelength: None,
         }
     }
-}
 
-impl Default for Ray {
-    fn default() -> Self {
+    // The same ray, carrying the wavelength materials with dispersion should refract.
+    pub fn with_wavelength(self, wavelength: f64) -> Self {
         Ray {
-            org: Point3::default(),
-            dir: Vec3::default(),
+            wavelength: Some(wavelength),
+            ..self
         }
     }
 }
@@ -33,6 +34,9 @@ impl Ray {
     pub fn direction(&self) -> Vec3 {
         self.dir
     }
+    pub fn wavelength(&self) -> Option<f64> {
+        self.wavelength
+    }
 }
 
 //utils
@@ -0,0 +1,428 @@
+use std::any::Any;
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{Cursor, Read};
+use std::net::TcpListener;
+use std::panic::{self, AssertUnwindSafe};
+use std::str::FromStr;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::encoder::ImageFormat;
+use crate::error::{Error, Result};
+use crate::image::Image;
+use crate::render_status::RenderStatus;
+use crate::scene_file::SceneFile;
+use crate::tonemap::ToneMapping;
+
+// A render server: scene files are posted over HTTP, queued, and rendered one after another.
+//
+//     POST   /jobs                  body is a .json or .toml SceneFile, answers {"id": 1}
+//     GET    /jobs                  status of every job
+//     GET    /jobs/{id}             status of one job: state, progress, elapsed and estimated seconds left
+//     GET    /jobs/{id}/image       the finished image, ?format=png (default), hdr, ppm or ppm-binary
+//     DELETE /jobs/{id}             cancels a queued or running job, forgets a finished one
+//
+// Every answer apart from the image is JSON; errors are {"error": "..."} with a 4xx status.
+// Posted scenes can't use image textures: their paths would be files on the server.
+// Finished jobs are forgotten after a day, or sooner once more than MAX_FINISHED of them are kept.
+
+// Largest scene file accepted.
+const MAX_BODY: u64 = 16 << 20;
+
+// Largest image a posted scene may render (4096 x 4096 pixels), so posting a scene can't make the
+// server allocate more than it can hold, and the most samples per pixel it may ask for.
+const MAX_PIXELS: u64 = 1 << 24;
+const MAX_SAMPLES_PER_PIXEL: u32 = 1 << 14;
+
+// Jobs waiting to be rendered; more are refused with 429 until the queue drains.
+const MAX_QUEUED: usize = 64;
+
+// Finished jobs kept with their images, the oldest are forgotten first.
+const MAX_FINISHED: usize = 16;
+const KEEP_FINISHED: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum JobState {
+    Queued,
+    Running,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+struct Job {
+    scene: SceneFile,
+    state: JobState,
+    status: Arc<RenderStatus>,
+    started: Option<Instant>,
+    finished: Option<Instant>,
+    image: Option<Image>,
+    error: Option<String>,
+}
+
+#[derive(Default)]
+struct Jobs {
+    next_id: u64,
+    jobs: BTreeMap<u64, Job>,
+    queue: VecDeque<u64>,
+}
+
+impl Jobs {
+    // Forgets finished jobs that are too old, then the oldest ones beyond MAX_FINISHED.
+    fn forget_finished(&mut self, now: Instant) {
+        let mut finished: Vec<(Instant, u64)> =
+            self.jobs.iter().filter_map(|(&id, job)| job.finished.map(|finished| (finished, id))).collect();
+        finished.sort();
+        let excess = finished.len().saturating_sub(MAX_FINISHED);
+        for (i, (finished, id)) in finished.into_iter().enumerate() {
+            if i < excess || now.duration_since(finished) > KEEP_FINISHED {
+                self.jobs.remove(&id);
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct Shared {
+    jobs: Mutex<Jobs>,
+    queued: Condvar,
+}
+
+#[derive(Serialize)]
+struct JobReport {
+    id: u64,
+    state: JobState,
+    completed: u64,
+    total: u64,
+    progress: f64,
+    elapsed: Option<f64>, // Seconds since the render started
+    eta: Option<f64>,     // Estimated seconds until it is done, while running
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Created {
+    id: u64,
+}
+
+#[derive(Serialize)]
+struct Failure {
+    error: String,
+}
+
+// Serves the HTTP API on `address` (e.g. 127.0.0.1:8080) until the listener fails.
+pub fn serve(address: &str) -> Result<()> {
+    let listener = TcpListener::bind(address).map_err(|e| Error::invalid("serve", format!("{}: {}", address, e)))?;
+    serve_listener(listener)
+}
+
+// Serves the HTTP API on connections to `listener`.
+// Requests are answered on this thread while a second thread renders the queued jobs in order.
+pub fn serve_listener(listener: TcpListener) -> Result<()> {
+    let server = Server::from_listener(listener, None).map_err(|e| Error::invalid("serve", e.to_string()))?;
+    if let Some(address) = server.server_addr().to_ip() {
+        eprintln!("render server listening on http://{}", address);
+    }
+
+    let shared = Arc::new(Shared::default());
+    let renderer = shared.clone();
+    thread::spawn(move || render_jobs(&renderer));
+
+    for mut request in server.incoming_requests() {
+        let response = answer(&shared, &mut request);
+        // A client that hung up before reading its answer is no concern of the server.
+        let _ = request.respond(response);
+    }
+    Ok(())
+}
+
+// The render thread: takes jobs off the queue and renders them, forever.
+// Scenes are built here rather than when posted, so queued jobs only hold their scene files.
+fn render_jobs(shared: &Shared) {
+    loop {
+        let (id, scene, status) = {
+            let mut jobs = lock(&shared.jobs);
+            let id = loop {
+                match jobs.queue.pop_front() {
+                    Some(id) => break id,
+                    None => jobs = shared.queued.wait(jobs).unwrap_or_else(|e| e.into_inner()),
+                }
+            };
+            let Some(job) = jobs.jobs.get_mut(&id) else {
+                continue;
+            };
+            job.state = JobState::Running;
+            job.started = Some(Instant::now());
+            (id, job.scene.clone(), job.status.clone())
+        };
+
+        // A scene that makes the renderer panic fails its job, the thread goes on with the next one.
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            scene.build().and_then(|scene| scene.camera.render_with_status(&scene.world, &scene.lights, &status))
+        }))
+        .unwrap_or_else(|panic| Err(Error::invalid("render", panic_message(panic.as_ref()))));
+
+        let mut jobs = lock(&shared.jobs);
+        // Gone if it was deleted while rendering.
+        if let Some(job) = jobs.jobs.get_mut(&id) {
+            job.finished = Some(Instant::now());
+            match result {
+                Ok(image) => {
+                    job.state = JobState::Done;
+                    job.image = Some(image);
+                }
+                Err(Error::Cancelled) => job.state = JobState::Cancelled,
+                Err(e) => {
+                    job.state = JobState::Failed;
+                    job.error = Some(e.to_string());
+                }
+            }
+        }
+        jobs.forget_finished(Instant::now());
+    }
+}
+
+fn panic_message(panic: &(dyn Any + Send)) -> String {
+    let message = panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown cause");
+    format!("the renderer panicked: {}", message)
+}
+
+fn answer(shared: &Shared, request: &mut Request) -> Response<Cursor<Vec<u8>>> {
+    let url = request.url().to_string();
+    let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+
+    let id = match segments.get(1).filter(|_| segments[0] == "jobs").map(|s| s.parse::<u64>()) {
+        Some(Ok(id)) => Some(id),
+        Some(Err(_)) => return failure(404, "no such job"),
+        None => None,
+    };
+    lock(&shared.jobs).forget_finished(Instant::now());
+    match (request.method(), segments.as_slice(), id) {
+        (Method::Post, ["jobs"], None) => submit(shared, request),
+        (Method::Get, ["jobs"], None) => {
+            let jobs = lock(&shared.jobs);
+            let reports: Vec<JobReport> = jobs.jobs.iter().map(|(&id, job)| report(id, job)).collect();
+            json(200, &reports)
+        }
+        (Method::Get, ["jobs", _], Some(id)) => match lock(&shared.jobs).jobs.get(&id) {
+            Some(job) => json(200, &report(id, job)),
+            None => failure(404, "no such job"),
+        },
+        (Method::Get, ["jobs", _, "image"], Some(id)) => image(shared, id, query),
+        (Method::Delete, ["jobs", _], Some(id)) => delete(shared, id),
+        (_, ["jobs"], None) | (_, ["jobs", _], Some(_)) | (_, ["jobs", _, "image"], Some(_)) => {
+            failure(405, "method not allowed")
+        }
+        _ => failure(404, "not found"),
+    }
+}
+
+fn submit(shared: &Shared, request: &mut Request) -> Response<Cursor<Vec<u8>>> {
+    if request.body_length().is_some_and(|length| length as u64 > MAX_BODY) {
+        return failure(413, "the scene file is too large");
+    }
+    let mut body = String::new();
+    if let Err(e) = request.as_reader().take(MAX_BODY + 1).read_to_string(&mut body) {
+        return failure(400, &format!("cannot read the scene file: {}", e));
+    }
+    if body.len() as u64 > MAX_BODY {
+        return failure(413, "the scene file is too large");
+    }
+
+    // Build it once here, so a bad scene is refused now rather than failing later in the queue.
+    let scene = match SceneFile::parse(&body) {
+        Ok(scene) => scene,
+        Err(e) => return failure(400, &e.to_string()),
+    };
+    if uses_image_textures(&scene) {
+        return failure(400, "image textures can't be used in posted scenes");
+    }
+    let camera = match scene.build() {
+        Ok(scene) => scene.camera,
+        Err(e) => return failure(400, &e.to_string()),
+    };
+    if camera.image_width() as u64 * camera.image_height() as u64 > MAX_PIXELS {
+        return failure(
+            413,
+            &format!("{} x {} pixels is more than the server renders", camera.image_width(), camera.image_height()),
+        );
+    }
+    if camera.samples_per_pixel() > MAX_SAMPLES_PER_PIXEL {
+        return failure(
+            413,
+            &format!("{} samples per pixel is more than the server renders", camera.samples_per_pixel()),
+        );
+    }
+
+    let mut jobs = lock(&shared.jobs);
+    if jobs.queue.len() >= MAX_QUEUED {
+        return failure(429, &format!("{} jobs are already queued", jobs.queue.len()));
+    }
+    jobs.next_id += 1;
+    let id = jobs.next_id;
+    jobs.jobs.insert(
+        id,
+        Job {
+            scene,
+            state: JobState::Queued,
+            status: Arc::new(RenderStatus::new()),
+            started: None,
+            finished: None,
+            image: None,
+            error: None,
+        },
+    );
+    jobs.queue.push_back(id);
+    shared.queued.notify_one();
+    json(201, &Created { id })
+}
+
+// Whether any texture of the scene is read from an image file. Texture descriptions nest in many
+// material parameters, so this looks for their tag in the scene's JSON form instead of walking them.
+fn uses_image_textures(scene: &SceneFile) -> bool {
+    fn is_image(value: &serde_json::Value) -> bool {
+        match value {
+            serde_json::Value::Object(map) => {
+                map.get("type").is_some_and(|t| t == "image") || map.values().any(is_image)
+            }
+            serde_json::Value::Array(values) => values.iter().any(is_image),
+            _ => false,
+        }
+    }
+    // A scene that can't be written as JSON can't be checked, so it is treated as using them.
+    serde_json::to_value(scene).map_or(true, |value| is_image(&value))
+}
+
+fn delete(shared: &Shared, id: u64) -> Response<Cursor<Vec<u8>>> {
+    let mut jobs = lock(&shared.jobs);
+    let Some(job) = jobs.jobs.get_mut(&id) else {
+        return failure(404, "no such job");
+    };
+    match job.state {
+        JobState::Queued => {
+            job.state = JobState::Cancelled;
+            job.finished = Some(Instant::now());
+            let report = report(id, job);
+            jobs.queue.retain(|&queued| queued != id);
+            jobs.forget_finished(Instant::now());
+            json(200, &report)
+        }
+        // The render thread notices at the next row and marks the job cancelled.
+        JobState::Running => {
+            job.status.cancel();
+            json(202, &report(id, job))
+        }
+        JobState::Done | JobState::Failed | JobState::Cancelled => {
+            let report = report(id, job);
+            jobs.jobs.remove(&id);
+            json(200, &report)
+        }
+    }
+}
+
+fn image(shared: &Shared, id: u64, query: &str) -> Response<Cursor<Vec<u8>>> {
+    let format = match query.split('&').find_map(|pair| pair.strip_prefix("format=")) {
+        Some(name) => match ImageFormat::from_str(name) {
+            Ok(format) => format,
+            Err(e) => return failure(400, &e),
+        },
+        None => ImageFormat::Png,
+    };
+
+    // Encoding a large PNG takes a moment, don't hold the lock (and the render thread) meanwhile.
+    let (image, tone_mapping) = {
+        let jobs = lock(&shared.jobs);
+        let Some(job) = jobs.jobs.get(&id) else {
+            return failure(404, "no such job");
+        };
+        match &job.image {
+            Some(image) => (image.clone(), job.scene.camera.tone_mapping),
+            None => return failure(409, &format!("job {} has no image, it is {}", id, state_name(job.state))),
+        }
+    };
+    encode(&image, format, tone_mapping)
+}
+
+fn encode(image: &Image, format: ImageFormat, tone_mapping: ToneMapping) -> Response<Cursor<Vec<u8>>> {
+    let mut bytes = Vec::new();
+    if let Err(e) = format.encoder(tone_mapping).encode(image, &mut bytes) {
+        return failure(500, &format!("cannot encode the image: {}", e));
+    }
+    let content_type = match format {
+        ImageFormat::Png => "image/png",
+        ImageFormat::Hdr => "image/vnd.radiance",
+        ImageFormat::PpmAscii | ImageFormat::PpmBinary => "image/x-portable-pixmap",
+    };
+    Response::from_data(bytes).with_header(header("Content-Type", content_type))
+}
+
+fn report(id: u64, job: &Job) -> JobReport {
+    let progress = match job.state {
+        JobState::Done => 1.0,
+        _ => job.status.fraction(),
+    };
+    let elapsed = job.started.map(|started| job.finished.unwrap_or_else(Instant::now) - started);
+    let eta = match (job.state, elapsed) {
+        (JobState::Running, Some(elapsed)) if progress > 0.0 => {
+            Some(elapsed.as_secs_f64() * (1.0 - progress) / progress)
+        }
+        _ => None,
+    };
+    JobReport {
+        id,
+        state: job.state,
+        completed: job.status.completed(),
+        total: job.status.total(),
+        progress,
+        elapsed: elapsed.map(|e| e.as_secs_f64()),
+        eta,
+        error: job.error.clone(),
+    }
+}
+
+fn state_name(state: JobState) -> &'static str {
+    match state {
+        JobState::Queued => "queued",
+        JobState::Running => "running",
+        JobState::Done => "done",
+        JobState::Failed => "failed",
+        JobState::Cancelled => "cancelled",
+    }
+}
+
+fn json<T: Serialize>(status: u16, value: &T) -> Response<Cursor<Vec<u8>>> {
+    // Serialising these plain structs can't fail.
+    let body = serde_json::to_vec(value).unwrap_or_default();
+    Response::from_data(body)
+        .with_status_code(status)
+        .with_header(header("Content-Type", "application/json"))
+}
+
+fn failure(status: u16, message: &str) -> Response<Cursor<Vec<u8>>> {
+    json(
+        status,
+        &Failure {
+            error: message.to_string(),
+        },
+    )
+}
+
+fn header(name: &str, value: &str) -> Header {
+    // Only called with constant ASCII names and values, which are always valid.
+    Header::from_bytes(name.as_bytes(), value.as_bytes()).expect("valid header")
+}
+
+// The jobs stay usable even if the render thread panicked while holding them.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
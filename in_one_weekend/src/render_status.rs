@@ -0,0 +1,55 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+// Progress of a render that other threads can watch, and a flag they can set to stop it.
+// Camera::render_with_status advances it row by row (what its progress bar shows) and returns
+// Error::Cancelled at the next row after cancel is called.
+#[derive(Default)]
+pub struct RenderStatus {
+    completed: AtomicU64,
+    total: AtomicU64,
+    cancelled: AtomicBool,
+}
+
+impl RenderStatus {
+    pub fn new() -> Self {
+        RenderStatus::default()
+    }
+}
+
+//getters
+impl RenderStatus {
+    pub fn completed(&self) -> u64 {
+        self.completed.load(Ordering::Relaxed)
+    }
+    pub fn total(&self) -> u64 {
+        self.total.load(Ordering::Relaxed)
+    }
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+//utils
+impl RenderStatus {
+    // Done fraction in [0, 1], 0 before the render has started.
+    pub fn fraction(&self) -> f64 {
+        let total = self.total();
+        if total == 0 {
+            return 0.0;
+        }
+        (self.completed() as f64 / total as f64).min(1.0)
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub(crate) fn start(&self, total: u64) {
+        self.completed.store(0, Ordering::Relaxed);
+        self.total.store(total, Ordering::Relaxed);
+    }
+
+    pub(crate) fn advance(&self, steps: u64) {
+        self.completed.fetch_add(steps, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,182 @@
+use crate::color::Color;
+use crate::hittable::HitRecord;
+use crate::material::{Material, ScatterRecord};
+use crate::microfacet::{self, Ggx};
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::scene_hash::SceneHasher;
+use crate::thin_film::ThinFilm;
+use crate::vec3::Vec3;
+
+// Frosted glass: a GGX microfacet boundary that reflects or refracts through each facet with the
+// exact dielectric Fresnel reflectance (Walter et al. 2007), sampled from the visible normals.
+// Like Dielectric it is colorless and refraction doesn't scale radiance by 1 / eta^2.
+// `anisotropy` stretches the roughness along the bitangent of the hit's tangent frame.
+// A thin `film` on top tints reflection and transmission with its interference colors; the two
+// are then picked with the mean of the reflectance over the channels.
+pub struct RoughDielectric {
+    refraction_index: f64,
+    roughness: f64,
+    anisotropy: f64,
+    film: Option<ThinFilm>,
+}
+
+impl RoughDielectric {
+    pub fn new(refraction_index: f64, roughness: f64, anisotropy: f64) -> Self {
+        RoughDielectric {
+            refraction_index,
+            roughness: roughness.clamp(0.0, 1.0),
+            anisotropy: anisotropy.clamp(0.0, 1.0),
+            film: None,
+        }
+    }
+
+    pub fn film(mut self, film: ThinFilm) -> Self {
+        self.film = Some(film);
+        self
+    }
+
+    fn distribution(&self) -> Ggx {
+        Ggx::from_roughness_anisotropic(self.roughness, self.anisotropy)
+    }
+
+    // Index below the surface over the index above it, as seen by the ray.
+    fn eta(&self, rec: &HitRecord) -> f64 {
+        if rec.front_face {
+            self.refraction_index
+        } else {
+            1.0 / self.refraction_index
+        }
+    }
+
+    // Reflectance of a facet seen at cos_theta (on the side of wo), per channel.
+    fn fresnel(&self, cos_theta: f64, eta: f64, rec: &HitRecord, wavelength: Option<f64>) -> Color {
+        match &self.film {
+            None => {
+                let fresnel = microfacet::fresnel_dielectric(cos_theta, eta);
+                Color::new(fresnel, fresnel, fresnel)
+            }
+            Some(film) => film.dielectric_reflectance(rec, cos_theta, wavelength, self.refraction_index),
+        }
+    }
+
+    // Probability of reflecting off a facet with reflectance `fresnel` rather than refracting.
+    fn reflect_probability(fresnel: &Color) -> f64 {
+        (fresnel.x() + fresnel.y() + fresnel.z()) / 3.0
+    }
+
+    // BSDF in the local frame, reflection when wi is above the surface and refraction below.
+    fn f(&self, wo: &Vec3, wi: &Vec3, eta: f64, rec: &HitRecord, wavelength: Option<f64>) -> Color {
+        let (cos_o, cos_i) = (wo.z(), wi.z());
+        if cos_o <= 0.0 || cos_i == 0.0 {
+            return Color::default();
+        }
+        let ggx = self.distribution();
+
+        if cos_i > 0.0 {
+            let h = Vec3::unit_vector(*wo + *wi);
+            let fresnel = self.fresnel(Vec3::dot(wo, &h), eta, rec, wavelength);
+            return (ggx.d(&h) * ggx.g(wo, wi) / (4.0 * cos_o * cos_i)) * fresnel;
+        }
+
+        let Some((h, o_h, i_h)) = microfacet::refraction_half_vector(wo, wi, eta) else {
+            return Color::default();
+        };
+        let fresnel = self.fresnel(o_h, eta, rec, wavelength);
+        let denominator = o_h + eta * i_h;
+        (ggx.d(&h) * ggx.g(wo, wi) * eta * eta * (i_h * o_h).abs() / (cos_i.abs() * cos_o * denominator * denominator))
+            * (Color::new(1.0, 1.0, 1.0) - fresnel)
+    }
+
+    fn local_pdf(&self, wo: &Vec3, wi: &Vec3, eta: f64, rec: &HitRecord, wavelength: Option<f64>) -> f64 {
+        let (cos_o, cos_i) = (wo.z(), wi.z());
+        if cos_o <= 0.0 || cos_i == 0.0 {
+            return 0.0;
+        }
+        let ggx = self.distribution();
+
+        if cos_i > 0.0 {
+            let h = Vec3::unit_vector(*wo + *wi);
+            let o_h = Vec3::dot(wo, &h);
+            let reflect = RoughDielectric::reflect_probability(&self.fresnel(o_h, eta, rec, wavelength));
+            return ggx.pdf_visible(wo, &h) * reflect / (4.0 * o_h);
+        }
+
+        let Some((h, o_h, i_h)) = microfacet::refraction_half_vector(wo, wi, eta) else {
+            return 0.0;
+        };
+        let reflect = RoughDielectric::reflect_probability(&self.fresnel(o_h, eta, rec, wavelength));
+        let denominator = o_h + eta * i_h;
+        ggx.pdf_visible(wo, &h) * (1.0 - reflect) * eta * eta * i_h.abs() / (denominator * denominator)
+    }
+}
+
+impl Material for RoughDielectric {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, sampler: &mut dyn Sampler) -> Option<ScatterRecord> {
+        let frame = Onb::with_tangent(&rec.normal, &rec.tangent);
+        let wo = frame.to_local(&-Vec3::unit_vector(r_in.direction()));
+        if wo.z() <= 0.0 {
+            return None;
+        }
+        let eta = self.eta(rec);
+        let wavelength = r_in.wavelength();
+
+        let h = self.distribution().sample_visible(&wo, sampler.get_2d());
+        let fresnel = self.fresnel(Vec3::dot(&wo, &h), eta, rec, wavelength);
+        let wi = if sampler.get_1d() < RoughDielectric::reflect_probability(&fresnel) {
+            let wi = microfacet::reflect(&wo, &h);
+            // Reflected below the surface by a steep facet: the path is shadowed.
+            if wi.z() <= 0.0 {
+                return None;
+            }
+            wi
+        } else {
+            let wi = microfacet::refract(&wo, &h, eta)?;
+            if wi.z() >= 0.0 {
+                return None;
+            }
+            wi
+        };
+
+        let pdf = self.local_pdf(&wo, &wi, eta, rec, wavelength);
+        if pdf <= 0.0 {
+            return None;
+        }
+
+        Some(ScatterRecord {
+            attenuation: self.f(&wo, &wi, eta, rec, wavelength) * wi.z().abs() / pdf,
+            ray: Ray::new(rec.p, frame.transform(&wi)),
+            pdf,
+            is_specular: false,
+        })
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> Color {
+        let frame = Onb::with_tangent(&rec.normal, &rec.tangent);
+        let wo = frame.to_local(&-Vec3::unit_vector(r_in.direction()));
+        let wi = frame.to_local(&Vec3::unit_vector(*direction));
+        self.f(&wo, &wi, self.eta(rec), rec, r_in.wavelength()) * wi.z().abs()
+    }
+
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> f64 {
+        let frame = Onb::with_tangent(&rec.normal, &rec.tangent);
+        let wo = frame.to_local(&-Vec3::unit_vector(r_in.direction()));
+        let wi = frame.to_local(&Vec3::unit_vector(*direction));
+        self.local_pdf(&wo, &wi, self.eta(rec), rec, r_in.wavelength())
+    }
+
+    fn is_dispersive(&self) -> bool {
+        self.film.is_some()
+    }
+
+    fn hash_into(&self, hasher: &mut SceneHasher) {
+        hasher.write_str("rough dielectric");
+        hasher.write_f64(self.refraction_index);
+        hasher.write_f64(self.roughness);
+        hasher.write_f64(self.anisotropy);
+        if let Some(film) = &self.film {
+            film.hash_into(hasher);
+        }
+    }
+}
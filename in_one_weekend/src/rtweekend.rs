@@ -1,9 +1,8 @@
-use rand::rngs::ThreadRng;
 use rand::{rng, Rng};
 
 // Constants
 pub const INFINITY: f64 = f64::INFINITY;
-pub const PI: f64 = 3.1415926535897932385;
+pub const PI: f64 = std::f64::consts::PI;
 
 // Utility Functions
 pub fn degrees_to_radians(degrees: f64) -> f64 {
@@ -0,0 +1,127 @@
+use serde::{Deserialize, Serialize};
+
+use crate::blue_noise_sampler::BlueNoiseSampler;
+use crate::enum_names::enum_names;
+use crate::halton_sampler::HaltonSampler;
+use crate::independent_sampler::IndependentSampler;
+use crate::sobol_sampler::SobolSampler;
+use crate::stratified_sampler::StratifiedSampler;
+
+// Source of the random numbers used to build a path.
+// Every sample of a pixel is a point in a high dimensional unit hypercube: the camera asks for
+// `start_pixel_sample` and then consumes dimensions in a fixed order (pixel offset first, then lens,
+// time, BSDF, ...), so better distributed points directly translate into less noise.
+pub trait Sampler {
+    // Prepares the `sample_index`-th sample of pixel i, j and resets the dimension counter.
+    fn start_pixel_sample(&mut self, i: u32, j: u32, sample_index: u32);
+    // Next dimension, in [0, 1).
+    fn get_1d(&mut self) -> f64;
+    // Next two dimensions, in [0, 1)^2.
+    fn get_2d(&mut self) -> (f64, f64);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SamplerKind {
+    #[default]
+    Independent,
+    Stratified,
+    Halton,
+    Sobol,
+    BlueNoise,
+}
+
+enum_names!(SamplerKind, "sampler", {
+    Independent => "independent",
+    Stratified => "stratified",
+    Halton => "halton",
+    Sobol => "sobol",
+    BlueNoise => "blue-noise",
+});
+
+impl SamplerKind {
+    // `samples_per_pixel` is the number of samples a pixel is expected to get;
+    // samplers that stratify use it to size their strata.
+    // Every value only depends on the pixel, sample index, dimension and `seed`, so the same seed
+    // gives the same image no matter in which order (or on which thread) pixels are rendered.
+    pub fn create(&self, samples_per_pixel: u32, seed: u64) -> Box<dyn Sampler> {
+        match self {
+            SamplerKind::Independent => Box::new(IndependentSampler::new(seed)),
+            SamplerKind::Stratified => Box::new(StratifiedSampler::new(samples_per_pixel, seed)),
+            SamplerKind::Halton => Box::new(HaltonSampler::new(seed)),
+            SamplerKind::Sobol => Box::new(SobolSampler::new(seed)),
+            SamplerKind::BlueNoise => Box::new(BlueNoiseSampler::new(seed)),
+        }
+    }
+}
+
+// Helpers shared by the sampler implementations.
+
+// 64-bit finalizer (from MurmurHash3 / SplitMix) used to derive well mixed seeds.
+#[inline]
+pub fn mix_bits(mut v: u64) -> u64 {
+    v ^= v >> 31;
+    v = v.wrapping_mul(0x7fb5_d329_728e_a185);
+    v ^= v >> 27;
+    v = v.wrapping_mul(0x81da_def4_bc2d_d44d);
+    v ^= v >> 33;
+    v
+}
+
+// Hash of a pixel, a dimension and a seed.
+#[inline]
+pub fn hash_pixel(i: u32, j: u32, dimension: u32, seed: u64) -> u64 {
+    mix_bits(mix_bits(mix_bits(((i as u64) << 32) | (j as u64)) ^ (dimension as u64)) ^ seed)
+}
+
+// Uniform value in [0, 1) for one dimension of one sample of a pixel, a counter based random number.
+#[inline]
+pub fn sample_hash_to_unit(i: u32, j: u32, sample_index: u32, dimension: u32, seed: u64) -> f64 {
+    hash_to_unit(hash_pixel(i, j, dimension, mix_bits(seed) ^ sample_index as u64))
+}
+
+// Maps a 32-bit fixed point value to [0, 1).
+#[inline]
+pub fn u32_to_unit(v: u32) -> f64 {
+    (v as f64 / 4_294_967_296.0).min(1.0 - f64::EPSILON)
+}
+
+// Maps a hash to [0, 1).
+#[inline]
+pub fn hash_to_unit(h: u64) -> f64 {
+    u32_to_unit((h >> 32) as u32)
+}
+
+// Element `i` of a pseudo-random permutation of [0, l), selected by `p` (Kensler, "Correlated Multi-Jittered Sampling").
+pub fn permutation_element(mut i: u32, l: u32, p: u32) -> u32 {
+    let mut w = l.wrapping_sub(1);
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170_893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929_eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | (p >> 27));
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860_a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < l {
+            break;
+        }
+    }
+    (i.wrapping_add(p)) % l
+}
@@ -0,0 +1,42 @@
+use crate::rtweekend::PI;
+use crate::vec3::Vec3;
+
+// Warps from uniform points in [0, 1)^2 to directions, all around the +Z axis.
+// Use an Onb to orient them around a normal or any other direction.
+
+// Cosine weighted direction on the hemisphere, pdf = cos(theta) / pi.
+pub fn cosine_hemisphere(u: (f64, f64)) -> Vec3 {
+    let phi = 2.0 * PI * u.0;
+    let r = u.1.sqrt();
+    Vec3::new(phi.cos() * r, phi.sin() * r, (1.0 - u.1).max(0.0).sqrt())
+}
+
+// Uniform direction on the unit sphere, pdf = 1 / (4 pi).
+pub fn uniform_sphere(u: (f64, f64)) -> Vec3 {
+    let z = 1.0 - 2.0 * u.0;
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * PI * u.1;
+    Vec3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+// Uniform direction inside the cone of half-angle acos(cos_max), pdf = 1 / (2 pi (1 - cos_max)).
+pub fn uniform_cone(u: (f64, f64), cos_max: f64) -> Vec3 {
+    let cos_theta = 1.0 - u.0 * (1.0 - cos_max);
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * u.1;
+    Vec3::new(phi.cos() * sin_theta, phi.sin() * sin_theta, cos_theta)
+}
+
+// Uniform point on the unit disk in the XY plane (Shirley-Chiu concentric mapping), pdf = 1 / pi.
+pub fn concentric_disk(u: (f64, f64)) -> Vec3 {
+    let (x, y) = (2.0 * u.0 - 1.0, 2.0 * u.1 - 1.0);
+    if x == 0.0 && y == 0.0 {
+        return Vec3::default();
+    }
+    let (r, theta) = if x.abs() > y.abs() {
+        (x, PI / 4.0 * (y / x))
+    } else {
+        (y, PI / 2.0 - PI / 4.0 * (x / y))
+    };
+    Vec3::new(r * theta.cos(), r * theta.sin(), 0.0)
+}
@@ -0,0 +1,528 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::camera::Camera;
+use crate::camera_settings::{CameraSettings, SettingsFormat};
+use crate::checker_texture::CheckerTexture;
+use crate::color::Color;
+use crate::conductor::{ComplexIor, Conductor};
+use crate::dielectric::{Dielectric, Dispersion};
+use crate::directional_light::DirectionalLight;
+use crate::error::{Error, Result};
+use crate::hittable::Hittable;
+use crate::hittable_list::HittableList;
+use crate::image_texture::ImageTexture;
+use crate::lambertian::Lambertian;
+use crate::light::Light;
+use crate::light_list::LightList;
+use crate::material::Material;
+use crate::metal::Metal;
+use crate::point_light::PointLight;
+use crate::principled::Principled;
+use crate::rough_dielectric::RoughDielectric;
+use crate::spectrum::{LAMBDA_MAX, LAMBDA_MIN};
+use crate::sphere::Sphere;
+use crate::subsurface::Subsurface;
+use crate::spot_light::SpotLight;
+use crate::texture::{self, Texture};
+use crate::thin_film::ThinFilm;
+use crate::vec3::{Point3, Vec3};
+
+// A scene and the camera looking at it, as written in a .json or .toml scene file:
+//
+//     [camera]
+//     image_width = 400
+//     samples_per_pixel = 100
+//
+//     [[objects]]
+//     type = "sphere"
+//     center = [0.0, 0.0, -1.0]
+//     radius = 0.5
+//     material = { type = "lambertian", albedo = [0.4, 0.4, 0.4] }
+//
+//     [[lights]]
+//     type = "point"
+//     position = [1.0, 2.0, 0.0]
+//     intensity = [10.0, 10.0, 10.0]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SceneFile {
+    pub camera: CameraSettings,
+    pub objects: Vec<ObjectDescription>,
+    pub lights: Vec<LightDescription>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum ObjectDescription {
+    Sphere {
+        center: Point3,
+        radius: f64,
+        material: MaterialDescription,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum MaterialDescription {
+    Lambertian {
+        albedo: Color,
+    },
+    Metal {
+        albedo: Color,
+        #[serde(default)]
+        fuzz: f64,
+    },
+    // Either a fixed refraction_index or a dispersion, which spectral renders refract per wavelength.
+    Dielectric {
+        refraction_index: Option<f64>,
+        dispersion: Option<DispersionDescription>,
+        film: Option<FilmDescription>,
+    },
+    // A measured `metal`, or any conductor given by its complex index eta + i k.
+    Conductor {
+        metal: Option<ConductorPreset>,
+        eta: Option<Color>,
+        k: Option<Color>,
+        #[serde(default)]
+        roughness: f64,
+        #[serde(default)]
+        anisotropy: f64,
+        film: Option<FilmDescription>,
+    },
+    RoughDielectric {
+        refraction_index: f64,
+        roughness: f64,
+        #[serde(default)]
+        anisotropy: f64,
+        film: Option<FilmDescription>,
+    },
+    Principled(Box<PrincipledDescription>),
+    // Translucent, see subsurface.rs; the object must be closed.
+    Subsurface {
+        albedo: Color,
+        mean_free_path: Color,
+        #[serde(default = "default_subsurface_ior")]
+        refraction_index: f64,
+    },
+}
+
+fn default_subsurface_ior() -> f64 {
+    1.4
+}
+
+// A glass by name, or a dispersion formula:
+//     dispersion = "bk7"
+//     dispersion = { type = "cauchy", a = 1.5046, b = 0.0042 }
+//     dispersion = { type = "sellmeier", b = [1.0396, 0.2318, 1.0105], c = [0.0060, 0.0200, 103.56] }
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum DispersionDescription {
+    Glass(GlassPreset),
+    Formula(Dispersion),
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GlassPreset {
+    Bk7,
+    FusedSilica,
+    DenseFlint,
+    Diamond,
+}
+
+impl DispersionDescription {
+    pub fn dispersion(&self) -> Dispersion {
+        match self {
+            DispersionDescription::Glass(GlassPreset::Bk7) => Dispersion::BK7,
+            DispersionDescription::Glass(GlassPreset::FusedSilica) => Dispersion::FUSED_SILICA,
+            DispersionDescription::Glass(GlassPreset::DenseFlint) => Dispersion::DENSE_FLINT,
+            DispersionDescription::Glass(GlassPreset::Diamond) => Dispersion::DIAMOND,
+            DispersionDescription::Formula(dispersion) => *dispersion,
+        }
+    }
+}
+
+// A thin film coating a dielectric, conductor or rough dielectric, `thickness` in nanometres:
+//     film = { thickness = 400, ior = 1.33 }
+//     film = { thickness = { type = "image", path = "textures/swirl.png" }, thickness_scale = 800, ior = 1.45 }
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FilmDescription {
+    pub thickness: TextureDescription,
+    pub thickness_scale: Option<f64>, // Multiplies the thickness, for image textures whose values are in [0, 1]
+    pub ior: f64,
+}
+
+impl FilmDescription {
+    pub fn build(&self) -> Result<ThinFilm> {
+        if let TextureDescription::Value(thickness) = self.thickness
+            && !(thickness.is_finite() && thickness >= 0.0)
+        {
+            return Err(Error::invalid("materials", "a film thickness must not be negative"));
+        }
+        if !(self.ior.is_finite() && self.ior > 0.0) {
+            return Err(Error::invalid("materials", "a film needs a positive ior"));
+        }
+        let mut film = ThinFilm::new(self.thickness.build(false)?, self.ior);
+        if let Some(scale) = self.thickness_scale {
+            if !(scale.is_finite() && scale >= 0.0) {
+                return Err(Error::invalid("materials", "a film thickness_scale must not be negative"));
+            }
+            film = film.thickness_scale(scale);
+        }
+        Ok(film)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConductorPreset {
+    Gold,
+    Copper,
+    #[serde(alias = "aluminum")]
+    Aluminium,
+    Silver,
+}
+
+impl ConductorPreset {
+    pub fn ior(&self) -> ComplexIor {
+        match self {
+            ConductorPreset::Gold => ComplexIor::GOLD,
+            ConductorPreset::Copper => ComplexIor::COPPER,
+            ConductorPreset::Aluminium => ComplexIor::ALUMINIUM,
+            ConductorPreset::Silver => ComplexIor::SILVER,
+        }
+    }
+}
+
+// Parameters left out take Principled's defaults.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrincipledDescription {
+    pub base_color: TextureDescription,
+    pub metallic: Option<TextureDescription>,
+    pub roughness: Option<TextureDescription>,
+    pub specular: Option<TextureDescription>,
+    pub specular_tint: Option<TextureDescription>,
+    pub sheen: Option<TextureDescription>,
+    pub sheen_tint: Option<TextureDescription>,
+    pub clearcoat: Option<TextureDescription>,
+    pub clearcoat_gloss: Option<TextureDescription>,
+    pub transmission: Option<TextureDescription>,
+    pub ior: Option<TextureDescription>,
+}
+
+// A material parameter: a number, a color, or a pattern.
+//     roughness = 0.3
+//     base_color = [0.8, 0.1, 0.1]
+//     base_color = { type = "checker", scale = 0.5, even = 0.9, odd = [0.2, 0.3, 0.1] }
+//     base_color = { type = "image", path = "textures/earth.png" }
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum TextureDescription {
+    Value(f64),
+    Color(Color),
+    Pattern(PatternDescription),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum PatternDescription {
+    Checker {
+        scale: f64,
+        even: Box<TextureDescription>,
+        odd: Box<TextureDescription>,
+    },
+    Image {
+        path: PathBuf, // Relative to the working directory
+        srgb: Option<bool>, // Defaults to true for base_color and false for the other parameters
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum LightDescription {
+    Point {
+        position: Point3,
+        intensity: Color,
+    },
+    Spot {
+        position: Point3,
+        direction: Vec3,
+        intensity: Color,
+        inner_angle: f64, // Degrees, full intensity inside
+        outer_angle: f64, // Degrees, no light outside
+    },
+    Directional {
+        direction: Vec3,
+        irradiance: Color,
+    },
+}
+
+// What a SceneFile describes, ready to render.
+pub struct Scene {
+    pub camera: Camera,
+    pub world: HittableList,
+    pub lights: LightList,
+}
+
+impl SceneFile {
+    // Reads a .json or .toml scene file.
+    pub fn load(path: &Path) -> Result<Self> {
+        SettingsFormat::from_path(path)?.load(path)
+    }
+
+    // Parses a scene from JSON or TOML text, telling them apart by the opening brace of JSON.
+    pub fn parse(text: &str) -> Result<Self> {
+        SettingsFormat::detect(text).parse(text)
+    }
+
+    // Creates the camera, objects and lights, or reports the first one that makes no sense.
+    pub fn build(&self) -> Result<Scene> {
+        let camera = Camera::new(self.camera.clone())?;
+
+        let mut world = HittableList::new();
+        for object in &self.objects {
+            match object {
+                ObjectDescription::Sphere {
+                    center,
+                    radius,
+                    material,
+                } => {
+                    if !(radius.is_finite() && *radius > 0.0 && finite(center)) {
+                        return Err(Error::invalid("objects", format!("sphere at {} has a bad radius or center", center)));
+                    }
+                    let boundary = Arc::new(Sphere::boundary(*center, *radius));
+                    world.add(Box::new(Sphere::new(*center, *radius, material.build(boundary)?)));
+                }
+            }
+        }
+
+        let mut lights = LightList::new();
+        for light in &self.lights {
+            lights.add(light.build()?);
+        }
+
+        Ok(Scene { camera, world, lights })
+    }
+}
+
+impl MaterialDescription {
+    // `boundary` is the shape of the object the material is on, for materials that trace rays inside it.
+    pub fn build(&self, boundary: Arc<dyn Hittable>) -> Result<Arc<dyn Material>> {
+        let material: Arc<dyn Material> = match self {
+            MaterialDescription::Lambertian { albedo } => {
+                check_color("lambertian albedo", albedo)?;
+                Arc::new(Lambertian::new(*albedo))
+            }
+            MaterialDescription::Metal { albedo, fuzz } => {
+                check_color("metal albedo", albedo)?;
+                Arc::new(Metal::new(*albedo, *fuzz))
+            }
+            MaterialDescription::Dielectric {
+                refraction_index,
+                dispersion,
+                film,
+            } => {
+                let dielectric = match (refraction_index, dispersion) {
+                    (Some(refraction_index), None) => {
+                        if !(refraction_index.is_finite() && *refraction_index > 0.0) {
+                            return Err(Error::invalid("materials", "a dielectric needs a positive refraction_index"));
+                        }
+                        Dielectric::new(*refraction_index)
+                    }
+                    (None, Some(dispersion)) => {
+                        let dispersion = dispersion.dispersion();
+                        // The index over the whole visible range must make sense, not only at one wavelength.
+                        let sane = |lambda: f64| {
+                            let ior = dispersion.ior(lambda);
+                            ior.is_finite() && ior > 0.0
+                        };
+                        if !(sane(LAMBDA_MIN) && sane(Dispersion::SODIUM_D) && sane(LAMBDA_MAX)) {
+                            return Err(Error::invalid("materials", "the dispersion gives no positive refractive index"));
+                        }
+                        Dielectric::dispersive(dispersion)
+                    }
+                    _ => {
+                        return Err(Error::invalid(
+                            "materials",
+                            "a dielectric needs either a refraction_index or a dispersion",
+                        ));
+                    }
+                };
+                match film {
+                    Some(film) => Arc::new(dielectric.film(film.build()?)),
+                    None => Arc::new(dielectric),
+                }
+            }
+            MaterialDescription::Conductor {
+                metal,
+                eta,
+                k,
+                roughness,
+                anisotropy,
+                film,
+            } => {
+                let ior = match (metal, eta, k) {
+                    (Some(metal), None, None) => metal.ior(),
+                    (None, Some(eta), Some(k)) => {
+                        check_color("conductor eta", eta)?;
+                        check_color("conductor k", k)?;
+                        ComplexIor::new(*eta, *k)
+                    }
+                    _ => return Err(Error::invalid("materials", "a conductor needs either a metal or both eta and k")),
+                };
+                check_unit("conductor", *roughness, *anisotropy)?;
+                let conductor = Conductor::new(ior, *roughness, *anisotropy);
+                match film {
+                    Some(film) => Arc::new(conductor.film(film.build()?)),
+                    None => Arc::new(conductor),
+                }
+            }
+            MaterialDescription::RoughDielectric {
+                refraction_index,
+                roughness,
+                anisotropy,
+                film,
+            } => {
+                if !(refraction_index.is_finite() && *refraction_index > 0.0) {
+                    return Err(Error::invalid("materials", "a rough dielectric needs a positive refraction_index"));
+                }
+                check_unit("rough dielectric", *roughness, *anisotropy)?;
+                let rough_dielectric = RoughDielectric::new(*refraction_index, *roughness, *anisotropy);
+                match film {
+                    Some(film) => Arc::new(rough_dielectric.film(film.build()?)),
+                    None => Arc::new(rough_dielectric),
+                }
+            }
+            MaterialDescription::Principled(principled) => Arc::new(principled.build()?),
+            MaterialDescription::Subsurface {
+                albedo,
+                mean_free_path,
+                refraction_index,
+            } => {
+                check_color("subsurface albedo", albedo)?;
+                if !(finite(mean_free_path) && mean_free_path.vector.iter().all(|&d| d > 0.0)) {
+                    return Err(Error::invalid("materials", "a subsurface mean_free_path must be positive in every channel"));
+                }
+                if !(refraction_index.is_finite() && *refraction_index > 0.0) {
+                    return Err(Error::invalid("materials", "a subsurface material needs a positive refraction_index"));
+                }
+                Arc::new(Subsurface::new(*albedo, *mean_free_path, *refraction_index, boundary))
+            }
+        };
+        Ok(material)
+    }
+}
+
+impl PrincipledDescription {
+    pub fn build(&self) -> Result<Principled> {
+        // A textured ior is only checked where it's looked up, see Principled.
+        if let Some(TextureDescription::Value(ior)) = self.ior
+            && !(ior.is_finite() && ior > 0.0)
+        {
+            return Err(Error::invalid("materials", "a principled material needs a positive ior"));
+        }
+        let mut principled = Principled::new(self.base_color.build(true)?);
+        let parameters = [
+            (&self.metallic, Principled::metallic as fn(Principled, Arc<dyn Texture>) -> Principled),
+            (&self.roughness, Principled::roughness),
+            (&self.specular, Principled::specular),
+            (&self.specular_tint, Principled::specular_tint),
+            (&self.sheen, Principled::sheen),
+            (&self.sheen_tint, Principled::sheen_tint),
+            (&self.clearcoat, Principled::clearcoat),
+            (&self.clearcoat_gloss, Principled::clearcoat_gloss),
+            (&self.transmission, Principled::transmission),
+            (&self.ior, Principled::ior),
+        ];
+        for (description, set) in parameters {
+            if let Some(description) = description {
+                principled = set(principled, description.build(false)?);
+            }
+        }
+        Ok(principled)
+    }
+}
+
+impl TextureDescription {
+    // `srgb` is how image files are decoded unless the description says otherwise.
+    pub fn build(&self, srgb: bool) -> Result<Arc<dyn Texture>> {
+        let texture: Arc<dyn Texture> = match self {
+            TextureDescription::Value(value) => {
+                if !value.is_finite() {
+                    return Err(Error::invalid("textures", format!("{} is not a finite value", value)));
+                }
+                texture::constant(*value)
+            }
+            TextureDescription::Color(color) => {
+                check_color("texture color", color)?;
+                texture::solid(*color)
+            }
+            TextureDescription::Pattern(PatternDescription::Checker { scale, even, odd }) => {
+                if !(scale.is_finite() && *scale > 0.0) {
+                    return Err(Error::invalid("textures", "a checker texture needs a positive scale"));
+                }
+                Arc::new(CheckerTexture::new(*scale, even.build(srgb)?, odd.build(srgb)?))
+            }
+            TextureDescription::Pattern(PatternDescription::Image { path, srgb: image_srgb }) => {
+                Arc::new(ImageTexture::load(path, image_srgb.unwrap_or(srgb))?)
+            }
+        };
+        Ok(texture)
+    }
+}
+
+impl LightDescription {
+    pub fn build(&self) -> Result<Box<dyn Light>> {
+        let light: Box<dyn Light> = match self {
+            LightDescription::Point { position, intensity } => {
+                check_color("point light intensity", intensity)?;
+                Box::new(PointLight::new(*position, *intensity))
+            }
+            LightDescription::Spot {
+                position,
+                direction,
+                intensity,
+                inner_angle,
+                outer_angle,
+            } => {
+                check_color("spot light intensity", intensity)?;
+                check_direction("spot light direction", direction)?;
+                Box::new(SpotLight::new(*position, *direction, *intensity, *inner_angle, *outer_angle))
+            }
+            LightDescription::Directional { direction, irradiance } => {
+                check_color("directional light irradiance", irradiance)?;
+                check_direction("directional light direction", direction)?;
+                Box::new(DirectionalLight::new(*direction, *irradiance))
+            }
+        };
+        Ok(light)
+    }
+}
+
+fn finite(v: &Vec3) -> bool {
+    v.vector.iter().all(|c| c.is_finite())
+}
+
+fn check_color(what: &str, c: &Color) -> Result<()> {
+    if !finite(c) || c.vector.iter().any(|&v| v < 0.0) {
+        return Err(Error::invalid("scene", format!("{} must be finite and not negative, got {}", what, c)));
+    }
+    Ok(())
+}
+
+fn check_direction(what: &str, d: &Vec3) -> Result<()> {
+    if !finite(d) || d.near_zero() {
+        return Err(Error::invalid("scene", format!("{} must be a finite, non-zero vector", what)));
+    }
+    Ok(())
+}
+
+fn check_unit(what: &str, roughness: f64, anisotropy: f64) -> Result<()> {
+    if !((0.0..=1.0).contains(&roughness) && (0.0..=1.0).contains(&anisotropy)) {
+        return Err(Error::invalid("materials", format!("{} roughness and anisotropy must be in [0, 1]", what)));
+    }
+    Ok(())
+}
@@ -0,0 +1,88 @@
+use std::hash::Hasher;
+
+use crate::camera_settings::CameraSettings;
+use crate::hittable::Hittable;
+use crate::light_list::LightList;
+use crate::tonemap::ToneMapping;
+use crate::vec3::Vec3;
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+// FNV-1a, used to fingerprint a scene so a checkpoint is only resumed against the scene it was made from.
+// Unlike std's DefaultHasher the result is the same on every platform and Rust version, which matters
+// because fingerprints are written to disk.
+pub struct SceneHasher {
+    state: u64,
+}
+
+impl SceneHasher {
+    pub fn new() -> Self {
+        SceneHasher { state: FNV_OFFSET }
+    }
+
+    pub fn write_f64(&mut self, v: f64) {
+        self.write_u64(v.to_bits());
+    }
+
+    pub fn write_vec3(&mut self, v: &Vec3) {
+        for c in v.vector {
+            self.write_f64(c);
+        }
+    }
+
+    // Length prefixed, so "ab" + "c" and "a" + "bc" differ.
+    pub fn write_str(&mut self, s: &str) {
+        self.write_usize(s.len());
+        self.write(s.as_bytes());
+    }
+}
+
+impl Default for SceneHasher {
+    fn default() -> Self {
+        SceneHasher::new()
+    }
+}
+
+impl Hasher for SceneHasher {
+    fn finish(&self) -> u64 {
+        self.state
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.state ^= b as u64;
+            self.state = self.state.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    // Fixed width and byte order, so the hash doesn't depend on the platform.
+    fn write_usize(&mut self, v: usize) {
+        self.write_u64(v as u64);
+    }
+
+    fn write_u64(&mut self, v: u64) {
+        self.write(&v.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, v: u32) {
+        self.write(&v.to_le_bytes());
+    }
+}
+
+// Fingerprint of everything that changes the samples of a render: the objects and their materials,
+// the lights, and the camera settings. Tone mapping is left out since it's only applied to the output.
+pub fn scene_hash(world: &dyn Hittable, lights: &LightList, settings: &CameraSettings) -> u64 {
+    let mut hasher = SceneHasher::new();
+    world.hash_into(&mut hasher);
+    lights.hash_into(&mut hasher);
+
+    let settings = CameraSettings {
+        tone_mapping: ToneMapping::default(),
+        ..settings.clone()
+    };
+    // Serialising a plain struct of numbers and names can't fail.
+    let json = serde_json::to_string(&settings).unwrap_or_default();
+    hasher.write_str(&json);
+    hasher.finish()
+}
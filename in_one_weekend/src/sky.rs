@@ -0,0 +1,267 @@
+use serde::{Deserialize, Serialize};
+
+use crate::color::Color;
+use crate::light::{Light, LightSample};
+use crate::onb::Onb;
+use crate::rtweekend::*;
+use crate::sampler::Sampler;
+use crate::sampling;
+use crate::vec3::{Point3, Vec3};
+
+// Angular radius of the sun disk as seen from the earth (~0.53 degrees across).
+const SUN_ANGULAR_RADIUS: f64 = 0.004_654;
+
+// Luminance of the sun disk outside the atmosphere, in the same kcd/m^2 units as the Preetham sky.
+const SUN_LUMINANCE: f64 = 1.6e6;
+
+// Analytic daylight sky after Preetham, Shirley & Smits, "A Practical Analytic Model for Daylight" (1999).
+// The sky is y-up: elevation is measured from the horizon, azimuth from -z (the camera's forward
+// direction) towards +x. Turbidity describes the haze in the air, 2 is a very clear sky and 10 a hazy one.
+// Serialised as the parameters it was created from, the model is recomputed when it is read back.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "SkyParameters", into = "SkyParameters")]
+pub struct Sky {
+    pub scale: f64, // Converts the model's kcd/m^2 into scene radiance units
+
+    sun_elevation: f64, // In degrees, as given to new
+    sun_azimuth: f64,
+    turbidity: f64,
+
+    sun_direction: Vec3,
+    theta_sun: f64,
+    zenith: [f64; 3],     // Y, x, y at the zenith
+    perez: [[f64; 5]; 3], // Perez A..E coefficients for Y, x, y
+    sun_radiance: Color,
+    sun_cos_max: f64,
+}
+
+impl Sky {
+    pub fn new(sun_elevation: f64, sun_azimuth: f64, turbidity: f64) -> Self {
+        // The fit is only valid for the sun above the horizon and for the turbidities it was made for.
+        let elevation = degrees_to_radians(sun_elevation.clamp(0.0, 90.0));
+        let azimuth = degrees_to_radians(sun_azimuth);
+        let t = turbidity.clamp(1.7, 10.0);
+
+        let sun_direction = Vec3::new(
+            elevation.cos() * azimuth.sin(),
+            elevation.sin(),
+            -elevation.cos() * azimuth.cos(),
+        );
+        let theta_sun = PI / 2.0 - elevation;
+
+        let mut sky = Sky {
+            scale: 0.05,
+
+            sun_elevation,
+            sun_azimuth,
+            turbidity,
+
+            sun_direction,
+            theta_sun,
+            zenith: Sky::zenith_values(theta_sun, t),
+            perez: Sky::perez_coefficients(t),
+            sun_radiance: Color::default(),
+            sun_cos_max: SUN_ANGULAR_RADIUS.cos(),
+        };
+        sky.sun_radiance = SUN_LUMINANCE * Sky::sun_transmittance(theta_sun, t);
+        sky
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct SkyParameters {
+    sun_elevation: f64,
+    sun_azimuth: f64,
+    turbidity: f64,
+    #[serde(default = "default_scale")]
+    scale: f64,
+}
+
+fn default_scale() -> f64 {
+    0.05
+}
+
+impl From<SkyParameters> for Sky {
+    fn from(p: SkyParameters) -> Self {
+        let mut sky = Sky::new(p.sun_elevation, p.sun_azimuth, p.turbidity);
+        sky.scale = p.scale;
+        sky
+    }
+}
+
+impl From<Sky> for SkyParameters {
+    fn from(sky: Sky) -> Self {
+        SkyParameters {
+            sun_elevation: sky.sun_elevation,
+            sun_azimuth: sky.sun_azimuth,
+            turbidity: sky.turbidity,
+            scale: sky.scale,
+        }
+    }
+}
+
+// public
+impl Sky {
+    pub fn sun_elevation(&self) -> f64 {
+        self.sun_elevation
+    }
+
+    pub fn sun_azimuth(&self) -> f64 {
+        self.sun_azimuth
+    }
+
+    pub fn turbidity(&self) -> f64 {
+        self.turbidity
+    }
+
+    // Solid angle subtended by the sun disk, 2*pi*(1 - cos(theta_max)).
+    pub fn sun_solid_angle(&self) -> f64 {
+        2.0 * PI * (1.0 - self.sun_cos_max)
+    }
+
+    // Radiance of the sky only, without the sun disk.
+    pub fn sky_radiance(&self, direction: &Vec3) -> Color {
+        let dir = Vec3::unit_vector(*direction);
+
+        // Below the horizon the model is undefined, keep the horizon value.
+        let cos_theta = dir.y().max(0.001);
+        let cos_gamma = Vec3::dot(&dir, &self.sun_direction).clamp(-1.0, 1.0);
+        let gamma = cos_gamma.acos();
+        let theta = cos_theta.acos();
+
+        let mut values = [0.0; 3];
+        for (i, value) in values.iter_mut().enumerate() {
+            *value = self.zenith[i] * Sky::perez(&self.perez[i], theta, gamma)
+                / Sky::perez(&self.perez[i], 0.0, self.theta_sun);
+        }
+
+        self.scale * xyy_to_rgb(values[0], values[1], values[2])
+    }
+
+    // Radiance of the sky plus the sun disk when `direction` falls inside it.
+    pub fn radiance(&self, direction: &Vec3) -> Color {
+        let dir = Vec3::unit_vector(*direction);
+        let mut color = self.sky_radiance(&dir);
+        if Vec3::dot(&dir, &self.sun_direction) >= self.sun_cos_max {
+            color += self.scale * self.sun_radiance;
+        }
+        color
+    }
+}
+
+impl Light for Sky {
+    // Samples the sun disk uniformly over its cone of directions.
+    fn sample_li(&self, _p: &Point3, sampler: &mut dyn Sampler) -> Option<LightSample> {
+        let u = sampler.get_2d();
+        if self.sun_direction.y() <= 0.0 {
+            return None;
+        }
+
+        let uvw = Onb::new(&self.sun_direction);
+        let direction = uvw.transform(&sampling::uniform_cone(u, self.sun_cos_max));
+
+        Some(LightSample {
+            direction: Vec3::unit_vector(direction),
+            distance: INFINITY,
+            radiance: self.scale * self.sun_radiance,
+            pdf: 1.0 / self.sun_solid_angle(),
+        })
+    }
+
+    fn pdf_li(&self, _p: &Point3, direction: &Vec3) -> f64 {
+        if self.sun_direction.y() <= 0.0 || Vec3::dot(&Vec3::unit_vector(*direction), &self.sun_direction) < self.sun_cos_max {
+            return 0.0;
+        }
+        1.0 / self.sun_solid_angle()
+    }
+
+    // Camera rays see the sun disk.
+    fn is_delta(&self) -> bool {
+        false
+    }
+}
+
+// private
+impl Sky {
+    // Perez et al. luminance distribution F(theta, gamma).
+    fn perez(c: &[f64; 5], theta: f64, gamma: f64) -> f64 {
+        (1.0 + c[0] * (c[1] / theta.cos()).exp())
+            * (1.0 + c[2] * (c[3] * gamma).exp() + c[4] * gamma.cos() * gamma.cos())
+    }
+
+    fn perez_coefficients(t: f64) -> [[f64; 5]; 3] {
+        [
+            [
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ],
+            [
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ],
+            [
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ],
+        ]
+    }
+
+    fn zenith_values(theta_sun: f64, t: f64) -> [f64; 3] {
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_sun);
+        let luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+
+        let th = [theta_sun.powi(3), theta_sun.powi(2), theta_sun, 1.0];
+        let poly = |c: [f64; 4]| c[0] * th[0] + c[1] * th[1] + c[2] * th[2] + c[3] * th[3];
+
+        let x = t * t * poly([0.00166, -0.00375, 0.00209, 0.0])
+            + t * poly([-0.02903, 0.06377, -0.03202, 0.00394])
+            + poly([0.11693, -0.21196, 0.06052, 0.25886]);
+        let y = t * t * poly([0.00275, -0.00610, 0.00317, 0.0])
+            + t * poly([-0.04214, 0.08970, -0.04153, 0.00516])
+            + poly([0.15346, -0.26756, 0.06670, 0.26688]);
+
+        [luminance.max(0.0), x, y]
+    }
+
+    // Attenuation of direct sunlight by Rayleigh and aerosol (Angstrom) extinction,
+    // evaluated at representative wavelengths (in micrometers) for the red, green and blue channels.
+    fn sun_transmittance(theta_sun: f64, t: f64) -> Color {
+        let theta_degrees = theta_sun * 180.0 / PI;
+        // Relative optical air mass (Kasten & Young).
+        let m = 1.0 / (theta_sun.cos() + 0.15 * (93.885 - theta_degrees).max(0.001).powf(-1.253));
+
+        let beta = 0.04608 * t - 0.04586;
+        let alpha = 1.3;
+
+        let tau = |lambda: f64| {
+            let rayleigh = 0.008735 * lambda.powf(-4.08);
+            let aerosol = beta * lambda.powf(-alpha);
+            (-m * (rayleigh + aerosol)).exp()
+        };
+        Color::new(tau(0.680), tau(0.550), tau(0.440))
+    }
+}
+
+// CIE xyY to linear sRGB (D65).
+fn xyy_to_rgb(luminance: f64, x: f64, y: f64) -> Color {
+    if y <= 0.0 {
+        return Color::default();
+    }
+    let cx = x / y * luminance;
+    let cz = (1.0 - x - y) / y * luminance;
+
+    Color::new(
+        (3.240_454_2 * cx - 1.537_138_5 * luminance - 0.498_531_4 * cz).max(0.0),
+        (-0.969_266 * cx + 1.876_010_8 * luminance + 0.041_556 * cz).max(0.0),
+        (0.055_643_4 * cx - 0.204_025_9 * luminance + 1.057_225_2 * cz).max(0.0),
+    )
+}
@@ -0,0 +1,91 @@
+use crate::sampler::{self, Sampler};
+
+// Owen-scrambled Sobol points, padded across dimensions.
+// Every 1D or 2D request uses the first two Sobol dimensions (a (0,2)-sequence), with the sample
+// index shuffled and the result Owen-scrambled by a seed unique to the pixel and dimension.
+// Scrambling keeps the stratification of the sequence while removing its structure and the
+// correlation between dimensions.
+pub struct SobolSampler {
+    seed: u64,
+    pixel: (u32, u32),
+    sample_index: u32,
+    dimension: u32,
+}
+
+impl SobolSampler {
+    pub fn new(seed: u64) -> Self {
+        SobolSampler {
+            seed,
+            pixel: (0, 0),
+            sample_index: 0,
+            dimension: 0,
+        }
+    }
+
+    fn seed(&self, salt: u64) -> u32 {
+        sampler::hash_pixel(self.pixel.0, self.pixel.1, self.dimension, sampler::mix_bits(self.seed) ^ salt) as u32
+    }
+}
+
+impl Default for SobolSampler {
+    fn default() -> Self {
+        SobolSampler::new(0)
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn start_pixel_sample(&mut self, i: u32, j: u32, sample_index: u32) {
+        self.pixel = (i, j);
+        self.sample_index = sample_index;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let index = owen_scramble(self.sample_index, self.seed(0));
+        let x = owen_scramble(sobol_0(index), self.seed(1));
+        self.dimension += 1;
+        sampler::u32_to_unit(x)
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let index = owen_scramble(self.sample_index, self.seed(0));
+        let x = owen_scramble(sobol_0(index), self.seed(1));
+        let y = owen_scramble(sobol_1(index), self.seed(2));
+        self.dimension += 2;
+        (sampler::u32_to_unit(x), sampler::u32_to_unit(y))
+    }
+}
+
+// First Sobol dimension: the van der Corput sequence in base 2.
+fn sobol_0(index: u32) -> u32 {
+    index.reverse_bits()
+}
+
+// Second Sobol dimension, generated by the primitive polynomial x + 1.
+fn sobol_1(mut index: u32) -> u32 {
+    let mut result = 0;
+    let mut v: u32 = 1 << 31;
+    while index != 0 {
+        if index & 1 != 0 {
+            result ^= v;
+        }
+        index >>= 1;
+        v ^= v >> 1;
+    }
+    result
+}
+
+// Hash based nested uniform (Owen) scramble of a 32-bit fixed point value
+// (Burley, "Practical Hash-based Owen Scrambling", 2020).
+pub fn owen_scramble(v: u32, seed: u32) -> u32 {
+    laine_karras_permutation(v.reverse_bits(), seed).reverse_bits()
+}
+
+fn laine_karras_permutation(mut x: u32, seed: u32) -> u32 {
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50_b47c);
+    x ^= x.wrapping_mul(0xb82f_1e52);
+    x ^= x.wrapping_mul(0xc7af_e638);
+    x ^= x.wrapping_mul(0x8d22_f6e6);
+    x
+}
@@ -0,0 +1,275 @@
+use std::ops::{Add, AddAssign, Mul};
+use std::sync::OnceLock;
+
+use crate::color::Color;
+
+// Spectral rendering with hero wavelength sampling (Wilkie et al. 2014): every camera path carries
+// WAVELENGTHS wavelengths, one picked at random (the hero) and the others spread evenly after it,
+// so a single path estimates a whole spectrum. Scenes are still described
+// in RGB; colors are turned into spectra (upsampled) where the path meets them, and the estimate is
+// projected back onto the CIE XYZ matching functions and converted to linear sRGB for the image.
+
+pub const LAMBDA_MIN: f64 = 360.0; // nm
+pub const LAMBDA_MAX: f64 = 830.0;
+
+// Wavelengths followed by every path.
+pub const WAVELENGTHS: usize = 4;
+
+// Radiance (or throughput) at the wavelengths of one path.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct SampledSpectrum {
+    values: [f64; WAVELENGTHS],
+}
+
+impl SampledSpectrum {
+    pub fn new(values: [f64; WAVELENGTHS]) -> Self {
+        SampledSpectrum { values }
+    }
+
+    pub fn constant(value: f64) -> Self {
+        SampledSpectrum::new([value; WAVELENGTHS])
+    }
+}
+
+//getters
+impl SampledSpectrum {
+    pub fn values(&self) -> &[f64; WAVELENGTHS] {
+        &self.values
+    }
+}
+
+impl Add for SampledSpectrum {
+    type Output = SampledSpectrum;
+    fn add(self, other: SampledSpectrum) -> SampledSpectrum {
+        SampledSpectrum::new(std::array::from_fn(|k| self.values[k] + other.values[k]))
+    }
+}
+
+impl AddAssign for SampledSpectrum {
+    fn add_assign(&mut self, other: SampledSpectrum) {
+        *self = *self + other;
+    }
+}
+
+impl Mul for SampledSpectrum {
+    type Output = SampledSpectrum;
+    fn mul(self, other: SampledSpectrum) -> SampledSpectrum {
+        SampledSpectrum::new(std::array::from_fn(|k| self.values[k] * other.values[k]))
+    }
+}
+
+impl Mul<SampledSpectrum> for f64 {
+    type Output = SampledSpectrum;
+    fn mul(self, s: SampledSpectrum) -> SampledSpectrum {
+        SampledSpectrum::new(s.values.map(|v| self * v))
+    }
+}
+
+// The wavelengths of one path with the density each was picked with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SampledWavelengths {
+    lambda: [f64; WAVELENGTHS],
+    pdf: [f64; WAVELENGTHS],
+}
+
+impl SampledWavelengths {
+    // The hero wavelength for `u` in [0, 1) and the others at equal steps of u after it, wrapping
+    // around; each one on its own follows visible_wavelength_pdf.
+    pub fn sample(u: f64) -> Self {
+        let lambda: [f64; WAVELENGTHS] =
+            std::array::from_fn(|k| sample_visible_wavelength((u + k as f64 / WAVELENGTHS as f64).fract()));
+        SampledWavelengths {
+            lambda,
+            pdf: lambda.map(visible_wavelength_pdf),
+        }
+    }
+}
+
+//getters
+impl SampledWavelengths {
+    pub fn lambda(&self) -> &[f64; WAVELENGTHS] {
+        &self.lambda
+    }
+
+    // The wavelength that wavelength dependent materials (see Material::is_dispersive) follow.
+    pub fn hero(&self) -> f64 {
+        self.lambda[0]
+    }
+
+    pub fn secondary_terminated(&self) -> bool {
+        self.pdf[1..].iter().all(|&pdf| pdf == 0.0)
+    }
+}
+
+//utils
+impl SampledWavelengths {
+    // After a wavelength dependent scattering event the other wavelengths would have gone a
+    // different way: drop them and let the hero stand for all of them.
+    pub fn terminate_secondary(&mut self) {
+        if self.secondary_terminated() {
+            return;
+        }
+        for pdf in &mut self.pdf[1..] {
+            *pdf = 0.0;
+        }
+        self.pdf[0] /= WAVELENGTHS as f64;
+    }
+
+    // A smooth spectrum with the given RGB color, at these wavelengths.
+    pub fn upsample(&self, color: &Color) -> SampledSpectrum {
+        SampledSpectrum::new(self.lambda.map(|lambda| rgb_to_spectrum(color, lambda)))
+    }
+
+    // Linear sRGB of the radiance `l` measured at these wavelengths: a Monte Carlo estimate of the
+    // spectrum's XYZ coordinates, each wavelength weighted by one over its density.
+    pub fn to_rgb(&self, l: &SampledSpectrum) -> Color {
+        let mut xyz = Color::default();
+        for k in 0..WAVELENGTHS {
+            if self.pdf[k] > 0.0 {
+                xyz += (l.values[k] / self.pdf[k]) * cie_xyz(self.lambda[k]);
+            }
+        }
+        xyz_to_rgb(&(xyz / (WAVELENGTHS as f64 * CIE_Y_INTEGRAL)))
+    }
+}
+
+// Density of sample_visible_wavelength: a fit to the luminance of the visible range (Radziszewski
+// et al. 2009, as in pbrt), so fewer paths are spent where the eye sees little.
+pub fn visible_wavelength_pdf(lambda: f64) -> f64 {
+    if !(LAMBDA_MIN..=LAMBDA_MAX).contains(&lambda) {
+        return 0.0;
+    }
+    let c = (0.0072 * (lambda - 538.0)).cosh();
+    0.0039398042 / (c * c)
+}
+
+pub fn sample_visible_wavelength(u: f64) -> f64 {
+    538.0 - 138.888889 * (0.85691062 - 1.82750197 * u).atanh()
+}
+
+// Integral of the CIE y matching function, the luminance of a spectrum of constant radiance 1.
+const CIE_Y_INTEGRAL: f64 = 106.856895;
+
+// The CIE 1931 2 degree colour matching functions, as the multi-lobe Gaussian fit of Wyman,
+// Sloan and Shirley (2013), good to about 1% of their peak.
+pub fn cie_xyz(lambda: f64) -> Color {
+    let g = |mu: f64, sigma_below: f64, sigma_above: f64| {
+        let t = (lambda - mu) / if lambda < mu { sigma_below } else { sigma_above };
+        (-0.5 * t * t).exp()
+    };
+    Color::new(
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2),
+        0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1),
+        1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8),
+    )
+}
+
+// XYZ to linear sRGB, white balanced so that the spectrum RGB white is upsampled to comes out white.
+pub fn xyz_to_rgb(xyz: &Color) -> Color {
+    let rgb = xyz_to_srgb(xyz);
+    let white = white_balance();
+    Color::new(rgb.x() / white.x(), rgb.y() / white.y(), rgb.z() / white.z())
+}
+
+// The standard XYZ to linear sRGB (D65) matrix.
+fn xyz_to_srgb(xyz: &Color) -> Color {
+    Color::new(
+        3.2404542 * xyz.x() - 1.5371385 * xyz.y() - 0.4985314 * xyz.z(),
+        -0.9692660 * xyz.x() + 1.8760108 * xyz.y() + 0.0415560 * xyz.z(),
+        0.0556434 * xyz.x() - 0.2040259 * xyz.y() + 1.0572252 * xyz.z(),
+    )
+}
+
+// sRGB of the flat (equal energy) spectrum before white balancing, over the range we sample.
+fn white_balance() -> &'static Color {
+    static WHITE: OnceLock<Color> = OnceLock::new();
+    WHITE.get_or_init(|| {
+        const STEPS: usize = 4700;
+        let step = (LAMBDA_MAX - LAMBDA_MIN) / STEPS as f64;
+        let mut xyz = Color::default();
+        for s in 0..STEPS {
+            xyz += step * cie_xyz(LAMBDA_MIN + (s as f64 + 0.5) * step);
+        }
+        xyz_to_srgb(&(xyz / CIE_Y_INTEGRAL))
+    })
+}
+
+// Linear sRGB of a surface with the given reflectance spectrum, lit by the flat white that RGB white
+// is upsampled to; for materials whose color comes from the wavelength, like thin films, in RGB
+// renders. A fixed quadrature of REFLECTANCE_STEPS wavelengths, so it is smooth rather than noisy.
+pub fn reflectance_to_rgb(reflectance: impl Fn(f64) -> f64) -> Color {
+    let mut rgb = Color::default();
+    for (lambda, weight) in reflectance_weights() {
+        rgb += reflectance(*lambda) * *weight;
+    }
+    rgb
+}
+
+const REFLECTANCE_STEPS: usize = 32;
+
+// Wavelengths of reflectance_to_rgb and their RGB weights, normalised so that a reflectance of one
+// comes out exactly white.
+fn reflectance_weights() -> &'static [(f64, Color); REFLECTANCE_STEPS] {
+    static WEIGHTS: OnceLock<[(f64, Color); REFLECTANCE_STEPS]> = OnceLock::new();
+    WEIGHTS.get_or_init(|| {
+        let step = (LAMBDA_MAX - LAMBDA_MIN) / REFLECTANCE_STEPS as f64;
+        let mut weights: [(f64, Color); REFLECTANCE_STEPS] = std::array::from_fn(|s| {
+            let lambda = LAMBDA_MIN + (s as f64 + 0.5) * step;
+            (lambda, xyz_to_srgb(&cie_xyz(lambda)))
+        });
+        let mut white = Color::default();
+        for (_, weight) in &weights {
+            white += *weight;
+        }
+        for (_, weight) in &mut weights {
+            *weight = Color::new(weight.x() / white.x(), weight.y() / white.y(), weight.z() / white.z());
+        }
+        weights
+    })
+}
+
+// Smits' (1999) RGB to spectrum conversion: the color as white plus the two primaries or secondaries
+// that make up the rest, each a smooth spectrum over SMITS_BINS bins of the visible range. It keeps
+// reflectances in [0, 1] bounded by about one, and white and greys are exactly flat.
+pub fn rgb_to_spectrum(color: &Color, lambda: f64) -> f64 {
+    let (r, g, b) = (color.x().max(0.0), color.y().max(0.0), color.z().max(0.0));
+    let basis = |table: &[f64; SMITS_BINS]| smits_lookup(table, lambda);
+
+    if r <= g && r <= b {
+        if g <= b {
+            r + (g - r) * basis(&SMITS_CYAN) + (b - g) * basis(&SMITS_BLUE)
+        } else {
+            r + (b - r) * basis(&SMITS_CYAN) + (g - b) * basis(&SMITS_GREEN)
+        }
+    } else if g <= r && g <= b {
+        if r <= b {
+            g + (r - g) * basis(&SMITS_MAGENTA) + (b - r) * basis(&SMITS_BLUE)
+        } else {
+            g + (b - g) * basis(&SMITS_MAGENTA) + (r - b) * basis(&SMITS_RED)
+        }
+    } else if r <= g {
+        b + (r - b) * basis(&SMITS_YELLOW) + (g - r) * basis(&SMITS_GREEN)
+    } else {
+        b + (g - b) * basis(&SMITS_YELLOW) + (r - g) * basis(&SMITS_RED)
+    }
+}
+
+// Smits' bins cover 380 to 720 nm.
+const SMITS_BINS: usize = 10;
+const SMITS_MIN: f64 = 380.0;
+const SMITS_MAX: f64 = 720.0;
+const SMITS_CYAN: [f64; SMITS_BINS] = [0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0.0, 0.0, 0.0];
+const SMITS_MAGENTA: [f64; SMITS_BINS] = [1.0, 1.0, 0.9685, 0.2229, 0.0, 0.0458, 0.8369, 1.0, 1.0, 0.9959];
+const SMITS_YELLOW: [f64; SMITS_BINS] = [0.0001, 0.0, 0.1088, 0.6651, 1.0, 1.0, 0.9996, 0.9586, 0.9685, 0.9840];
+const SMITS_RED: [f64; SMITS_BINS] = [0.1012, 0.0515, 0.0, 0.0, 0.0, 0.0, 0.8325, 1.0149, 1.0149, 1.0149];
+const SMITS_GREEN: [f64; SMITS_BINS] = [0.0, 0.0, 0.0273, 0.7937, 1.0, 0.9418, 0.1719, 0.0, 0.0, 0.0025];
+const SMITS_BLUE: [f64; SMITS_BINS] = [1.0, 1.0, 0.8916, 0.3323, 0.0, 0.0, 0.0003, 0.0369, 0.0483, 0.0496];
+
+// Linear interpolation between the bin centers, constant beyond the outer ones.
+fn smits_lookup(table: &[f64; SMITS_BINS], lambda: f64) -> f64 {
+    let x = (lambda - SMITS_MIN) / (SMITS_MAX - SMITS_MIN) * SMITS_BINS as f64 - 0.5;
+    let x = x.clamp(0.0, (SMITS_BINS - 1) as f64);
+    let i = (x as usize).min(SMITS_BINS - 2);
+    let t = x - i as f64;
+    (1.0 - t) * table[i] + t * table[i + 1]
+}
@@ -1,20 +1,54 @@
+use std::sync::Arc;
+
 use crate::hittable::{HitRecord, Hittable};
 use crate::interval::Interval;
-use crate::ray::{self, Ray};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::rtweekend::PI;
 use crate::vec3::{Point3, Vec3};
+use crate::scene_hash::SceneHasher;
 
 pub struct Sphere {
     center: Point3,
     radius: f64,
+    mat: Option<Arc<dyn Material>>,
 }
 
 impl Sphere {
-    pub fn new(center: Point3, radius: f64) -> Self {
+    pub fn new(center: Point3, radius: f64, mat: Arc<dyn Material>) -> Self {
+        Sphere {
+            center,
+            radius: radius.max(0.0),
+            mat: Some(mat),
+        }
+    }
+
+    // The shape alone, without a material, e.g. the boundary a Subsurface material walks inside.
+    pub fn boundary(center: Point3, radius: f64) -> Self {
         Sphere {
             center,
             radius: radius.max(0.0),
+            mat: None,
         }
     }
+
+    fn get_sphere_uv(p: &Point3) -> (f64, f64) {
+        // p: a given point on the sphere of radius one, centered at the origin.
+        // u: returned value [0,1] of angle around the Y axis from X=-1.
+        // v: returned value [0,1] of angle from Y=-1 to Y=+1.
+        //     <1 0 0> yields <0.50 0.50>       <-1  0  0> yields <0.00 0.50>
+        //     <0 1 0> yields <0.50 1.00>       < 0 -1  0> yields <0.50 0.00>
+        //     <0 0 1> yields <0.25 0.50>       < 0  0 -1> yields <0.75 0.50>
+        let theta = (-p.y()).clamp(-1.0, 1.0).acos();
+        let phi = (-p.z()).atan2(p.x()) + PI;
+        (phi / (2.0 * PI), theta / PI)
+    }
+
+    fn get_sphere_tangent(p: &Point3) -> Vec3 {
+        // Direction of increasing u at p, around the Y axis; undefined (zero) at the poles.
+        let t = Vec3::new(p.z(), 0.0, -p.x());
+        if t.near_zero() { Vec3::default() } else { Vec3::unit_vector(t) }
+    }
 }
 impl Hittable for Sphere {
     //color map: n is a unit length => x, y, z E (-1.0, 1.0) ==> (0.0, 1.0) => (red, green, blue)
@@ -44,7 +78,19 @@ impl Hittable for Sphere {
         rec.p = r.at(rec.t);
         let outward_normal = (rec.p - self.center) / self.radius;
         rec.set_face_normal(r, &outward_normal);
+        (rec.u, rec.v) = Sphere::get_sphere_uv(&outward_normal);
+        rec.tangent = Sphere::get_sphere_tangent(&outward_normal);
+        rec.mat = self.mat.clone();
 
-        return true;
+        true
+    }
+
+    fn hash_into(&self, hasher: &mut SceneHasher) {
+        hasher.write_str("sphere");
+        hasher.write_vec3(&self.center);
+        hasher.write_f64(self.radius);
+        if let Some(mat) = &self.mat {
+            mat.hash_into(hasher);
+        }
     }
 }
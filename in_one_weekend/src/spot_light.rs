@@ -0,0 +1,100 @@
+use crate::color::Color;
+use crate::light::{Light, LightEmission, LightSample};
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::sampling;
+use crate::rtweekend::*;
+use crate::vec3::{Point3, Vec3};
+use crate::scene_hash::SceneHasher;
+
+// Point light restricted to a cone around `direction`.
+// Full intensity inside `inner_angle`, nothing outside `outer_angle`, and a smoothstep falloff between the two.
+// Angles are half-angles of the cone, in degrees.
+pub struct SpotLight {
+    position: Point3,
+    direction: Vec3,
+    intensity: Color,
+    cos_inner: f64,
+    cos_outer: f64,
+}
+
+impl SpotLight {
+    pub fn new(position: Point3, direction: Vec3, intensity: Color, inner_angle: f64, outer_angle: f64) -> Self {
+        let outer = outer_angle.clamp(0.0, 180.0);
+        let inner = inner_angle.clamp(0.0, outer);
+        SpotLight {
+            position,
+            direction: Vec3::unit_vector(direction),
+            intensity,
+            cos_inner: degrees_to_radians(inner).cos(),
+            cos_outer: degrees_to_radians(outer).cos(),
+        }
+    }
+
+    fn falloff(&self, cos_theta: f64) -> f64 {
+        if cos_theta >= self.cos_inner {
+            return 1.0;
+        }
+        if cos_theta <= self.cos_outer {
+            return 0.0;
+        }
+        let t = (cos_theta - self.cos_outer) / (self.cos_inner - self.cos_outer);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+impl Light for SpotLight {
+    fn sample_li(&self, p: &Point3, _sampler: &mut dyn Sampler) -> Option<LightSample> {
+        let to_light = self.position - *p;
+        let distance_squared = to_light.length_squared();
+        if distance_squared == 0.0 {
+            return None;
+        }
+        let distance = distance_squared.sqrt();
+        let direction = to_light / distance;
+
+        let falloff = self.falloff(Vec3::dot(&-direction, &self.direction));
+        if falloff == 0.0 {
+            return None;
+        }
+
+        Some(LightSample {
+            direction,
+            distance,
+            radiance: (falloff / distance_squared) * self.intensity,
+            pdf: 1.0,
+        })
+    }
+
+    // Emits uniformly over the outer cone, weighted by the falloff.
+    fn sample_le(&self, sampler: &mut dyn Sampler) -> Option<LightEmission> {
+        let pdf = self.pdf_le(&self.direction);
+        if !pdf.is_finite() {
+            return None; // A cone of no width
+        }
+        let direction = Onb::new(&self.direction).transform(&sampling::uniform_cone(sampler.get_2d(), self.cos_outer));
+        let direction = Vec3::unit_vector(direction);
+        Some(LightEmission {
+            ray: Ray::new(self.position, direction),
+            radiance: self.falloff(Vec3::dot(&direction, &self.direction)) * self.intensity,
+            pdf,
+        })
+    }
+
+    fn pdf_le(&self, direction: &Vec3) -> f64 {
+        if Vec3::dot(&Vec3::unit_vector(*direction), &self.direction) < self.cos_outer {
+            return 0.0;
+        }
+        1.0 / (2.0 * PI * (1.0 - self.cos_outer))
+    }
+
+    fn hash_into(&self, hasher: &mut SceneHasher) {
+        hasher.write_str("spot");
+        hasher.write_vec3(&self.position);
+        hasher.write_vec3(&self.direction);
+        hasher.write_vec3(&self.intensity);
+        hasher.write_f64(self.cos_inner);
+        hasher.write_f64(self.cos_outer);
+    }
+}
@@ -0,0 +1,71 @@
+use crate::sampler::{self, Sampler};
+
+// Jittered stratified sampling: each dimension is split into as many strata as there are samples
+// per pixel (a square grid for 2D dimensions), and every sample gets a random point inside its own stratum.
+// Strata are handed out in a different random order for every pixel and dimension so that
+// dimensions don't correlate with each other.
+pub struct StratifiedSampler {
+    seed: u64,
+    samples_per_pixel: u32,
+    grid: u32, // Strata per axis for 2D dimensions
+
+    pixel: (u32, u32),
+    sample_index: u32,
+    dimension: u32,
+}
+
+impl StratifiedSampler {
+    pub fn new(samples_per_pixel: u32, seed: u64) -> Self {
+        let samples_per_pixel = samples_per_pixel.max(1);
+        StratifiedSampler {
+            seed,
+            samples_per_pixel,
+            grid: (samples_per_pixel as f64).sqrt().ceil() as u32,
+
+            pixel: (0, 0),
+            sample_index: 0,
+            dimension: 0,
+        }
+    }
+
+    fn stratum(&self, count: u32) -> u32 {
+        // Past `count` samples start a new round of strata with a fresh permutation.
+        let round = self.sample_index / count;
+        let seed = sampler::hash_pixel(self.pixel.0, self.pixel.1, self.dimension, sampler::mix_bits(self.seed) ^ round as u64);
+        sampler::permutation_element(self.sample_index % count, count, seed as u32)
+    }
+
+    // Position inside the stratum for `dimension`.
+    fn jitter(&self, dimension: u32) -> f64 {
+        sampler::sample_hash_to_unit(self.pixel.0, self.pixel.1, self.sample_index, dimension, !self.seed)
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_pixel_sample(&mut self, i: u32, j: u32, sample_index: u32) {
+        self.pixel = (i, j);
+        self.sample_index = sample_index;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let count = self.samples_per_pixel;
+        let stratum = self.stratum(count);
+        let jitter = self.jitter(self.dimension);
+        self.dimension += 1;
+        (stratum as f64 + jitter) / count as f64
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let count = self.grid * self.grid;
+        let stratum = self.stratum(count);
+        let (jitter_x, jitter_y) = (self.jitter(self.dimension), self.jitter(self.dimension + 1));
+        self.dimension += 2;
+        let x = stratum % self.grid;
+        let y = stratum / self.grid;
+        (
+            (x as f64 + jitter_x) / self.grid as f64,
+            (y as f64 + jitter_y) / self.grid as f64,
+        )
+    }
+}
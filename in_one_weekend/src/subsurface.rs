@@ -0,0 +1,180 @@
+use std::sync::Arc;
+
+use crate::color::Color;
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::material::{Material, ScatterRecord};
+use crate::microfacet;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::rtweekend::{INFINITY, PI};
+use crate::sampler::Sampler;
+use crate::sampling;
+use crate::scene_hash::SceneHasher;
+use crate::vec3::{Point3, Vec3};
+
+// Translucent material (skin, wax, marble) whose light scatters inside the object, followed with a
+// random walk (Chiang et al. 2016, as in Cycles). The object must be closed, and `boundary` is its
+// shape: the walk intersects it from inside to find where the light comes out.
+//
+// Light arriving from outside reflects specularly with the Fresnel reflectance of
+// `refraction_index`, or enters with a cosine distribution and walks through the volume: straight
+// flights with exponentially distributed lengths, then an isotropic scattering event, until a flight
+// reaches the boundary. The returned ray is the last flight, so the path hits the boundary from
+// inside next, where the light leaves diffusely and lights are sampled as on any diffuse surface.
+//
+// `albedo` is the color of the object (its multiple scattering albedo), `mean_free_path` the
+// average distance light travels between scattering events, in scene units, per channel. A walk
+// samples its flights with one channel and weights the others with multiple importance sampling
+// over the three choices, which keeps the color noise of very different free paths bounded.
+pub struct Subsurface {
+    albedo: Color,
+    mean_free_path: Color,
+    refraction_index: f64,
+    boundary: Arc<dyn Hittable>,
+    single_scattering_albedo: Color,
+}
+
+// Walks longer than this are given up on, as absorbed.
+const MAX_WALK_STEPS: usize = 256;
+
+impl Subsurface {
+    pub fn new(
+        albedo: Color,
+        mean_free_path: Color,
+        refraction_index: f64,
+        boundary: Arc<dyn Hittable>,
+    ) -> Self {
+        let single = |a: f64| Subsurface::single_scattering_albedo(a.clamp(0.0, 1.0));
+        Subsurface {
+            albedo,
+            mean_free_path,
+            refraction_index,
+            boundary,
+            single_scattering_albedo: Color::new(single(albedo.x()), single(albedo.y()), single(albedo.z())),
+        }
+    }
+
+    // Albedo of one scattering event that gives the multiple scattering `albedo` of a thick slab,
+    // the fit of Chiang et al. to van de Hulst's relation.
+    pub fn single_scattering_albedo(albedo: f64) -> f64 {
+        let t = 4.09712 + 4.20863 * albedo - (9.59217 + 41.6808 * albedo + 17.7126 * albedo * albedo).sqrt();
+        1.0 - t * t
+    }
+
+    // Extinction coefficient of each channel, the inverse of its mean free path.
+    fn sigma_t(&self) -> Color {
+        let sigma = |d: f64| 1.0 / d.max(1e-9);
+        Color::new(sigma(self.mean_free_path.x()), sigma(self.mean_free_path.y()), sigma(self.mean_free_path.z()))
+    }
+
+    // Fraction of each channel's light that travels `distance` without scattering.
+    fn transmittance(sigma_t: &Color, distance: f64) -> Color {
+        Color::new((-sigma_t.x() * distance).exp(), (-sigma_t.y() * distance).exp(), (-sigma_t.z() * distance).exp())
+    }
+
+    // Follows light entering at `origin` towards `direction` until a flight reaches the boundary.
+    // Returns that last flight and the walk's weight, or None if it was absorbed.
+    fn walk(&self, origin: Point3, direction: Vec3, sampler: &mut dyn Sampler) -> Option<(Ray, Color)> {
+        let sigma_t = self.sigma_t();
+        let sigma_s = self.single_scattering_albedo * sigma_t;
+        let c = ((sampler.get_1d() * 3.0) as usize).min(2);
+
+        // The walk's throughput per channel and the density of sampling it with each channel's
+        // free paths, both relative to the density with channel c, so they stay in range.
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+        let mut density = Color::new(1.0, 1.0, 1.0);
+        let mut ray = Ray::new(origin, direction);
+
+        for _ in 0..MAX_WALK_STEPS {
+            let mut rec = HitRecord::default();
+            // Same interval as the path's own intersections, so the path finds the same exit.
+            if !self.boundary.hit(&ray, Interval::new(0.001, INFINITY), &mut rec) {
+                return None; // Not inside a closed boundary after all
+            }
+            let to_boundary = rec.t * ray.direction().length();
+            let distance = -(1.0 - sampler.get_1d()).ln() / sigma_t[c];
+
+            if distance >= to_boundary {
+                // Out: the probability of flying this far is the transmittance.
+                let transmittance = Subsurface::transmittance(&sigma_t, to_boundary);
+                throughput = throughput * transmittance / transmittance[c];
+                density = density * transmittance / transmittance[c];
+                let mean_density = (density.x() + density.y() + density.z()) / 3.0;
+                return Some((ray, throughput / mean_density));
+            }
+
+            // Scatters: the density of a flight ending here is sigma_t T, of which sigma_s T scatters.
+            let transmittance = Subsurface::transmittance(&sigma_t, distance);
+            let sampled = sigma_t[c] * transmittance[c];
+            throughput = throughput * sigma_s * transmittance / sampled;
+            density = density * sigma_t * transmittance / sampled;
+            ray = Ray::new(ray.at(distance / ray.direction().length()), sampling::uniform_sphere(sampler.get_2d()));
+        }
+        None
+    }
+
+    // Outward normal of a hit from inside, the side light leaves on.
+    fn outward(rec: &HitRecord) -> Vec3 {
+        -rec.normal
+    }
+}
+
+impl Material for Subsurface {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, sampler: &mut dyn Sampler) -> Option<ScatterRecord> {
+        if !rec.front_face {
+            // Leaving at the end of a walk: diffuse transmission out of the object.
+            let outward = Subsurface::outward(rec);
+            let direction = Onb::new(&outward).transform(&sampling::cosine_hemisphere(sampler.get_2d()));
+            let cos_theta = Vec3::dot(&Vec3::unit_vector(direction), &outward);
+            return Some(ScatterRecord {
+                attenuation: Color::new(1.0, 1.0, 1.0),
+                ray: Ray::new(rec.p, direction),
+                pdf: cos_theta.max(0.0) / PI,
+                is_specular: false,
+            });
+        }
+
+        let unit_direction = Vec3::unit_vector(r_in.direction());
+        let cos_theta = Vec3::dot(&-unit_direction, &rec.normal).min(1.0);
+        if microfacet::fresnel_dielectric(cos_theta, self.refraction_index) > sampler.get_1d() {
+            return Some(ScatterRecord {
+                attenuation: Color::new(1.0, 1.0, 1.0),
+                ray: Ray::new(rec.p, Vec3::reflect(&unit_direction, &rec.normal)),
+                pdf: 0.0,
+                is_specular: true,
+            });
+        }
+
+        // Into the volume. Lights can't be sampled from inside, so the walk counts as specular.
+        let inward = Onb::new(&-rec.normal).transform(&sampling::cosine_hemisphere(sampler.get_2d()));
+        let (ray, weight) = self.walk(rec.p, inward, sampler)?;
+        Some(ScatterRecord {
+            attenuation: weight,
+            ray,
+            pdf: 0.0,
+            is_specular: true,
+        })
+    }
+
+    // Only the diffuse exit has a density.
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> Color {
+        let pdf = self.pdf(r_in, rec, direction);
+        Color::new(pdf, pdf, pdf)
+    }
+
+    fn pdf(&self, _r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> f64 {
+        if rec.front_face {
+            return 0.0;
+        }
+        Vec3::dot(&Vec3::unit_vector(*direction), &Subsurface::outward(rec)).max(0.0) / PI
+    }
+
+    fn hash_into(&self, hasher: &mut SceneHasher) {
+        hasher.write_str("subsurface");
+        hasher.write_vec3(&self.albedo);
+        hasher.write_vec3(&self.mean_free_path);
+        hasher.write_f64(self.refraction_index);
+        self.boundary.hash_into(hasher);
+    }
+}
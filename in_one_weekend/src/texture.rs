@@ -0,0 +1,54 @@
+use std::sync::Arc;
+
+use crate::color::Color;
+use crate::scene_hash::SceneHasher;
+use crate::vec3::Point3;
+
+// Color (or scalar) that varies over a surface, looked up with the hit's surface coordinates
+// u, v and its position p.
+pub trait Texture: Send + Sync {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color;
+
+    // The texture as a single number, for parameters like roughness: the mean of the channels,
+    // so a grey texture gives its grey level.
+    fn scalar(&self, u: f64, v: f64, p: &Point3) -> f64 {
+        let c = self.value(u, v, p);
+        (c.x() + c.y() + c.z()) / 3.0
+    }
+
+    // Feeds the texture's parameters into `hasher`, see scene_hash. The default only tells types apart.
+    fn hash_into(&self, hasher: &mut SceneHasher) {
+        hasher.write_str(std::any::type_name::<Self>());
+    }
+}
+
+// The same color everywhere.
+pub struct SolidColor {
+    albedo: Color,
+}
+
+impl SolidColor {
+    pub fn new(albedo: Color) -> Self {
+        SolidColor { albedo }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _p: &Point3) -> Color {
+        self.albedo
+    }
+
+    fn hash_into(&self, hasher: &mut SceneHasher) {
+        hasher.write_str("solid");
+        hasher.write_vec3(&self.albedo);
+    }
+}
+
+// Shorthands for untextured material parameters.
+pub fn solid(albedo: Color) -> Arc<dyn Texture> {
+    Arc::new(SolidColor::new(albedo))
+}
+
+pub fn constant(value: f64) -> Arc<dyn Texture> {
+    solid(Color::new(value, value, value))
+}
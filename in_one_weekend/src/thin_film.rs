@@ -0,0 +1,217 @@
+use std::ops::{Add, Div, Mul, Sub};
+use std::sync::Arc;
+
+use crate::color::Color;
+use crate::hittable::HitRecord;
+use crate::rtweekend::PI;
+use crate::scene_hash::SceneHasher;
+use crate::spectrum;
+use crate::texture::Texture;
+
+// A thin transparent layer (soap, oil, a lens coating) on top of a dielectric or conductor.
+// Light reflected at its top and bottom interferes, so the reflectance of the coated surface
+// depends on the wavelength: the colors of soap bubbles and oil slicks. The reflectance is the
+// exact sum of all the bounces inside the film (the Airy formula), averaged over both polarisations.
+// `thickness` is in nanometres, times `thickness_scale` (for textures in [0, 1] such as images).
+pub struct ThinFilm {
+    thickness: Arc<dyn Texture>,
+    thickness_scale: f64,
+    ior: f64,
+}
+
+impl ThinFilm {
+    pub fn new(thickness: Arc<dyn Texture>, ior: f64) -> Self {
+        ThinFilm {
+            thickness,
+            thickness_scale: 1.0,
+            ior,
+        }
+    }
+
+    pub fn thickness_scale(mut self, thickness_scale: f64) -> Self {
+        self.thickness_scale = thickness_scale;
+        self
+    }
+
+    // Thickness at the hit point, in nm.
+    pub fn thickness_at(&self, rec: &HitRecord) -> f64 {
+        (self.thickness.scalar(rec.u, rec.v, &rec.p) * self.thickness_scale).max(0.0)
+    }
+
+    // Reflectance at `wavelength` (nm) for light arriving at cos_theta through a medium of index
+    // `outside` onto the film of `thickness` nm, on top of a base with complex index (eta, k).
+    pub fn reflectance(&self, cos_theta: f64, thickness: f64, wavelength: f64, outside: f64, base: (f64, f64)) -> f64 {
+        Interference::new(cos_theta, thickness, outside, self.ior, base).reflectance(wavelength)
+    }
+
+    // Reflectance of a film on a dielectric of index `refraction_index`, for light arriving at
+    // cos_theta on the side of the hit's front_face; at the ray's wavelength in spectral renders,
+    // or its RGB under white light otherwise.
+    pub fn dielectric_reflectance(
+        &self,
+        rec: &HitRecord,
+        cos_theta: f64,
+        wavelength: Option<f64>,
+        refraction_index: f64,
+    ) -> Color {
+        let thickness = self.thickness_at(rec);
+        // From inside the film sits between the dielectric and the outside air.
+        let (outside, base) = if rec.front_face {
+            (1.0, refraction_index)
+        } else {
+            (refraction_index, 1.0)
+        };
+        let interference = Interference::new(cos_theta, thickness, outside, self.ior, (base, 0.0));
+        self.spectral(wavelength, |lambda| interference.reflectance(lambda))
+    }
+
+    // Reflectance of a film on a conductor whose complex index at a wavelength is `base`, seen
+    // from the outside at cos_theta.
+    pub fn conductor_reflectance(
+        &self,
+        rec: &HitRecord,
+        cos_theta: f64,
+        wavelength: Option<f64>,
+        base: impl Fn(f64) -> (f64, f64),
+    ) -> Color {
+        let thickness = self.thickness_at(rec);
+        self.spectral(wavelength, |lambda| self.reflectance(cos_theta, thickness, lambda, 1.0, base(lambda)))
+    }
+
+    pub fn hash_into(&self, hasher: &mut SceneHasher) {
+        hasher.write_str("thin film");
+        hasher.write_f64(self.ior);
+        hasher.write_f64(self.thickness_scale);
+        self.thickness.hash_into(hasher);
+    }
+}
+
+//utils
+impl ThinFilm {
+    fn spectral(&self, wavelength: Option<f64>, reflectance: impl Fn(f64) -> f64) -> Color {
+        match wavelength {
+            Some(lambda) => {
+                let r = reflectance(lambda);
+                Color::new(r, r, r)
+            }
+            None => spectrum::reflectance_to_rgb(reflectance),
+        }
+    }
+}
+
+// The film at one angle: the Fresnel amplitudes of its two interfaces for both polarisations,
+// which don't depend on the wavelength unless the base's index does, and its optical thickness.
+struct Interference {
+    r01: [Complex; 2],
+    r12: [Complex; 2],
+    path: Complex, // 4 pi n1 cos1 times the thickness, the phase difference times the wavelength
+}
+
+impl Interference {
+    fn new(cos_theta: f64, thickness: f64, outside: f64, ior: f64, base: (f64, f64)) -> Self {
+        let n0 = Complex::real(outside);
+        let n1 = Complex::real(ior);
+        let n2 = Complex::new(base.0, base.1);
+
+        // Snell's law with complex angles: cos_j = sqrt(1 - (n0 sin_0 / n_j)^2). It covers total
+        // internal reflection in the film or the base (an imaginary cosine) and absorbing bases.
+        let cos0 = cos_theta.clamp(0.0, 1.0);
+        let sin0 = Complex::real(outside * (1.0 - cos0 * cos0).sqrt());
+        let cos_in = |n: Complex| (Complex::real(1.0) - (sin0 / n) * (sin0 / n)).sqrt();
+        let (cos0, cos1, cos2) = (Complex::real(cos0), cos_in(n1), cos_in(n2));
+
+        Interference {
+            r01: [fresnel_s(n0, cos0, n1, cos1), fresnel_p(n0, cos0, n1, cos1)],
+            r12: [fresnel_s(n1, cos1, n2, cos2), fresnel_p(n1, cos1, n2, cos2)],
+            path: Complex::real(4.0 * PI * thickness) * n1 * cos1,
+        }
+    }
+
+    // The Airy sum of the bounces inside the film, averaged over the polarisations.
+    fn reflectance(&self, wavelength: f64) -> f64 {
+        let phase = (self.path / Complex::real(wavelength)).exp_i();
+        let airy = |r01: Complex, r12: Complex| {
+            let r = (r01 + r12 * phase) / (Complex::real(1.0) + r01 * r12 * phase);
+            r.norm_sqr()
+        };
+        let s = airy(self.r01[0], self.r12[0]);
+        let p = airy(self.r01[1], self.r12[1]);
+        ((s + p) / 2.0).clamp(0.0, 1.0)
+    }
+}
+
+// Fresnel amplitude reflection coefficients from medium a into medium b.
+fn fresnel_s(n_a: Complex, cos_a: Complex, n_b: Complex, cos_b: Complex) -> Complex {
+    (n_a * cos_a - n_b * cos_b) / (n_a * cos_a + n_b * cos_b)
+}
+
+fn fresnel_p(n_a: Complex, cos_a: Complex, n_b: Complex, cos_b: Complex) -> Complex {
+    (n_b * cos_a - n_a * cos_b) / (n_b * cos_a + n_a * cos_b)
+}
+
+// Just enough complex arithmetic for the amplitudes above.
+#[derive(Debug, Clone, Copy)]
+struct Complex {
+    re: f64,
+    im: f64,
+}
+
+impl Complex {
+    fn new(re: f64, im: f64) -> Self {
+        Complex { re, im }
+    }
+
+    fn real(re: f64) -> Self {
+        Complex::new(re, 0.0)
+    }
+
+    fn norm_sqr(self) -> f64 {
+        self.re * self.re + self.im * self.im
+    }
+
+    // Principal square root, with a non-negative real part.
+    fn sqrt(self) -> Complex {
+        let r = self.norm_sqr().sqrt();
+        let re = ((r + self.re) / 2.0).max(0.0).sqrt();
+        let im = ((r - self.re) / 2.0).max(0.0).sqrt();
+        Complex::new(re, if self.im < 0.0 { -im } else { im })
+    }
+
+    // e^(i z)
+    fn exp_i(self) -> Complex {
+        let magnitude = (-self.im).exp();
+        Complex::new(magnitude * self.re.cos(), magnitude * self.re.sin())
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+    fn add(self, other: Complex) -> Complex {
+        Complex::new(self.re + other.re, self.im + other.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+    fn sub(self, other: Complex) -> Complex {
+        Complex::new(self.re - other.re, self.im - other.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+    fn mul(self, other: Complex) -> Complex {
+        Complex::new(self.re * other.re - self.im * other.im, self.re * other.im + self.im * other.re)
+    }
+}
+
+impl Div for Complex {
+    type Output = Complex;
+    fn div(self, other: Complex) -> Complex {
+        let d = other.norm_sqr();
+        Complex::new(
+            (self.re * other.re + self.im * other.im) / d,
+            (self.im * other.re - self.re * other.im) / d,
+        )
+    }
+}
@@ -0,0 +1,55 @@
+// A rectangle of pixels, [x0, x1) x [y0, y1), the unit of work of a distributed render.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tile {
+    pub x0: u32,
+    pub y0: u32,
+    pub x1: u32,
+    pub y1: u32,
+}
+
+impl Tile {
+    pub fn new(x0: u32, y0: u32, x1: u32, y1: u32) -> Self {
+        Tile { x0, y0, x1, y1 }
+    }
+
+    // Splits a width x height image into tiles of at most size x size pixels, row by row.
+    pub fn grid(width: u32, height: u32, size: u32) -> Vec<Tile> {
+        let size = size.max(1);
+        let mut tiles = Vec::new();
+        for y0 in (0..height).step_by(size as usize) {
+            for x0 in (0..width).step_by(size as usize) {
+                tiles.push(Tile::new(x0, y0, (x0 + size).min(width), (y0 + size).min(height)));
+            }
+        }
+        tiles
+    }
+}
+
+//getters
+impl Tile {
+    pub fn width(&self) -> u32 {
+        self.x1 - self.x0
+    }
+    pub fn height(&self) -> u32 {
+        self.y1 - self.y0
+    }
+}
+
+//utils
+impl Tile {
+    // The tile grown by `pad` pixels on every side, clipped to a width x height image.
+    pub fn expand(&self, pad: u32, width: u32, height: u32) -> Tile {
+        Tile::new(
+            self.x0.saturating_sub(pad),
+            self.y0.saturating_sub(pad),
+            (self.x1 + pad).min(width),
+            (self.y1 + pad).min(height),
+        )
+    }
+
+    // Pixel coordinates i, j of the tile, row by row.
+    pub fn pixels(&self) -> impl Iterator<Item = (u32, u32)> + use<> {
+        let (x0, x1) = (self.x0, self.x1);
+        (self.y0..self.y1).flat_map(move |j| (x0..x1).map(move |i| (i, j)))
+    }
+}
@@ -0,0 +1,158 @@
+use serde::{Deserialize, Serialize};
+
+use crate::color::{self, Color};
+use crate::enum_names::enum_names;
+
+// Curve that compresses scene-referred linear radiance into the displayable [0, 1] range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ToneMapOperator {
+    #[default]
+    Clamp, // No curve, values above 1 are clipped
+    Reinhard, // Extended Reinhard on luminance, white point = `ToneMapping::white_point`
+    Hable,    // Uncharted 2 filmic curve
+    Aces,     // Stephen Hill's fit of the ACES RRT + sRGB ODT
+    Agx,      // Troy Sobotka's AgX, polynomial approximation of the base contrast look
+}
+
+enum_names!(ToneMapOperator, "tone mapping operator", {
+    Clamp => "clamp",
+    Reinhard => "reinhard",
+    Hable => "hable",
+    Aces => "aces",
+    Agx => "agx",
+});
+
+// Image-wide tone mapping applied to linear pixel values before they are encoded to sRGB and quantised.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ToneMapping {
+    pub operator: ToneMapOperator,
+    pub exposure: f64,    // In stops, the image is scaled by 2^exposure first
+    pub white_point: f64, // Luminance mapped to white by the Reinhard operator
+}
+
+impl Default for ToneMapping {
+    fn default() -> Self {
+        ToneMapping {
+            operator: ToneMapOperator::default(),
+            exposure: 0.0,
+            white_point: 4.0,
+        }
+    }
+}
+
+impl ToneMapping {
+    // Maps linear radiance to linear display values in [0, 1].
+    pub fn apply(&self, c: &Color) -> Color {
+        let c = 2f64.powf(self.exposure) * *c;
+        let mapped = match self.operator {
+            ToneMapOperator::Clamp => c,
+            ToneMapOperator::Reinhard => reinhard_extended(&c, self.white_point),
+            ToneMapOperator::Hable => hable(&c),
+            ToneMapOperator::Aces => aces_fitted(&c),
+            ToneMapOperator::Agx => agx(&c),
+        };
+        Color::new(
+            mapped.x().clamp(0.0, 1.0),
+            mapped.y().clamp(0.0, 1.0),
+            mapped.z().clamp(0.0, 1.0),
+        )
+    }
+}
+
+fn reinhard_extended(c: &Color, white_point: f64) -> Color {
+    let l = color::luminance(c);
+    if l <= 0.0 {
+        return Color::default();
+    }
+    let l_out = l * (1.0 + l / (white_point * white_point)) / (1.0 + l);
+    (l_out / l) * *c
+}
+
+fn hable(c: &Color) -> Color {
+    const EXPOSURE_BIAS: f64 = 2.0;
+    const WHITE: f64 = 11.2;
+
+    fn partial(x: f64) -> f64 {
+        const A: f64 = 0.15; // Shoulder strength
+        const B: f64 = 0.50; // Linear strength
+        const C: f64 = 0.10; // Linear angle
+        const D: f64 = 0.20; // Toe strength
+        const E: f64 = 0.02; // Toe numerator
+        const F: f64 = 0.30; // Toe denominator
+        ((x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F)) - E / F
+    }
+
+    let white_scale = 1.0 / partial(WHITE);
+    Color::new(
+        partial(EXPOSURE_BIAS * c.x()) * white_scale,
+        partial(EXPOSURE_BIAS * c.y()) * white_scale,
+        partial(EXPOSURE_BIAS * c.z()) * white_scale,
+    )
+}
+
+fn aces_fitted(c: &Color) -> Color {
+    // sRGB => XYZ => D65_2_D60 => AP1 => RRT_SAT
+    const INPUT: [[f64; 3]; 3] = [
+        [0.59719, 0.35458, 0.04823],
+        [0.07600, 0.90834, 0.01566],
+        [0.02840, 0.13383, 0.83777],
+    ];
+    // ODT_SAT => XYZ => D60_2_D65 => sRGB
+    const OUTPUT: [[f64; 3]; 3] = [
+        [1.60475, -0.53108, -0.07367],
+        [-0.10208, 1.10813, -0.00605],
+        [-0.00327, -0.07276, 1.07602],
+    ];
+
+    fn rrt_and_odt_fit(v: f64) -> f64 {
+        let a = v * (v + 0.024_578_6) - 0.000_090_537;
+        let b = v * (0.983_729 * v + 0.432_951) + 0.238_081;
+        a / b
+    }
+
+    let v = mul(&INPUT, c);
+    let v = Color::new(rrt_and_odt_fit(v.x()), rrt_and_odt_fit(v.y()), rrt_and_odt_fit(v.z()));
+    mul(&OUTPUT, &v)
+}
+
+fn agx(c: &Color) -> Color {
+    const INSET: [[f64; 3]; 3] = [
+        [0.842_479_062_253_094, 0.078_433_6, 0.079_223_745_147_764_3],
+        [0.042_328_242_261_012_3, 0.878_468_636_469_772, 0.079_166_127_460_543_4],
+        [0.042_375_654_905_705_1, 0.078_433_6, 0.879_142_973_793_104],
+    ];
+    const OUTSET: [[f64; 3]; 3] = [
+        [1.196_879_005_120_17, -0.098_020_881_140_136_8, -0.099_029_744_079_720_5],
+        [-0.052_896_851_757_456_2, 1.151_903_129_904_17, -0.098_961_176_844_843_3],
+        [-0.052_971_635_514_443_8, -0.098_043_450_117_124_1, 1.151_073_672_641_16],
+    ];
+    const MIN_EV: f64 = -12.473_93;
+    const MAX_EV: f64 = 4.026_069;
+
+    fn encode(v: f64) -> f64 {
+        let x = ((v.max(1e-10).log2().clamp(MIN_EV, MAX_EV)) - MIN_EV) / (MAX_EV - MIN_EV);
+        // 6th order fit of the default AgX contrast curve.
+        let x2 = x * x;
+        let x4 = x2 * x2;
+        15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x - 0.00232
+    }
+
+    let v = mul(&INSET, c);
+    let v = mul(&OUTSET, &Color::new(encode(v.x()), encode(v.y()), encode(v.z())));
+    // The curve produces display encoded values, bring them back to linear for the sRGB encoding.
+    Color::new(
+        v.x().max(0.0).powf(2.2),
+        v.y().max(0.0).powf(2.2),
+        v.z().max(0.0).powf(2.2),
+    )
+}
+
+fn mul(m: &[[f64; 3]; 3], c: &Color) -> Color {
+    Color::new(
+        m[0][0] * c.x() + m[0][1] * c.y() + m[0][2] * c.z(),
+        m[1][0] * c.x() + m[1][1] * c.y() + m[1][2] * c.z(),
+        m[2][0] * c.x() + m[2][1] * c.y() + m[2][2] * c.z(),
+    )
+}
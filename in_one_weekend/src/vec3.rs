@@ -2,10 +2,13 @@ use std::fmt::Display;
 use std::ops::{
     Add, AddAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Neg, Sub, SubAssign,
 };
-use std::vec;
+use serde::{Deserialize, Serialize};
+
 use crate::rtweekend;
 
-#[derive(Debug, Copy, Clone)]
+// Serialised as a plain [x, y, z] array.
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct Vec3 {
     pub vector: [f64; 3],
 }
@@ -18,7 +21,7 @@ impl Vec3 {
             vector: [tuple.0, tuple.1, tuple.2],
         }
     }
-    pub fn new(x: f64, y: f64, z: f64) -> Self {
+    pub const fn new(x: f64, y: f64, z: f64) -> Self {
         Self { vector: [x, y, z] }
     }
 }
@@ -47,6 +50,12 @@ impl Vec3 {
     pub fn z(&self) -> f64 {
         self.vector[2]
     }
+
+    // Component `index`, or None when it is not 0, 1 or 2.
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<f64> {
+        self.vector.get(index).copied()
+    }
 }
 
 //utilitis
@@ -83,7 +92,7 @@ impl Vec3 {
     #[inline]
     pub fn unit_vector(v: Vec3) -> Vec3 {
         if v.length() == 0.0 {
-            return Vec3::default();
+            Vec3::default()
         } else {
             v / v.length()
         }
@@ -98,8 +107,12 @@ impl Vec3 {
     #[inline]
     // rejection method
     pub fn random_unit_vector() -> Self {
+        Vec3::random_unit_vector_from(&mut rtweekend::random_double)
+    }
+    // The same with the numbers in [0, 1) drawn from `random`, e.g. a seeded Sampler.
+    pub fn random_unit_vector_from(random: &mut dyn FnMut() -> f64) -> Self {
         loop {
-            let p = Vec3::random_range(-1.0, 1.0);
+            let p = Vec3::new(2.0 * random() - 1.0, 2.0 * random() - 1.0, 2.0 * random() - 1.0);
             let lensq = p.length_squared();
             
             if 1e-160 < lensq && lensq <= 1.0 {
@@ -107,14 +120,46 @@ impl Vec3 {
             }
         }
     }
+    // Division that refuses a zero (or non-finite) divisor instead of producing inf / NaN components.
+    #[inline]
+    pub fn checked_div(&self, t: f64) -> Option<Vec3> {
+        if t == 0.0 || !t.is_finite() {
+            return None;
+        }
+        Some(*self / t)
+    }
+
+    #[inline]
+    pub fn near_zero(&self) -> bool {
+        // Return true if the vector is close to zero in all dimensions.
+        let s = 1e-8;
+        self.vector[0].abs() < s && self.vector[1].abs() < s && self.vector[2].abs() < s
+    }
+
+    #[inline]
+    pub fn reflect(v: &Vec3, n: &Vec3) -> Vec3 {
+        *v - 2.0 * Vec3::dot(v, n) * *n
+    }
+
+    #[inline]
+    pub fn refract(uv: &Vec3, n: &Vec3, etai_over_etat: f64) -> Vec3 {
+        let cos_theta = Vec3::dot(&-*uv, n).min(1.0);
+        let r_out_perp = etai_over_etat * (*uv + cos_theta * *n);
+        let r_out_parallel = -(1.0 - r_out_perp.length_squared()).abs().sqrt() * *n;
+        r_out_perp + r_out_parallel
+    }
+
     #[inline]
     pub fn random_on_hemisphere(normal: &Vec3) -> Self{
-        let on_unit_sphere = Vec3::random_unit_vector();
+        Vec3::random_on_hemisphere_from(normal, &mut rtweekend::random_double)
+    }
+    pub fn random_on_hemisphere_from(normal: &Vec3, random: &mut dyn FnMut() -> f64) -> Self {
+        let on_unit_sphere = Vec3::random_unit_vector_from(random);
         if Vec3::dot(&on_unit_sphere, normal) > 0.0 {
-            return  on_unit_sphere;
+            on_unit_sphere
         }
         else {
-            return  -on_unit_sphere;
+            -on_unit_sphere
         }
     }
 }
@@ -232,19 +277,14 @@ impl MulAssign<f64> for Vec3 {
 impl Div<f64> for Vec3 {
     type Output = Vec3;
 
+    // Follows f64 division: dividing by zero gives infinite or NaN components, see checked_div.
     fn div(self, t: f64) -> Vec3 {
-        if t == 0.0 {
-            panic!("Attempt to divide Vec3 by zero");
-        }
         self * (1.0 / t)
     }
 }
 
 impl DivAssign<f64> for Vec3 {
     fn div_assign(&mut self, rhs: f64) {
-        if rhs == 0.0 {
-            panic!("Attempt to divide Vec3 by zero");
-        }
         self.vector[0] /= rhs;
         self.vector[1] /= rhs;
         self.vector[2] /= rhs;
@@ -254,24 +294,15 @@ impl DivAssign<f64> for Vec3 {
 impl Index<usize> for Vec3 {
     type Output = f64;
 
+    // Out of range indices behave like any array index, use get for a checked access.
     fn index(&self, index: usize) -> &Self::Output {
-        match index {
-            0 => &self.vector[0],
-            1 => &self.vector[1],
-            2 => &self.vector[2],
-            _ => panic!("Index out of range for Vec3"),
-        }
+        &self.vector[index]
     }
 }
 
 impl IndexMut<usize> for Vec3 {
     fn index_mut(&mut self, index: usize) -> &mut Self::Output {
-        match index {
-            0 => &mut self.vector[0],
-            1 => &mut self.vector[1],
-            2 => &mut self.vector[2],
-            _ => panic!("Index out of range for Vec3"),
-        }
+        &mut self.vector[index]
     }
 }
 
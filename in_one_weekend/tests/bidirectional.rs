@@ -0,0 +1,59 @@
+// Tests of the bidirectional path tracer: on a scene without caustics, which the path tracer
+// renders well, both integrators must converge to the same image.
+
+mod common;
+
+use in_one_weekend::{
+    Background, Camera, Color, DirectionalLight, Image, IntegratorKind, Point3, SamplerKind, Sky, SpotLight, Vec3,
+};
+
+use common::{block_means, scene};
+
+// The shared scene with a spot and a directional light added.
+fn render(integrator: IntegratorKind, background: Background) -> Image {
+    let (world, mut lights) = scene();
+    lights.add(Box::new(SpotLight::new(
+        Point3::new(1.0, 2.0, -1.0),
+        Vec3::new(-0.5, -1.0, 0.0),
+        Color::new(20.0, 20.0, 30.0),
+        15.0,
+        30.0,
+    )));
+    lights.add(Box::new(DirectionalLight::new(Vec3::new(0.3, -1.0, -0.2), Color::new(0.5, 0.5, 0.5))));
+    let camera = common::camera(32)
+        .samples_per_pixel(256)
+        .sampler(SamplerKind::Sobol)
+        .background(background)
+        .integrator(integrator)
+        .build()
+        .expect("valid camera");
+    camera.render(&world, &lights).expect("scene renders")
+}
+
+fn assert_same_image(path_traced: &Image, bidirectional: &Image) {
+    for (block, (a, b)) in block_means(path_traced).iter().zip(&block_means(bidirectional)).enumerate() {
+        let error = (*a - *b).length() / (a.length() + 0.05);
+        assert!(error < 0.05, "block {}: path tracing gives {:?}, bidirectional {:?}", block, a, b);
+    }
+}
+
+#[test]
+fn bidirectional_matches_path_tracing() {
+    let path_traced = render(IntegratorKind::PathTracing, Background::Gradient);
+    let bidirectional = render(IntegratorKind::Bidirectional, Background::Gradient);
+    assert_same_image(&path_traced, &bidirectional);
+}
+
+#[test]
+fn bidirectional_matches_path_tracing_under_a_sky() {
+    let sky = || Background::Sky(Box::new(Sky::new(35.0, 40.0, 3.0)));
+    let path_traced = render(IntegratorKind::PathTracing, sky());
+    let bidirectional = render(IntegratorKind::Bidirectional, sky());
+    assert_same_image(&path_traced, &bidirectional);
+}
+
+#[test]
+fn bidirectional_renders_are_not_spectral() {
+    let camera = Camera::builder().spectral(true).integrator(IntegratorKind::Bidirectional).build();
+    assert!(camera.is_err());
+}
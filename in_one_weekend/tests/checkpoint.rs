@@ -0,0 +1,105 @@
+// Tests of checkpointed progressive renders: a resumed render must come out as if it had never
+// stopped, and a checkpoint must not be resumed against another scene.
+
+mod common;
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use in_one_weekend::{
+    Camera, Color, Error, HittableList, Image, ImageFormat, LightList, Point3, PointLight, ProgressiveSettings,
+    SamplerKind,
+};
+
+use common::scene;
+
+fn camera() -> Camera {
+    common::camera(16)
+        .max_depth(4)
+        .sampler(SamplerKind::Stratified)
+        .build()
+        .expect("valid camera")
+}
+
+// Files of one test, apart from those of the others running at the same time.
+struct Files {
+    checkpoint: PathBuf,
+    output: PathBuf,
+}
+
+impl Files {
+    fn new(name: &str) -> Files {
+        let dir = env::temp_dir().join(format!("in_one_weekend_checkpoint_{}_{}", name, std::process::id()));
+        fs::create_dir_all(&dir).expect("temporary directory");
+        Files {
+            checkpoint: dir.join("render.ckpt"),
+            output: dir.join("render.hdr"),
+        }
+    }
+}
+
+impl Drop for Files {
+    fn drop(&mut self) {
+        if let Some(dir) = self.checkpoint.parent() {
+            let _ = fs::remove_dir_all(dir);
+        }
+    }
+}
+
+// A progressive render of `scene`, checkpointed to `files`.
+fn render(
+    files: &Files,
+    scene: (HittableList, LightList),
+    max_samples: u32,
+    time_limit: Option<Duration>,
+    resume: bool,
+) -> Result<Image, Error> {
+    let (world, lights) = scene;
+    let settings = ProgressiveSettings {
+        samples_per_pass: 2,
+        max_samples,
+        time_limit,
+        checkpoint: Some(files.checkpoint.clone()),
+        resume,
+        ..ProgressiveSettings::default()
+    };
+    camera().render_progressive(&world, &lights, &settings, &files.output, ImageFormat::Hdr)
+}
+
+#[test]
+fn resumed_render_matches_an_uninterrupted_one() {
+    let files = Files::new("uninterrupted");
+    let uninterrupted = render(&files, scene(), 8, None, false).expect("render");
+
+    // Stopped by the time limit after the first pass, then resumed.
+    render(&files, scene(), 8, Some(Duration::ZERO), false).expect("first pass");
+    let resumed = render(&files, scene(), 8, None, true).expect("resumed render");
+    assert_eq!(resumed.pixels(), uninterrupted.pixels());
+}
+
+#[test]
+fn resuming_with_more_samples_keeps_the_sampler() {
+    // The stratified sampler's strata depend on its sample count (a 3 x 3 grid for 5 samples, 4 x 4
+    // for 16): resuming twice must take the same samples as resuming once to the same count.
+    let once = Files::new("once");
+    render(&once, scene(), 4, None, false).expect("render");
+    let resumed_once = render(&once, scene(), 16, None, true).expect("resumed render");
+
+    let twice = Files::new("twice");
+    render(&twice, scene(), 4, None, false).expect("render");
+    render(&twice, scene(), 5, None, true).expect("resumed render");
+    let resumed_twice = render(&twice, scene(), 16, None, true).expect("resumed render");
+    assert_eq!(resumed_twice.pixels(), resumed_once.pixels());
+}
+
+#[test]
+fn checkpoints_of_another_scene_are_refused() {
+    let files = Files::new("refused");
+    render(&files, scene(), 4, None, false).expect("render");
+    let (world, mut lights) = scene();
+    lights.add(Box::new(PointLight::new(Point3::new(1.5, 1.5, 0.0), Color::new(4.0, 4.0, 4.0))));
+    let result = render(&files, (world, lights), 8, None, true);
+    assert!(matches!(result, Err(Error::Checkpoint(_))), "{:?}", result.err());
+}
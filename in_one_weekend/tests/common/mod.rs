@@ -0,0 +1,83 @@
+// Fixtures shared by the integration tests: the small scene most of them render, the camera
+// looking at it, and the image statistics renders are compared by.
+// Every test crate compiles its own copy and uses only part of it.
+#![allow(dead_code)]
+
+use std::sync::Arc;
+
+use in_one_weekend::{
+    Camera, CameraBuilder, Color, ComplexIor, Conductor, HittableList, Image, Lambertian, LightList, Point3, PointLight,
+    Sphere,
+};
+
+// Side of the pixel blocks whose means are compared.
+pub const BLOCK: u32 = 8;
+
+// The diffuse ground every scene stands on.
+pub fn floor() -> Box<Sphere> {
+    Box::new(Sphere::new(
+        Point3::new(0.0, -100.5, -1.0),
+        100.0,
+        Arc::new(Lambertian::new(Color::new(0.6, 0.6, 0.6))),
+    ))
+}
+
+// A diffuse red sphere and a rough gold one on the floor, under a point light.
+// (Metal counts as specular, so the glossy sphere is a rough conductor: a fuzzy metal would reflect
+// caustics only the bidirectional tracer finds.)
+pub fn scene() -> (HittableList, LightList) {
+    let mut world = HittableList::new();
+    world.add(floor());
+    world.add(Box::new(Sphere::new(
+        Point3::new(-0.6, 0.0, -1.2),
+        0.5,
+        Arc::new(Lambertian::new(Color::new(0.7, 0.3, 0.3))),
+    )));
+    world.add(Box::new(Sphere::new(
+        Point3::new(0.6, 0.0, -1.2),
+        0.5,
+        Arc::new(Conductor::new(ComplexIor::GOLD, 0.4, 0.0)),
+    )));
+
+    let mut lights = LightList::new();
+    lights.add(Box::new(PointLight::new(Point3::new(-1.5, 1.5, 0.0), Color::new(8.0, 6.0, 4.0))));
+    (world, lights)
+}
+
+// A 4:3 camera looking down at the spheres, tests set the rest.
+pub fn camera(image_width: u32) -> CameraBuilder {
+    Camera::builder()
+        .aspect_ratio(4.0 / 3.0)
+        .image_width(image_width)
+        .max_depth(8)
+        .look_from(Point3::new(0.0, 1.0, 1.0))
+        .look_at(Point3::new(0.0, 0.0, -1.0))
+}
+
+pub fn mean(image: &Image) -> Color {
+    let mut sum = Color::default();
+    for y in 0..image.height() {
+        for x in 0..image.width() {
+            sum += image.pixel(x, y);
+        }
+    }
+    sum / (image.width() as f64 * image.height() as f64)
+}
+
+// Means of the BLOCK x BLOCK pixel blocks.
+pub fn block_means(image: &Image) -> Vec<Color> {
+    let mut means = Vec::new();
+    for y0 in (0..image.height()).step_by(BLOCK as usize) {
+        for x0 in (0..image.width()).step_by(BLOCK as usize) {
+            let (x1, y1) = ((x0 + BLOCK).min(image.width()), (y0 + BLOCK).min(image.height()));
+            let mut sum = Color::default();
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    sum += image.pixel(x, y);
+                }
+            }
+            means.push(sum / ((x1 - x0) * (y1 - y0)) as f64);
+        }
+    }
+    means
+}
@@ -0,0 +1,148 @@
+// Tests of distributed renders against workers on this machine: the tiles must add up to the
+// image of a local render, whichever workers answer, drop out or refuse the job.
+
+mod common;
+
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use in_one_weekend::distributed::serve_worker;
+use in_one_weekend::scene_hash::scene_hash;
+use in_one_weekend::{Camera, DistributedSettings, Image};
+
+use common::scene;
+
+fn camera() -> Camera {
+    common::camera(32)
+        .samples_per_pixel(4)
+        .max_depth(4)
+        .build()
+        .expect("valid camera")
+}
+
+fn settings(workers: Vec<String>) -> DistributedSettings {
+    DistributedSettings {
+        workers,
+        tile_size: 8,
+        timeout: Duration::from_secs(30),
+    }
+}
+
+// Starts serving the scene on `listener` in the background (for as long as the test runs).
+fn start_worker(listener: TcpListener) {
+    thread::spawn(move || {
+        let (world, lights) = scene();
+        serve_worker(&listener, &world, &lights)
+    });
+}
+
+fn bind() -> (TcpListener, String) {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("a free port");
+    let address = listener.local_addr().expect("bound address").to_string();
+    (listener, address)
+}
+
+// The address of a port nobody listens on.
+fn dead_address() -> String {
+    bind().1
+}
+
+// Tiles are merged in whatever order they come back, so sums of overlapping splats may round
+// differently than in a local render.
+fn assert_same_image(a: &Image, b: &Image) {
+    assert_eq!((a.width(), a.height()), (b.width(), b.height()));
+    for y in 0..a.height() {
+        for x in 0..a.width() {
+            let (p, q) = (a.pixel(x, y), b.pixel(x, y));
+            assert!((p - q).length() <= 1e-9 * (1.0 + p.length()), "pixel ({}, {}): {:?} vs {:?}", x, y, p, q);
+        }
+    }
+}
+
+// One message of the worker protocol: a kind byte, the payload length and the payload.
+fn write_message(stream: &mut TcpStream, kind: u8, payload: &[u8]) {
+    stream.write_all(&[kind]).expect("write");
+    stream.write_all(&(payload.len() as u32).to_le_bytes()).expect("write");
+    stream.write_all(payload).expect("write");
+}
+
+fn read_message(stream: &mut TcpStream) -> (u8, Vec<u8>) {
+    let mut header = [0u8; 5];
+    stream.read_exact(&mut header).expect("read");
+    let mut payload = vec![0u8; u32::from_le_bytes([header[1], header[2], header[3], header[4]]) as usize];
+    stream.read_exact(&mut payload).expect("read");
+    (header[0], payload)
+}
+
+#[test]
+fn distributed_render_matches_a_local_one() {
+    let (world, lights) = scene();
+    let local = camera().render(&world, &lights).expect("local render");
+
+    let (first, first_address) = bind();
+    let (second, second_address) = bind();
+    start_worker(first);
+    start_worker(second);
+    // The dead worker is dropped right away, the others render all of the tiles.
+    let workers = vec![first_address, dead_address(), second_address];
+    let distributed = camera().render_distributed(&world, &lights, &settings(workers)).expect("distributed render");
+    assert_same_image(&distributed, &local);
+}
+
+#[test]
+fn tiles_of_a_lost_worker_are_reassigned() {
+    let (world, lights) = scene();
+    let local = camera().render(&world, &lights).expect("local render");
+    let hash = scene_hash(&world, &lights, camera().settings());
+
+    // A worker that takes the job and a tile, then hangs up without an answer.
+    let (quitter, quitter_address) = bind();
+    let (taken_tx, taken) = mpsc::channel();
+    thread::spawn(move || {
+        let (mut stream, _) = quitter.accept().expect("coordinator connects");
+        read_message(&mut stream);
+        write_message(&mut stream, 2, &hash.to_le_bytes());
+        let (kind, tile) = read_message(&mut stream);
+        assert_eq!(kind, 3, "expected a tile");
+        taken_tx.send(tile).expect("test still running");
+    });
+    // The real worker only starts serving once the quitter holds its tile, so the tile can't
+    // have been rendered before it was lost.
+    let (worker, worker_address) = bind();
+    let (started_tx, started) = mpsc::channel();
+    thread::spawn(move || {
+        let (world, lights) = scene();
+        started.recv().expect("quitter took a tile");
+        serve_worker(&worker, &world, &lights)
+    });
+    let gate = thread::spawn(move || {
+        let tile = taken.recv().expect("quitter took a tile");
+        started_tx.send(()).expect("worker waits");
+        tile
+    });
+
+    let workers = vec![quitter_address, worker_address];
+    let distributed = camera().render_distributed(&world, &lights, &settings(workers)).expect("distributed render");
+    let tile = gate.join().expect("gate thread");
+    assert_eq!(tile.len(), 20, "tile number and bounds");
+    assert_same_image(&distributed, &local);
+}
+
+#[test]
+fn workers_refuse_images_too_large_to_render() {
+    let (listener, address) = bind();
+    start_worker(listener);
+
+    let mut settings = camera().settings().clone();
+    // Within what a camera renders, beyond what a worker takes on.
+    settings.aspect_ratio = 1.0;
+    settings.image_width = 1 << 14;
+    let mut stream = TcpStream::connect(address).expect("worker listens");
+    write_message(&mut stream, 1, settings.to_json().expect("settings serialise").as_bytes());
+    let (kind, reason) = read_message(&mut stream);
+    assert_eq!(kind, 6, "expected an error, got {}", String::from_utf8_lossy(&reason));
+    assert!(String::from_utf8_lossy(&reason).contains("more than a worker renders"));
+}
@@ -0,0 +1,112 @@
+// Tests of Metropolis light transport: the mutations of the primary sample space sampler, and
+// renders that must come out as bright as the path tracer's.
+
+mod common;
+
+use in_one_weekend::metropolis_sampler::MetropolisSampler;
+use in_one_weekend::{AdaptiveSettings, Camera, IntegratorKind, MetropolisSettings, Sampler};
+
+use common::{block_means, mean, scene};
+
+#[test]
+fn first_path_repeats_from_the_seed() {
+    // A chain starts from the bootstrap path with the same seed, it must find the same numbers.
+    let path = |seed: u64| {
+        let mut sampler = MetropolisSampler::new(seed, 0.01, 0.3);
+        (0..16).map(|_| sampler.get_1d()).collect::<Vec<f64>>()
+    };
+    assert_eq!(path(3), path(3));
+    assert_ne!(path(3), path(4));
+}
+
+#[test]
+fn mutations_keep_the_numbers_uniform() {
+    // Accepting every proposal leaves the uniform distribution as it is, also for numbers the
+    // path only asks for now and then (mutated by several small steps at once).
+    const BINS: usize = 10;
+    const ITERATIONS: u32 = 100_000;
+    let mut sampler = MetropolisSampler::new(1, 0.05, 0.3);
+    let mut counts = [[0.0; BINS]; 2];
+    for iteration in 0..ITERATIONS {
+        let every = sampler.get_1d();
+        counts[0][(every * BINS as f64) as usize] += 1.0;
+        if iteration % 7 == 0 {
+            let seldom = sampler.get_1d();
+            assert!((0.0..1.0).contains(&seldom));
+            counts[1][(seldom * BINS as f64) as usize] += 1.0;
+        }
+        sampler.start_iteration();
+        sampler.accept();
+    }
+    // The numbers are correlated from one iteration to the next, the bound is loose.
+    for counts in counts {
+        let total: f64 = counts.iter().sum();
+        for count in counts {
+            let share = count / total;
+            assert!((share - 0.1).abs() < 0.02, "{:?}", counts);
+        }
+    }
+}
+
+#[test]
+fn rejected_proposals_are_undone() {
+    // Small steps only: after many rejected proposals the next one is still a single small step
+    // away from the first path.
+    let mut sampler = MetropolisSampler::new(2, 1e-4, 0.0);
+    let first: Vec<f64> = (0..4).map(|_| sampler.get_1d()).collect();
+    for _ in 0..1000 {
+        sampler.start_iteration();
+        for _ in 0..4 {
+            sampler.get_1d();
+        }
+        sampler.reject();
+    }
+    sampler.start_iteration();
+    for x in first {
+        let y = sampler.get_1d();
+        let distance = (x - y).abs().min(1.0 - (x - y).abs());
+        assert!(distance < 1e-3, "{} moved to {}", x, y);
+    }
+}
+
+fn camera(integrator: IntegratorKind, samples_per_pixel: u32) -> Camera {
+    common::camera(40)
+        .samples_per_pixel(samples_per_pixel)
+        .integrator(integrator)
+        .build()
+        .expect("valid camera")
+}
+
+#[test]
+fn metropolis_is_as_bright_as_path_tracing() {
+    let (world, lights) = scene();
+    let path_traced = camera(IntegratorKind::PathTracing, 64).render(&world, &lights).expect("scene renders");
+    let metropolis = camera(IntegratorKind::Metropolis, 64).render(&world, &lights).expect("scene renders");
+    let (a, b) = (mean(&path_traced), mean(&metropolis));
+    let error = (a - b).length() / a.length();
+    assert!(error < 0.03, "path tracing gives {:?}, metropolis {:?}", a, b);
+    // Also part by part: the chains must have covered all of the image.
+    for (block, (a, b)) in block_means(&path_traced).iter().zip(&block_means(&metropolis)).enumerate() {
+        let error = (*a - *b).length() / a.length();
+        assert!(error < 0.1, "block {}: path tracing gives {:?}, metropolis {:?}", block, a, b);
+    }
+
+    // The same seed renders the same image, however the chains' threads were scheduled.
+    let again = camera(IntegratorKind::Metropolis, 64).render(&world, &lights).expect("scene renders");
+    assert_eq!(again.pixels(), metropolis.pixels());
+}
+
+#[test]
+fn metropolis_renders_are_not_adaptive() {
+    let (world, lights) = scene();
+    let result = camera(IntegratorKind::Metropolis, 4).render_adaptive(&world, &lights, &AdaptiveSettings::default());
+    assert!(result.is_err());
+}
+
+#[test]
+fn chains_are_bounded() {
+    let chains = |chains: u32| MetropolisSettings { chains, ..MetropolisSettings::default() }.validate();
+    assert!(chains(0).is_err());
+    assert!(chains(256).is_ok());
+    assert!(chains(1 << 20).is_err());
+}
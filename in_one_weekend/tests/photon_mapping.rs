@@ -0,0 +1,146 @@
+// Tests of the photon mapping integrators: the kd-tree lookups, the progressive radius, and the
+// caustics they add to the path tracer, which the bidirectional path tracer finds as well.
+
+mod common;
+
+use std::sync::Arc;
+
+use in_one_weekend::tile::Tile;
+use in_one_weekend::{
+    Camera, Color, Dielectric, HittableList, Image, IntegratorKind, Lambertian, LightList, Photon, PhotonMap,
+    PhotonSettings, Point3, PointLight, SamplerKind, Sampler, Sphere, Vec3,
+};
+
+use common::mean;
+
+fn random_photons(sampler: &mut dyn Sampler, count: u32) -> Vec<Photon> {
+    (0..count)
+        .map(|n| {
+            sampler.start_pixel_sample(n, 0, 0);
+            let p = Point3::new(sampler.get_1d(), sampler.get_1d(), sampler.get_1d() * 0.1);
+            Photon {
+                p,
+                wi: Vec3::new(0.0, 0.0, 1.0),
+                power: Color::new(n as f64, 0.0, 0.0), // Tells the photons apart
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn lookups_find_the_photons_a_linear_search_finds() {
+    let mut sampler = SamplerKind::Independent.create(1, 5);
+    let photons = random_photons(sampler.as_mut(), 2000);
+    let map = PhotonMap::new(photons.clone());
+    assert_eq!(map.len(), photons.len());
+
+    for q in 0..200 {
+        sampler.start_pixel_sample(q, 1, 0);
+        let p = Point3::new(sampler.get_1d() * 1.2 - 0.1, sampler.get_1d() * 1.2 - 0.1, sampler.get_1d() * 0.1);
+        let radius = 0.01 + 0.1 * sampler.get_1d();
+
+        let mut found: Vec<u64> = Vec::new();
+        map.for_each_within(&p, radius, |photon| found.push(photon.power.x() as u64));
+        found.sort_unstable();
+        let mut expected: Vec<u64> = photons
+            .iter()
+            .filter(|photon| (photon.p - p).length() < radius)
+            .map(|photon| photon.power.x() as u64)
+            .collect();
+        expected.sort_unstable();
+        assert_eq!(found, expected, "photons within {} of {:?}", radius, p);
+    }
+}
+
+#[test]
+fn progressive_radius_shrinks() {
+    let settings = PhotonSettings {
+        radius: 0.1,
+        alpha: 0.5,
+        ..PhotonSettings::default()
+    };
+    assert_eq!(settings.radius_at(0), 0.1);
+    // r_1^2 = r_0^2 (1 + alpha) / 2
+    assert!((settings.radius_at(1) - 0.1 * 0.75f64.sqrt()).abs() < 1e-12);
+    let radii: Vec<f64> = (0..100).map(|pass| settings.radius_at(pass)).collect();
+    assert!(radii.windows(2).all(|w| w[1] < w[0]));
+    // Slowly enough that the photons of all the passes add up to an unbiased estimate: r^2 falls
+    // like pass^(alpha - 1).
+    let ratio = radii[99] * radii[99] / (radii[49] * radii[49]);
+    assert!((ratio - 0.5f64.sqrt()).abs() < 0.01, "r^2 ratio {}", ratio);
+}
+
+// A glass sphere on a diffuse floor under a point light, `glass` false for a diffuse sphere.
+fn scene(glass: bool) -> (HittableList, LightList) {
+    let mut world = HittableList::new();
+    world.add(common::floor());
+    if glass {
+        world.add(Box::new(Sphere::new(Point3::new(0.0, 0.0, -1.0), 0.5, Arc::new(Dielectric::new(1.5)))));
+    } else {
+        world.add(Box::new(Sphere::new(
+            Point3::new(0.0, 0.0, -1.0),
+            0.5,
+            Arc::new(Lambertian::new(Color::new(0.7, 0.3, 0.3))),
+        )));
+    }
+    let mut lights = LightList::new();
+    lights.add(Box::new(PointLight::new(Point3::new(0.0, 1.5, -1.8), Color::new(40.0, 40.0, 40.0))));
+    (world, lights)
+}
+
+fn camera(integrator: IntegratorKind, samples_per_pixel: u32) -> Camera {
+    let mut camera = common::camera(40)
+        .samples_per_pixel(samples_per_pixel)
+        .vfov(50.0)
+        .look_from(Point3::new(0.0, 1.5, 1.0))
+        .look_at(Point3::new(0.0, -0.3, -1.0))
+        .integrator(integrator)
+        .settings()
+        .clone();
+    camera.photons.count = 20_000;
+    camera.photons.radius = 0.1;
+    Camera::new(camera).expect("valid camera")
+}
+
+fn render(integrator: IntegratorKind, glass: bool, samples_per_pixel: u32) -> Image {
+    let (world, lights) = scene(glass);
+    camera(integrator, samples_per_pixel).render(&world, &lights).expect("scene renders")
+}
+
+#[test]
+fn photon_mapping_only_adds_caustics() {
+    // Without glass no photon is stored, the images are the path tracer's.
+    let path_traced = render(IntegratorKind::PathTracing, false, 4);
+    for integrator in [IntegratorKind::PhotonMapping, IntegratorKind::ProgressivePhotonMapping] {
+        let image = render(integrator, false, 4);
+        assert_eq!(image.pixels(), path_traced.pixels(), "{}", integrator);
+    }
+}
+
+#[test]
+fn progressive_photon_mapping_finds_the_caustics_bidirectional_finds() {
+    let path_traced = mean(&render(IntegratorKind::PathTracing, true, 64));
+    let bidirectional = mean(&render(IntegratorKind::Bidirectional, true, 64));
+    let photon_mapped = mean(&render(IntegratorKind::ProgressivePhotonMapping, true, 64));
+    // The caustic is a good part of the image, which the path tracer misses.
+    assert!(bidirectional.x() > 1.05 * path_traced.x(), "{:?} {:?}", bidirectional, path_traced);
+    let error = (photon_mapped - bidirectional).length() / bidirectional.length();
+    assert!(error < 0.03, "photon mapping {:?}, bidirectional {:?}", photon_mapped, bidirectional);
+}
+
+#[test]
+fn tiles_of_another_scene_trace_their_own_photons() {
+    // The photon maps a camera keeps from tile to tile must not light another scene: without the
+    // glass sphere there are no caustics.
+    let render_tile = |camera: &Camera, glass: bool| {
+        let (world, lights) = scene(glass);
+        let mut accumulator = camera.create_accumulator();
+        let tile = Tile::new(0, 0, camera.image_width(), camera.image_height());
+        camera.render_tile(&world, &lights, &tile, &mut accumulator);
+        accumulator.to_image()
+    };
+    let camera = camera(IntegratorKind::ProgressivePhotonMapping, 8);
+    render_tile(&camera, true);
+    let after_glass = render_tile(&camera, false);
+    assert_eq!(after_glass.pixels(), render(IntegratorKind::PathTracing, false, 8).pixels());
+}
@@ -0,0 +1,210 @@
+// Image regression tests: renders the scene files in tests/scenes and compares them against the
+// reference images in tests/references.
+//
+// Renders are seeded, so on one machine they come out the same every time, but any change to the
+// order samples are drawn in gives a different (and equally valid) noise pattern. Images are therefore
+// compared after averaging BLOCK x BLOCK pixel blocks, which removes most of the noise but keeps any
+// change in brightness, color or geometry. On a failure the rendered image, the reference and a
+// magnified difference are written to target/tmp/regression.
+//
+// After an intended change to the images, write new references with
+//     UPDATE_REFERENCES=1 cargo test --test regression
+// and look at them before committing.
+
+use std::env;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use in_one_weekend::{ImageFormat, SceneFile};
+
+// Side of the pixel blocks that are averaged before comparing.
+const BLOCK: usize = 4;
+
+// Largest root mean square difference of the block averages, in [0, 1] sRGB units.
+const TOLERANCE: f64 = 0.01;
+
+// An 8-bit sRGB image as stored in the reference PNGs.
+struct Rgb8 {
+    width: usize,
+    height: usize,
+    data: Vec<u8>,
+}
+
+impl Rgb8 {
+    fn read(path: &Path) -> Rgb8 {
+        let bytes = fs::read(path).unwrap_or_else(|e| panic!("cannot read {}: {}", path.display(), e));
+        Rgb8::decode(&bytes)
+    }
+
+    fn decode(bytes: &[u8]) -> Rgb8 {
+        let mut reader = png::Decoder::new(bytes).read_info().expect("a PNG image");
+        let mut data = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut data).expect("a valid PNG image");
+        assert_eq!(info.color_type, png::ColorType::Rgb, "not an RGB PNG");
+        assert_eq!(info.bit_depth, png::BitDepth::Eight, "not an 8-bit PNG");
+        data.truncate(info.buffer_size());
+        Rgb8 {
+            width: info.width as usize,
+            height: info.height as usize,
+            data,
+        }
+    }
+
+    fn write(&self, path: &Path) {
+        let file = File::create(path).unwrap_or_else(|e| panic!("cannot create {}: {}", path.display(), e));
+        let mut encoder = png::Encoder::new(BufWriter::new(file), self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().expect("PNG header");
+        writer.write_image_data(&self.data).expect("PNG data");
+    }
+
+    // Means of the BLOCK x BLOCK blocks (smaller at the right and bottom edges), per channel in [0, 1].
+    fn block_means(&self) -> Vec<f64> {
+        let mut means = Vec::new();
+        for y0 in (0..self.height).step_by(BLOCK) {
+            for x0 in (0..self.width).step_by(BLOCK) {
+                let (x1, y1) = ((x0 + BLOCK).min(self.width), (y0 + BLOCK).min(self.height));
+                let count = ((x1 - x0) * (y1 - y0)) as f64;
+                for c in 0..3 {
+                    let mut sum = 0.0;
+                    for y in y0..y1 {
+                        for x in x0..x1 {
+                            sum += self.data[3 * (y * self.width + x) + c] as f64;
+                        }
+                    }
+                    means.push(sum / count / 255.0);
+                }
+            }
+        }
+        means
+    }
+
+    // Absolute difference, magnified so small errors are visible.
+    fn difference(&self, other: &Rgb8) -> Rgb8 {
+        let data = self
+            .data
+            .iter()
+            .zip(&other.data)
+            .map(|(&a, &b)| (a.abs_diff(b) as u32 * 8).min(255) as u8)
+            .collect();
+        Rgb8 {
+            width: self.width,
+            height: self.height,
+            data,
+        }
+    }
+}
+
+fn rmse(a: &[f64], b: &[f64]) -> f64 {
+    let sum: f64 = a.iter().zip(b).map(|(x, y)| (x - y) * (x - y)).sum();
+    (sum / a.len() as f64).sqrt()
+}
+
+fn render(name: &str) -> Rgb8 {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/scenes").join(format!("{}.toml", name));
+    let scene = SceneFile::load(&path).and_then(|file| file.build()).expect("scene file is valid");
+    let image = scene.camera.render(&scene.world, &scene.lights).expect("scene renders");
+
+    let mut png = Vec::new();
+    ImageFormat::Png
+        .encoder(*scene.camera.tone_mapping())
+        .encode(&image, &mut png)
+        .expect("image encodes");
+    Rgb8::decode(&png)
+}
+
+fn check(name: &str) {
+    let actual = render(name);
+    let reference_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/references").join(format!("{}.png", name));
+
+    if env::var_os("UPDATE_REFERENCES").is_some() {
+        actual.write(&reference_path);
+        return;
+    }
+
+    let expected = Rgb8::read(&reference_path);
+    assert_eq!(
+        (actual.width, actual.height),
+        (expected.width, expected.height),
+        "{}: rendered size differs from the reference",
+        name
+    );
+
+    let error = rmse(&actual.block_means(), &expected.block_means());
+    if error > TOLERANCE {
+        let out = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("regression");
+        fs::create_dir_all(&out).expect("can create the diff directory");
+        actual.write(&out.join(format!("{}.actual.png", name)));
+        expected.write(&out.join(format!("{}.expected.png", name)));
+        actual.difference(&expected).write(&out.join(format!("{}.diff.png", name)));
+        panic!(
+            "{}: block RMSE {:.4} exceeds {} (images written to {})",
+            name,
+            error,
+            TOLERANCE,
+            out.display()
+        );
+    }
+}
+
+#[test]
+fn diffuse() {
+    check("diffuse");
+}
+
+#[test]
+fn materials() {
+    check("materials");
+}
+
+#[test]
+fn lights() {
+    check("lights");
+}
+
+#[test]
+fn sky() {
+    check("sky");
+}
+
+#[test]
+fn principled() {
+    check("principled");
+}
+
+#[test]
+fn microfacet() {
+    check("microfacet");
+}
+
+#[test]
+fn dispersion() {
+    check("dispersion");
+}
+
+#[test]
+fn thin_film() {
+    check("thin_film");
+}
+
+#[test]
+fn subsurface() {
+    check("subsurface");
+}
+
+#[test]
+fn bidirectional() {
+    check("bidirectional");
+}
+
+#[test]
+fn photon_mapping() {
+    check("photon_mapping");
+}
+
+#[test]
+fn metropolis() {
+    check("metropolis");
+}
@@ -0,0 +1,192 @@
+// Tests of the render server's HTTP API, served on this machine: submitting scenes, following
+// their jobs, fetching the images and cancelling.
+
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use in_one_weekend::render_server::serve_listener;
+
+const SCENE: &str = r#"
+[camera]
+aspect_ratio = 1.3333333333333333
+image_width = 16
+samples_per_pixel = 2
+max_depth = 4
+
+[[objects]]
+type = "sphere"
+center = [0.0, -100.5, -1.0]
+radius = 100.0
+material = { type = "lambertian", albedo = [0.6, 0.6, 0.6] }
+
+[[objects]]
+type = "sphere"
+center = [0.0, 0.0, -1.0]
+radius = 0.5
+material = { type = "lambertian", albedo = [0.7, 0.3, 0.3] }
+
+[[lights]]
+type = "point"
+position = [-1.5, 1.5, 0.0]
+intensity = [8.0, 6.0, 4.0]
+"#;
+
+// Long enough to still be running when a test is done with it.
+fn slow_scene() -> String {
+    SCENE.replace("image_width = 16", "image_width = 256").replace("samples_per_pixel = 2", "samples_per_pixel = 10000")
+}
+
+// A server of its own for every test, so their jobs don't queue behind each other.
+fn start_server() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("a free port");
+    let address = listener.local_addr().expect("bound address").to_string();
+    thread::spawn(move || serve_listener(listener));
+    address
+}
+
+// Sends one request and returns the status code and body of the answer.
+fn request(address: &str, method: &str, path: &str, body: &str) -> (u16, Vec<u8>) {
+    let mut stream = TcpStream::connect(address).expect("server listens");
+    write!(
+        stream,
+        "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        method,
+        path,
+        address,
+        body.len(),
+        body
+    )
+    .expect("write request");
+    let mut answer = Vec::new();
+    stream.read_to_end(&mut answer).expect("read answer");
+
+    let end = answer.windows(4).position(|w| w == b"\r\n\r\n").expect("end of the headers");
+    let head = String::from_utf8_lossy(&answer[..end]);
+    let status = head.split_whitespace().nth(1).and_then(|s| s.parse().ok()).expect("status code");
+    (status, answer[end + 4..].to_vec())
+}
+
+fn json(body: &[u8]) -> serde_json::Value {
+    serde_json::from_slice(body).expect("JSON answer")
+}
+
+fn submit(address: &str, scene: &str) -> u64 {
+    let (status, body) = request(address, "POST", "/jobs", scene);
+    assert_eq!(status, 201, "{}", String::from_utf8_lossy(&body));
+    json(&body)["id"].as_u64().expect("job id")
+}
+
+fn state(address: &str, id: u64) -> String {
+    let (status, body) = request(address, "GET", &format!("/jobs/{}", id), "");
+    assert_eq!(status, 200);
+    json(&body)["state"].as_str().expect("job state").to_string()
+}
+
+fn wait_until_finished(address: &str, id: u64) -> String {
+    let start = Instant::now();
+    loop {
+        let state = state(address, id);
+        if state != "queued" && state != "running" {
+            return state;
+        }
+        assert!(start.elapsed() < Duration::from_secs(60), "job {} is still {}", id, state);
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+#[test]
+fn submitted_scenes_render_to_images() {
+    let address = start_server();
+    let id = submit(&address, SCENE);
+    assert_eq!(wait_until_finished(&address, id), "done");
+
+    let (status, body) = request(&address, "GET", "/jobs", "");
+    assert_eq!(status, 200);
+    assert_eq!(json(&body)[0]["id"].as_u64(), Some(id));
+
+    let (status, png) = request(&address, "GET", &format!("/jobs/{}/image", id), "");
+    assert_eq!(status, 200);
+    assert!(png.starts_with(b"\x89PNG"));
+    let (status, ppm) = request(&address, "GET", &format!("/jobs/{}/image?format=ppm", id), "");
+    assert_eq!(status, 200);
+    assert!(ppm.starts_with(b"P3\n16 12\n"));
+    let (status, _) = request(&address, "GET", &format!("/jobs/{}/image?format=gif", id), "");
+    assert_eq!(status, 400);
+
+    // Deleting a finished job forgets it.
+    let (status, _) = request(&address, "DELETE", &format!("/jobs/{}", id), "");
+    assert_eq!(status, 200);
+    let (status, _) = request(&address, "GET", &format!("/jobs/{}", id), "");
+    assert_eq!(status, 404);
+}
+
+#[test]
+fn queued_and_running_jobs_can_be_cancelled() {
+    let address = start_server();
+    let slow = submit(&address, &slow_scene());
+    let queued = submit(&address, SCENE);
+    assert_eq!(state(&address, queued), "queued");
+
+    let (status, body) = request(&address, "DELETE", &format!("/jobs/{}", queued), "");
+    assert_eq!(status, 200);
+    assert_eq!(json(&body)["state"], "cancelled");
+    let (status, _) = request(&address, "DELETE", &format!("/jobs/{}", slow), "");
+    assert_eq!(status, 202);
+    assert_eq!(wait_until_finished(&address, slow), "cancelled");
+
+    let (status, body) = request(&address, "GET", &format!("/jobs/{}/image", slow), "");
+    assert_eq!(status, 409, "{}", String::from_utf8_lossy(&body));
+}
+
+#[test]
+fn bad_requests_are_refused() {
+    let address = start_server();
+    let (status, _) = request(&address, "POST", "/jobs", "[camera]\nimage_width = \"wide\"\n");
+    assert_eq!(status, 400);
+    let (status, body) = request(&address, "POST", "/jobs", &SCENE.replace("image_width = 16", "image_width = 10000"));
+    assert_eq!(status, 413, "{}", String::from_utf8_lossy(&body));
+    let (status, body) =
+        request(&address, "POST", "/jobs", &SCENE.replace("samples_per_pixel = 2", "samples_per_pixel = 100000"));
+    assert_eq!(status, 413, "{}", String::from_utf8_lossy(&body));
+    let textured = SCENE.replace(
+        r#"{ type = "lambertian", albedo = [0.7, 0.3, 0.3] }"#,
+        r#"{ type = "principled", base_color = { type = "image", path = "/etc/passwd" } }"#,
+    );
+    let (status, body) = request(&address, "POST", "/jobs", &textured);
+    assert_eq!(status, 400, "{}", String::from_utf8_lossy(&body));
+    assert!(String::from_utf8_lossy(&body).contains("image textures"));
+    let (status, _) = request(&address, "GET", "/jobs/7", "");
+    assert_eq!(status, 404);
+    let (status, _) = request(&address, "PUT", "/jobs", "");
+    assert_eq!(status, 405);
+}
+
+#[test]
+fn only_the_latest_finished_jobs_are_kept() {
+    let address = start_server();
+    let ids: Vec<u64> = (0..20).map(|_| submit(&address, SCENE)).collect();
+    for &id in &ids[16..] {
+        wait_until_finished(&address, id);
+    }
+    let (_, body) = request(&address, "GET", "/jobs", "");
+    let kept: Vec<u64> = json(&body).as_array().expect("job list").iter().filter_map(|job| job["id"].as_u64()).collect();
+    assert_eq!(kept, ids[4..]);
+}
+
+#[test]
+fn full_queues_refuse_jobs() {
+    let address = start_server();
+    let slow = submit(&address, &slow_scene());
+    let queued: Vec<u64> = (0..64).map(|_| submit(&address, SCENE)).collect();
+    let (status, body) = request(&address, "POST", "/jobs", SCENE);
+    assert_eq!(status, 429, "{}", String::from_utf8_lossy(&body));
+
+    // Room again once a queued job is cancelled.
+    let (status, _) = request(&address, "DELETE", &format!("/jobs/{}", queued[0]), "");
+    assert_eq!(status, 200);
+    submit(&address, SCENE);
+    let (status, _) = request(&address, "DELETE", &format!("/jobs/{}", slow), "");
+    assert_eq!(status, 202);
+}
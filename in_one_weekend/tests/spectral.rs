@@ -0,0 +1,141 @@
+// Tests of the spectral rendering mode: turning RGB into spectra and back, the wavelength sampling
+// and the dispersion formulas of dielectrics.
+
+use in_one_weekend::spectrum::{self, LAMBDA_MAX, LAMBDA_MIN, WAVELENGTHS};
+use in_one_weekend::{Color, Dispersion, SampledSpectrum, SampledWavelengths};
+
+// Stratified hero wavelengths, enough for the estimates below to be within about 1e-3.
+const STRATA: usize = 4096;
+
+// Average of to_rgb over stratified wavelength samples, the color a spectral render converges to.
+fn mean_rgb(spectrum: impl Fn(&SampledWavelengths) -> SampledSpectrum, terminate: bool) -> Color {
+    let mut sum = Color::default();
+    for s in 0..STRATA {
+        let mut wavelengths = SampledWavelengths::sample((s as f64 + 0.5) / STRATA as f64);
+        if terminate {
+            wavelengths.terminate_secondary();
+        }
+        sum += wavelengths.to_rgb(&spectrum(&wavelengths));
+    }
+    sum / STRATA as f64
+}
+
+fn assert_close(a: &Color, b: &Color, tolerance: f64) {
+    let error = (*a - *b).length();
+    assert!(error < tolerance, "{:?} and {:?} differ by {}", a, b, error);
+}
+
+#[test]
+fn greys_survive_the_round_trip() {
+    for grey in [0.0, 0.18, 0.5, 1.0, 3.0] {
+        let color = Color::new(grey, grey, grey);
+        let rgb = mean_rgb(|w| w.upsample(&color), false);
+        assert_close(&rgb, &color, 2e-3 * grey.max(1.0));
+    }
+}
+
+#[test]
+fn colors_survive_the_round_trip() {
+    let colors = [
+        Color::new(1.0, 0.0, 0.0),
+        Color::new(0.0, 1.0, 0.0),
+        Color::new(0.0, 0.0, 1.0),
+        Color::new(1.0, 1.0, 0.0),
+        Color::new(0.0, 1.0, 1.0),
+        Color::new(1.0, 0.0, 1.0),
+        Color::new(0.8, 0.3, 0.1),
+        Color::new(0.1, 0.2, 0.5),
+    ];
+    for color in colors {
+        let rgb = mean_rgb(|w| w.upsample(&color), false);
+        assert_close(&rgb, &color, 0.05);
+    }
+}
+
+#[test]
+fn upsampled_reflectances_stay_physical() {
+    let steps = [0.0, 0.25, 0.5, 0.75, 1.0];
+    for &r in &steps {
+        for &g in &steps {
+            for &b in &steps {
+                let color = Color::new(r, g, b);
+                for i in 0..=100 {
+                    let lambda = LAMBDA_MIN + (LAMBDA_MAX - LAMBDA_MIN) * i as f64 / 100.0;
+                    let value = spectrum::rgb_to_spectrum(&color, lambda);
+                    assert!((0.0..=1.02).contains(&value), "{:?} at {} nm gives {}", color, lambda, value);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn terminating_secondary_wavelengths_keeps_the_mean() {
+    let color = Color::new(0.7, 0.4, 0.2);
+    let all = mean_rgb(|w| w.upsample(&color), false);
+    let hero_only = mean_rgb(|w| w.upsample(&color), true);
+    assert_close(&all, &hero_only, 0.01);
+}
+
+#[test]
+fn visible_wavelength_pdf_matches_its_sampling() {
+    // The density integrates to one over the sampled range.
+    let steps = 47_000;
+    let step = (LAMBDA_MAX - LAMBDA_MIN) / steps as f64;
+    let integral: f64 =
+        (0..steps).map(|s| spectrum::visible_wavelength_pdf(LAMBDA_MIN + (s as f64 + 0.5) * step) * step).sum();
+    assert!((integral - 1.0).abs() < 1e-3, "pdf integrates to {}", integral);
+
+    // And the sampling routine inverts its CDF: the fraction of samples below a wavelength is the
+    // integral of the pdf up to it.
+    let mut cdf = 0.0;
+    let mut next = 0;
+    for u in 1..10 {
+        let u = u as f64 / 10.0;
+        let lambda = spectrum::sample_visible_wavelength(u);
+        assert!((LAMBDA_MIN..=LAMBDA_MAX).contains(&lambda));
+        while LAMBDA_MIN + (next as f64 + 0.5) * step < lambda {
+            cdf += spectrum::visible_wavelength_pdf(LAMBDA_MIN + (next as f64 + 0.5) * step) * step;
+            next += 1;
+        }
+        assert!((cdf - u).abs() < 2e-3, "cdf at {} nm is {}, expected {}", lambda, cdf, u);
+    }
+}
+
+#[test]
+fn hero_wavelengths_are_evenly_spread() {
+    let wavelengths = SampledWavelengths::sample(0.3);
+    assert_eq!(wavelengths.hero(), wavelengths.lambda()[0]);
+    assert_eq!(wavelengths.lambda().len(), WAVELENGTHS);
+    let mut sorted = *wavelengths.lambda();
+    sorted.sort_by(f64::total_cmp);
+    assert!(sorted.windows(2).all(|w| w[0] < w[1]));
+    assert!(!wavelengths.secondary_terminated());
+}
+
+#[test]
+fn glasses_have_normal_dispersion() {
+    let glasses = [Dispersion::BK7, Dispersion::FUSED_SILICA, Dispersion::DENSE_FLINT, Dispersion::DIAMOND];
+    for glass in glasses {
+        let indices: Vec<f64> = (0..=10).map(|i| glass.ior(LAMBDA_MIN + 47.0 * i as f64)).collect();
+        assert!(indices.windows(2).all(|w| w[0] > w[1]), "{:?} gives {:?}", glass, indices);
+    }
+    // Indices quoted at the sodium D line.
+    let quoted = [
+        (Dispersion::BK7, 1.5168),
+        (Dispersion::FUSED_SILICA, 1.4585),
+        (Dispersion::DENSE_FLINT, 1.7847),
+        (Dispersion::DIAMOND, 2.4175),
+    ];
+    for (glass, n) in quoted {
+        let ior = glass.ior(Dispersion::SODIUM_D);
+        assert!((ior - n).abs() < 2e-3, "{:?} has index {} at the D line, expected {}", glass, ior, n);
+    }
+}
+
+#[test]
+fn cauchy_follows_its_formula() {
+    let glass = Dispersion::Cauchy { a: 1.5, b: 0.004 };
+    assert!((glass.ior(500.0) - (1.5 + 0.004 / 0.25)).abs() < 1e-12);
+    assert!((glass.ior(400.0) - (1.5 + 0.004 / 0.16)).abs() < 1e-12);
+}
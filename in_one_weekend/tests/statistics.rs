@@ -0,0 +1,644 @@
+// Statistical tests of the sampling routines and materials.
+//
+// Sampled directions are binned by (cos theta, phi) around a reference axis and compared with the
+// counts their pdf predicts using Pearson's chi-square test; a material passes when its sampled
+// directions match its own `pdf`, `scatter` agrees with `eval / pdf`, and it doesn't create energy
+// (the white furnace test). Everything is seeded, so the tests give the same result on every run.
+
+use std::sync::Arc;
+
+use in_one_weekend::interval::Interval;
+use in_one_weekend::microfacet::Ggx;
+use in_one_weekend::onb::Onb;
+use in_one_weekend::rtweekend::PI;
+use in_one_weekend::sampling;
+use in_one_weekend::texture;
+use in_one_weekend::{
+    CheckerTexture, Color, ComplexIor, Conductor, Dielectric, HitRecord, Hittable, Lambertian, Material, Metal, Point3,
+    Principled, Ray, RoughDielectric, Sampler, SamplerKind, Sphere, Subsurface, ThinFilm, Vec3,
+};
+
+// Probability of rejecting a correct distribution.
+const SIGNIFICANCE: f64 = 1e-4;
+
+// Samples per distribution tested.
+const SAMPLES: usize = 200_000;
+
+const THETA_BINS: usize = 16;
+const PHI_BINS: usize = 32;
+
+// Samples the `index`-th value of a seeded sequence, one pixel sample per value.
+fn sampler(seed: u64) -> Box<dyn Sampler> {
+    SamplerKind::Independent.create(1, seed)
+}
+
+fn next_2d(sampler: &mut dyn Sampler, index: usize) -> (f64, f64) {
+    sampler.start_pixel_sample(0, 0, index as u32);
+    sampler.get_2d()
+}
+
+// The `index`-th value of `f`, drawing as many numbers as it needs.
+fn next_with<T>(sampler: &mut dyn Sampler, index: usize, f: impl FnOnce(&mut dyn FnMut() -> f64) -> T) -> T {
+    sampler.start_pixel_sample(0, 0, index as u32);
+    f(&mut || sampler.get_1d())
+}
+
+// Upper tail probability of the chi-square distribution, Q(dof / 2, x / 2) with the regularised
+// incomplete gamma function (series and continued fraction as in Numerical Recipes).
+fn chi_square_p_value(x: f64, dof: usize) -> f64 {
+    let a = dof as f64 / 2.0;
+    let x = x / 2.0;
+    if x <= 0.0 {
+        return 1.0;
+    }
+    let ln_prefix = a * x.ln() - x - ln_gamma(a);
+    if x < a + 1.0 {
+        let (mut term, mut sum, mut n) = (1.0 / a, 1.0 / a, a);
+        for _ in 0..1000 {
+            n += 1.0;
+            term *= x / n;
+            sum += term;
+            if term.abs() < sum.abs() * 1e-15 {
+                break;
+            }
+        }
+        1.0 - sum * ln_prefix.exp()
+    } else {
+        let tiny = 1e-300;
+        let mut b = x + 1.0 - a;
+        let mut c = 1.0 / tiny;
+        let mut d = 1.0 / b;
+        let mut h = d;
+        for i in 1..1000 {
+            let an = -(i as f64) * (i as f64 - a);
+            b += 2.0;
+            d = an * d + b;
+            if d.abs() < tiny {
+                d = tiny;
+            }
+            c = b + an / c;
+            if c.abs() < tiny {
+                c = tiny;
+            }
+            d = 1.0 / d;
+            let delta = d * c;
+            h *= delta;
+            if (delta - 1.0).abs() < 1e-15 {
+                break;
+            }
+        }
+        ln_prefix.exp() * h
+    }
+}
+
+// Lanczos approximation, good to ~15 digits for positive arguments.
+fn ln_gamma(x: f64) -> f64 {
+    const G: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+    let x = x - 1.0;
+    let t = x + 7.5;
+    let sum = G[0] + (1..9).map(|i| G[i] / (x + i as f64)).sum::<f64>();
+    0.5 * (2.0 * PI).ln() + (x + 0.5) * t.ln() - t + sum.ln()
+}
+
+// Pearson's test of observed against expected counts. Bins expecting fewer than 5 samples are
+// pooled, as the chi-square approximation is poor for them.
+fn chi_square_test(name: &str, observed: &[f64], expected: &[f64]) {
+    let mut pooled = (0.0, 0.0);
+    let mut chi_square = 0.0;
+    let mut dof = 0;
+    for (&o, &e) in observed.iter().zip(expected) {
+        if e < 5.0 {
+            pooled.0 += o;
+            pooled.1 += e;
+        } else {
+            chi_square += (o - e) * (o - e) / e;
+            dof += 1;
+        }
+    }
+    if pooled.1 > 0.0 {
+        chi_square += (pooled.0 - pooled.1) * (pooled.0 - pooled.1) / pooled.1;
+        dof += 1;
+    }
+    assert!(dof > 1, "{}: too few populated bins for a chi-square test", name);
+
+    let p = chi_square_p_value(chi_square, dof - 1);
+    assert!(
+        p >= SIGNIFICANCE,
+        "{}: chi-square {:.1} with {} degrees of freedom, p = {:.2e} < {}",
+        name,
+        chi_square,
+        dof - 1,
+        p,
+        SIGNIFICANCE
+    );
+}
+
+// Bins of (cos theta, phi) around `frame.w()`, all of the same solid angle.
+fn direction_bin(frame: &Onb, direction: &Vec3) -> usize {
+    let d = Vec3::unit_vector(*direction);
+    let (x, y, z) = (Vec3::dot(&d, &frame.u()), Vec3::dot(&d, &frame.v()), Vec3::dot(&d, &frame.w()));
+    let theta_bin = (((z + 1.0) / 2.0 * THETA_BINS as f64) as usize).min(THETA_BINS - 1);
+    let phi = y.atan2(x).rem_euclid(2.0 * PI);
+    let phi_bin = ((phi / (2.0 * PI) * PHI_BINS as f64) as usize).min(PHI_BINS - 1);
+    theta_bin * PHI_BINS + phi_bin
+}
+
+// Expected counts of `samples` directions drawn from `pdf` (solid angle measure) in every
+// direction_bin, by midpoint integration over each bin.
+fn expected_direction_counts(frame: &Onb, samples: usize, pdf: impl Fn(&Vec3) -> f64) -> Vec<f64> {
+    const STEPS: usize = 8;
+    let d_cos = 2.0 / THETA_BINS as f64 / STEPS as f64;
+    let d_phi = 2.0 * PI / PHI_BINS as f64 / STEPS as f64;
+
+    let mut expected = vec![0.0; THETA_BINS * PHI_BINS];
+    for t in 0..THETA_BINS * STEPS {
+        let z = -1.0 + (t as f64 + 0.5) * d_cos;
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        for p in 0..PHI_BINS * STEPS {
+            let phi = (p as f64 + 0.5) * d_phi;
+            let direction = r * phi.cos() * frame.u() + r * phi.sin() * frame.v() + z * frame.w();
+            let bin = (t / STEPS) * PHI_BINS + p / STEPS;
+            expected[bin] += pdf(&direction) * d_cos * d_phi * samples as f64;
+        }
+    }
+    expected
+}
+
+fn check_directions(name: &str, frame: &Onb, directions: &[Vec3], pdf: impl Fn(&Vec3) -> f64) {
+    let mut observed = vec![0.0; THETA_BINS * PHI_BINS];
+    for direction in directions {
+        observed[direction_bin(frame, direction)] += 1.0;
+    }
+    chi_square_test(name, &observed, &expected_direction_counts(frame, SAMPLES, pdf));
+}
+
+// Mean and standard error of the mean.
+fn mean_and_error(values: &[f64]) -> (f64, f64) {
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let variance = values.iter().map(|v| (v - mean) * (v - mean)).sum::<f64>() / (n - 1.0);
+    (mean, (variance / n).sqrt())
+}
+
+fn z_axis() -> Onb {
+    Onb::new(&Vec3::new(0.0, 0.0, 1.0))
+}
+
+// A tilted surface at the origin, hit from outside by a ray coming in at about 50 degrees.
+// Its tangent is deliberately not perpendicular to the normal, materials must project it.
+fn surface_hit() -> (Ray, HitRecord) {
+    let normal = Vec3::unit_vector(Vec3::new(0.3, 0.8, -0.5));
+    let frame = Onb::new(&normal);
+    let incoming = -Vec3::unit_vector(0.65 * normal + 0.75 * frame.u());
+    let ray = Ray::new(Point3::default() - 2.0 * incoming, incoming);
+    let rec = HitRecord {
+        p: Point3::default(),
+        normal,
+        t: 2.0,
+        tangent: Vec3::unit_vector(frame.v() + 0.3 * normal),
+        front_face: true,
+        ..HitRecord::default()
+    };
+    (ray, rec)
+}
+
+#[test]
+fn chi_square_p_values_match_tables() {
+    // Critical values for p = 0.05 and p = 0.001 from standard tables.
+    assert!((chi_square_p_value(3.841, 1) - 0.05).abs() < 1e-3);
+    assert!((chi_square_p_value(18.307, 10) - 0.05).abs() < 1e-3);
+    assert!((chi_square_p_value(124.342, 100) - 0.05).abs() < 1e-3);
+    assert!((chi_square_p_value(29.588, 10) - 0.001).abs() < 1e-4);
+}
+
+#[test]
+fn random_unit_vector_is_uniform() {
+    let mut sampler = sampler(10);
+    let directions: Vec<Vec3> =
+        (0..SAMPLES).map(|k| next_with(sampler.as_mut(), k, |random| Vec3::random_unit_vector_from(random))).collect();
+    for d in &directions {
+        assert!((d.length() - 1.0).abs() < 1e-9);
+    }
+    check_directions("random_unit_vector", &z_axis(), &directions, |_| 1.0 / (4.0 * PI));
+}
+
+#[test]
+fn random_on_hemisphere_is_uniform_around_the_normal() {
+    let normal = Vec3::unit_vector(Vec3::new(-1.0, 2.0, 0.5));
+    let mut sampler = sampler(11);
+    let directions: Vec<Vec3> = (0..SAMPLES)
+        .map(|k| next_with(sampler.as_mut(), k, |random| Vec3::random_on_hemisphere_from(&normal, random)))
+        .collect();
+    check_directions("random_on_hemisphere", &Onb::new(&normal), &directions, |d| {
+        if Vec3::dot(d, &normal) > 0.0 { 1.0 / (2.0 * PI) } else { 0.0 }
+    });
+}
+
+#[test]
+fn uniform_sphere_matches_its_pdf() {
+    let mut sampler = sampler(1);
+    let directions: Vec<Vec3> = (0..SAMPLES).map(|k| sampling::uniform_sphere(next_2d(sampler.as_mut(), k))).collect();
+    check_directions("uniform_sphere", &z_axis(), &directions, |_| 1.0 / (4.0 * PI));
+}
+
+#[test]
+fn cosine_hemisphere_matches_its_pdf() {
+    let mut sampler = sampler(2);
+    let directions: Vec<Vec3> =
+        (0..SAMPLES).map(|k| sampling::cosine_hemisphere(next_2d(sampler.as_mut(), k))).collect();
+    check_directions("cosine_hemisphere", &z_axis(), &directions, |d| d.z().max(0.0) / PI);
+}
+
+#[test]
+fn uniform_cone_matches_its_pdf() {
+    let cos_max = 0.3_f64;
+    let mut sampler = sampler(3);
+    let directions: Vec<Vec3> =
+        (0..SAMPLES).map(|k| sampling::uniform_cone(next_2d(sampler.as_mut(), k), cos_max)).collect();
+    check_directions("uniform_cone", &z_axis(), &directions, |d| {
+        if Vec3::unit_vector(*d).z() >= cos_max { 1.0 / (2.0 * PI * (1.0 - cos_max)) } else { 0.0 }
+    });
+}
+
+#[test]
+fn concentric_disk_is_uniform() {
+    // A uniform point on the disk has r^2 and phi uniform and independent.
+    const BINS: usize = 16;
+    let mut sampler = sampler(4);
+    let mut observed = vec![0.0; BINS * BINS];
+    for k in 0..SAMPLES {
+        let p = sampling::concentric_disk(next_2d(sampler.as_mut(), k));
+        let r2 = p.x() * p.x() + p.y() * p.y();
+        assert!(r2 <= 1.0 + 1e-12 && p.z() == 0.0);
+        let phi = p.y().atan2(p.x()).rem_euclid(2.0 * PI);
+        let (a, b) = ((r2 * BINS as f64) as usize, (phi / (2.0 * PI) * BINS as f64) as usize);
+        observed[a.min(BINS - 1) * BINS + b.min(BINS - 1)] += 1.0;
+    }
+    let expected = vec![SAMPLES as f64 / (BINS * BINS) as f64; BINS * BINS];
+    chi_square_test("concentric_disk", &observed, &expected);
+}
+
+#[test]
+fn samplers_are_uniform() {
+    // Low discrepancy samplers are more even than random points, which only ever lowers the statistic.
+    const BINS: usize = 16;
+    const SPP: u32 = 16;
+    for kind in SamplerKind::ALL {
+        let mut sampler = kind.create(SPP, 5);
+        let mut observed = vec![0.0; BINS * BINS];
+        let mut samples = 0;
+        for j in 0..32 {
+            for i in 0..32 {
+                for s in 0..SPP {
+                    sampler.start_pixel_sample(i, j, s);
+                    // The pixel offset and a later dimension, both have to be uniform.
+                    for _ in 0..2 {
+                        let (u, v) = sampler.get_2d();
+                        assert!((0.0..1.0).contains(&u) && (0.0..1.0).contains(&v), "{}: ({}, {})", kind, u, v);
+                        observed[(u * BINS as f64) as usize * BINS + (v * BINS as f64) as usize] += 1.0;
+                        samples += 1;
+                    }
+                }
+            }
+        }
+        let expected = vec![samples as f64 / (BINS * BINS) as f64; BINS * BINS];
+        chi_square_test(kind.name(), &observed, &expected);
+    }
+}
+
+// Checks that `material` samples directions with the density its `pdf` reports, that the weight
+// `scatter` returns is eval / pdf, and that the pdf integrates to at most one.
+fn check_bsdf_sampling(name: &str, material: &dyn Material) {
+    let (ray, rec) = surface_hit();
+    check_bsdf_sampling_at(name, material, &ray, &rec);
+}
+
+fn check_bsdf_sampling_at(name: &str, material: &dyn Material, ray: &Ray, rec: &HitRecord) {
+    let mut sampler = sampler(6);
+    let mut directions = Vec::new();
+    for k in 0..SAMPLES {
+        sampler.start_pixel_sample(0, 0, k as u32);
+        let Some(scattered) = material.scatter(ray, rec, sampler.as_mut()) else {
+            continue;
+        };
+        let direction = scattered.ray.direction();
+        let pdf = material.pdf(ray, rec, &direction);
+        assert!(
+            (scattered.pdf - pdf).abs() <= 1e-9 * pdf.max(1.0),
+            "{}: scatter reports pdf {} but pdf() gives {}",
+            name,
+            scattered.pdf,
+            pdf
+        );
+        if pdf > 0.0 {
+            let weight = material.eval(ray, rec, &direction) / pdf;
+            assert!(
+                (weight - scattered.attenuation).length() <= 1e-6 * weight.length().max(1.0),
+                "{}: scatter weight {} differs from eval / pdf {}",
+                name,
+                scattered.attenuation,
+                weight
+            );
+        }
+        directions.push(direction);
+    }
+
+    let frame = Onb::new(&rec.normal);
+    let expected = expected_direction_counts(&frame, 1, |d| material.pdf(ray, rec, d));
+    let total: f64 = expected.iter().sum();
+    assert!(total <= 1.0 + 1e-2, "{}: pdf integrates to {}", name, total);
+
+    check_directions(name, &frame, &directions, |d| material.pdf(ray, rec, d));
+}
+
+// Mean scattering weight per channel: the fraction of light the surface reflects or transmits
+// towards the incoming direction, at most one for a material that doesn't emit.
+fn directional_albedo(material: &dyn Material) -> [(f64, f64); 3] {
+    let (ray, rec) = surface_hit();
+    let mut sampler = sampler(7);
+    let mut weights = [Vec::new(), Vec::new(), Vec::new()];
+    for k in 0..SAMPLES {
+        sampler.start_pixel_sample(0, 0, k as u32);
+        let attenuation = material.scatter(&ray, &rec, sampler.as_mut()).map(|s| s.attenuation).unwrap_or_default();
+        for c in 0..3 {
+            weights[c].push(attenuation[c]);
+        }
+    }
+    weights.map(|w| mean_and_error(&w))
+}
+
+// The same albedo estimated from `eval` with uniformly sampled directions, independent of `scatter`.
+fn eval_albedo(material: &dyn Material) -> [(f64, f64); 3] {
+    let (ray, rec) = surface_hit();
+    let mut sampler = sampler(8);
+    let mut values = [Vec::new(), Vec::new(), Vec::new()];
+    for k in 0..SAMPLES {
+        let direction = sampling::uniform_sphere(next_2d(sampler.as_mut(), k));
+        let f = material.eval(&ray, &rec, &direction) * (4.0 * PI);
+        for c in 0..3 {
+            values[c].push(f[c]);
+        }
+    }
+    values.map(|v| mean_and_error(&v))
+}
+
+// Checks that the albedos from `scatter` and from `eval` agree within five standard errors.
+fn check_albedo_agreement(name: &str, material: &dyn Material) {
+    let sampled = directional_albedo(material);
+    let evaluated = eval_albedo(material);
+    for c in 0..3 {
+        let ((a, ea), (b, eb)) = (sampled[c], evaluated[c]);
+        assert!((a - b).abs() <= 5.0 * (ea * ea + eb * eb).sqrt(), "{}, channel {}: scatter {} vs eval {}", name, c, a, b);
+    }
+}
+
+fn assert_conserves_energy(name: &str, albedo: &[(f64, f64); 3]) {
+    for &(mean, error) in albedo {
+        assert!(mean <= 1.0 + 5.0 * error + 1e-9, "{}: reflects {} ± {} of the light", name, mean, error);
+    }
+}
+
+#[test]
+fn lambertian_samples_match_its_pdf() {
+    check_bsdf_sampling("lambertian", &Lambertian::new(Color::new(0.8, 0.5, 0.2)));
+}
+
+#[test]
+fn white_furnace() {
+    let white = Color::new(1.0, 1.0, 1.0);
+    let materials: Vec<(&str, Arc<dyn Material>)> = vec![
+        ("lambertian", Arc::new(Lambertian::new(white))),
+        ("metal", Arc::new(Metal::new(white, 0.0))),
+        ("fuzzy metal", Arc::new(Metal::new(white, 0.5))),
+        ("glass", Arc::new(Dielectric::new(1.5))),
+        ("glass bubble", Arc::new(Dielectric::new(1.0 / 1.5))),
+        ("coated glass", Arc::new(Dielectric::new(1.5).film(soap_film()))),
+        ("soap bubble", Arc::new(Dielectric::new(1.0).film(soap_film()))),
+    ];
+    for (name, material) in &materials {
+        assert_conserves_energy(name, &directional_albedo(material.as_ref()));
+    }
+
+    // A clear film splits the light between reflection and transmission without losing any, but its
+    // weights are colored, so that only holds on average.
+    for name in ["coated glass", "soap bubble"] {
+        let material = &materials.iter().find(|(n, _)| *n == name).unwrap().1;
+        for (mean, error) in directional_albedo(material.as_ref()) {
+            assert!((mean - 1.0).abs() <= 5.0 * error + 1e-9, "{}: reflects {} ± {} of the light", name, mean, error);
+        }
+    }
+
+    // Lossless materials don't absorb anything either.
+    for name in ["lambertian", "metal", "glass", "glass bubble"] {
+        let material = &materials.iter().find(|(n, _)| *n == name).unwrap().1;
+        for (mean, _) in directional_albedo(material.as_ref()) {
+            assert!((mean - 1.0).abs() < 1e-9, "{}: a white surface reflects {} of the light", name, mean);
+        }
+    }
+}
+
+#[test]
+fn lambertian_albedo_agrees_with_eval() {
+    let material = Lambertian::new(Color::new(0.8, 0.5, 0.2));
+    check_albedo_agreement("lambertian", &material);
+    assert_conserves_energy("lambertian (eval)", &eval_albedo(&material));
+}
+
+fn principled_variants() -> Vec<(&'static str, Principled)> {
+    let base = || texture::solid(Color::new(0.8, 0.5, 0.3));
+    vec![
+        ("principled plastic", Principled::new(base())),
+        ("principled metal", Principled::new(base()).metallic(texture::constant(1.0)).roughness(texture::constant(0.6))),
+        (
+            "principled cloth",
+            Principled::new(base()).roughness(texture::constant(0.9)).sheen(texture::constant(1.0)),
+        ),
+        (
+            "principled coated",
+            Principled::new(base()).clearcoat(texture::constant(1.0)).clearcoat_gloss(texture::constant(0.3)),
+        ),
+        (
+            "principled glass",
+            Principled::new(base()).transmission(texture::constant(1.0)).roughness(texture::constant(0.6)),
+        ),
+        (
+            // Water and diamond, the hit point decides which.
+            "principled textured ior",
+            Principled::new(base())
+                .transmission(texture::constant(1.0))
+                .roughness(texture::constant(0.6))
+                .ior(Arc::new(CheckerTexture::new(0.5, texture::constant(1.33), texture::constant(2.4)))),
+        ),
+        (
+            "principled mix",
+            Principled::new(base())
+                .metallic(texture::constant(0.3))
+                .transmission(texture::constant(0.5))
+                .clearcoat(texture::constant(0.5))
+                .clearcoat_gloss(texture::constant(0.5))
+                .sheen(texture::constant(0.5))
+                .roughness(texture::constant(0.7)),
+        ),
+    ]
+}
+
+#[test]
+fn principled_samples_match_its_pdf() {
+    for (name, material) in principled_variants() {
+        check_bsdf_sampling(name, &material);
+    }
+}
+
+#[test]
+fn principled_albedo_agrees_with_eval() {
+    for (name, material) in principled_variants() {
+        check_albedo_agreement(name, &material);
+    }
+}
+
+#[test]
+fn principled_white_furnace() {
+    // Rough conductors and rough glass lose some energy to the single scattering approximation, never gain it.
+    let white = || texture::solid(Color::new(1.0, 1.0, 1.0));
+    let materials = [
+        ("white metal", Principled::new(white()).metallic(texture::constant(1.0)).roughness(texture::constant(0.4))),
+        ("white glass", Principled::new(white()).transmission(texture::constant(1.0)).roughness(texture::constant(0.4))),
+    ];
+    for (name, material) in &materials {
+        assert_conserves_energy(name, &directional_albedo(material));
+    }
+}
+
+#[test]
+fn ggx_normals_match_their_pdfs() {
+    let frame = z_axis();
+    let w = Vec3::unit_vector(Vec3::new(0.6, -0.3, 0.4));
+    for (name, ggx) in [("isotropic", Ggx::new(0.5)), ("anisotropic", Ggx::anisotropic(0.3, 0.9))] {
+        let mut sampler = sampler(9);
+        let normals: Vec<Vec3> = (0..SAMPLES).map(|k| ggx.sample_h(next_2d(sampler.as_mut(), k))).collect();
+        check_directions(&format!("{} ggx normals", name), &frame, &normals, |h| ggx.pdf_h(h));
+
+        let visible: Vec<Vec3> = (0..SAMPLES).map(|k| ggx.sample_visible(&w, next_2d(sampler.as_mut(), k))).collect();
+        check_directions(&format!("{} ggx visible normals", name), &frame, &visible, |h| ggx.pdf_visible(&w, h));
+    }
+}
+
+fn microfacet_variants() -> Vec<(&'static str, Arc<dyn Material>)> {
+    vec![
+        ("rough gold", Arc::new(Conductor::new(ComplexIor::GOLD, 0.5, 0.0))),
+        ("brushed copper", Arc::new(Conductor::new(ComplexIor::COPPER, 0.6, 0.8))),
+        ("rough glass", Arc::new(RoughDielectric::new(1.5, 0.5, 0.0))),
+        // Seen from inside, with total internal reflection.
+        ("rough glass bubble", Arc::new(RoughDielectric::new(1.0 / 1.5, 0.5, 0.0))),
+        ("anisotropic rough glass", Arc::new(RoughDielectric::new(1.5, 0.6, 0.7))),
+        ("coated gold", Arc::new(Conductor::new(ComplexIor::GOLD, 0.4, 0.0).film(soap_film()))),
+        ("coated rough glass", Arc::new(RoughDielectric::new(1.5, 0.5, 0.0).film(soap_film()))),
+        ("coated rough glass bubble", Arc::new(RoughDielectric::new(1.0 / 1.5, 0.5, 0.0).film(soap_film()))),
+    ]
+}
+
+// Thick enough to color the reflection.
+fn soap_film() -> ThinFilm {
+    ThinFilm::new(texture::constant(380.0), 1.33)
+}
+
+#[test]
+fn microfacet_samples_match_their_pdfs() {
+    for (name, material) in microfacet_variants() {
+        check_bsdf_sampling(name, material.as_ref());
+    }
+}
+
+#[test]
+fn microfacet_albedo_agrees_with_eval() {
+    for (name, material) in microfacet_variants() {
+        check_albedo_agreement(name, material.as_ref());
+    }
+}
+
+#[test]
+fn microfacet_white_furnace() {
+    // A conductor with a huge extinction coefficient is a perfect mirror at every angle.
+    let white = ComplexIor::new(Color::new(1.0, 1.0, 1.0), Color::new(1e4, 1e4, 1e4));
+    let materials: Vec<(&str, Arc<dyn Material>)> = vec![
+        ("white conductor", Arc::new(Conductor::new(white, 0.4, 0.0))),
+        ("white anisotropic conductor", Arc::new(Conductor::new(white, 0.4, 1.0))),
+        ("rough glass", Arc::new(RoughDielectric::new(1.5, 0.4, 0.0))),
+        ("rough glass bubble", Arc::new(RoughDielectric::new(1.0 / 1.5, 0.4, 0.0))),
+        ("coated rough glass", Arc::new(RoughDielectric::new(1.5, 0.4, 0.0).film(soap_film()))),
+    ];
+    for (name, material) in &materials {
+        assert_conserves_energy(name, &directional_albedo(material.as_ref()));
+    }
+
+    // Visible normal sampling makes a smooth white conductor's weights exactly one.
+    let mirror = Conductor::new(white, 0.0, 0.0);
+    for (mean, _) in directional_albedo(&mirror) {
+        assert!((mean - 1.0).abs() < 1e-3, "smooth white conductor reflects {} of the light", mean);
+    }
+}
+
+// A subsurface material on a sphere of `radius` below the origin, hit from above.
+fn subsurface_hit(albedo: f64, mean_free_path: Color, refraction_index: f64, radius: f64) -> (Subsurface, Ray, HitRecord) {
+    let boundary = Arc::new(Sphere::boundary(Point3::new(0.0, -radius, 0.0), radius));
+    let material = Subsurface::new(Color::new(albedo, albedo, albedo), mean_free_path, refraction_index, boundary.clone());
+    let ray = Ray::new(Point3::new(0.3, 1.0, 0.2), Vec3::new(-0.3, -1.0, -0.2));
+    let mut rec = HitRecord::default();
+    assert!(boundary.hit(&ray, Interval::new(0.001, f64::INFINITY), &mut rec));
+    (material, ray, rec)
+}
+
+// Mean and standard error of the walk weights of light entering at `rec`.
+fn walk_albedo(material: &Subsurface, ray: &Ray, rec: &HitRecord) -> [(f64, f64); 3] {
+    let mut sampler = sampler(9);
+    let mut weights = [Vec::new(), Vec::new(), Vec::new()];
+    for k in 0..SAMPLES / 2 {
+        sampler.start_pixel_sample(0, 0, k as u32);
+        let attenuation = material.scatter(ray, rec, sampler.as_mut()).map(|s| s.attenuation).unwrap_or_default();
+        for c in 0..3 {
+            weights[c].push(attenuation[c]);
+        }
+    }
+    weights.map(|w| mean_and_error(&w))
+}
+
+#[test]
+fn subsurface_walks_conserve_energy() {
+    // Nothing is absorbed at albedo one, whatever the free paths of the channels.
+    let (material, ray, rec) = subsurface_hit(1.0, Color::new(0.2, 0.4, 1.0), 1.5, 1.0);
+    for (c, (mean, error)) in walk_albedo(&material, &ray, &rec).into_iter().enumerate() {
+        assert!((mean - 1.0).abs() <= 5.0 * error, "channel {}: walks carry {} ± {} of the light", c, mean, error);
+    }
+}
+
+#[test]
+fn subsurface_albedo_is_that_of_a_thick_slab() {
+    // A large sphere is locally a half space, which reflects the multiple scattering albedo.
+    for albedo in [0.2, 0.5, 0.8] {
+        let (material, ray, rec) = subsurface_hit(albedo, Color::new(0.1, 0.1, 0.1), 1.0, 100.0);
+        let (mean, error) = walk_albedo(&material, &ray, &rec)[0];
+        assert!((mean - albedo).abs() <= 0.02 + 5.0 * error, "albedo {} reflects {} ± {}", albedo, mean, error);
+    }
+}
+
+#[test]
+fn subsurface_exit_is_diffuse() {
+    // Where a walk reaches the boundary from inside, light leaves with a cosine distribution.
+    let (material, _, rec) = subsurface_hit(0.8, Color::new(0.1, 0.2, 0.3), 1.4, 1.0);
+    let center = Point3::new(0.0, -1.0, 0.0);
+    let exit = Ray::new(center, rec.p - center);
+    let mut inside = HitRecord::default();
+    assert!(Sphere::boundary(center, 1.0).hit(&exit, Interval::new(0.001, f64::INFINITY), &mut inside));
+    assert!(!inside.front_face);
+    check_bsdf_sampling_at("subsurface exit", &material, &exit, &inside);
+}
@@ -0,0 +1,96 @@
+// Tests of the thin film reflectance against cases with known answers.
+
+use in_one_weekend::microfacet;
+use in_one_weekend::spectrum;
+use in_one_weekend::texture;
+use in_one_weekend::{Color, ComplexIor, HitRecord, ThinFilm};
+
+fn film(thickness: f64, ior: f64) -> ThinFilm {
+    ThinFilm::new(texture::constant(thickness), ior)
+}
+
+const COSINES: [f64; 6] = [1.0, 0.9, 0.7, 0.5, 0.2, 0.05];
+const WAVELENGTHS: [f64; 4] = [400.0, 480.0, 550.0, 700.0];
+
+#[test]
+fn vanishing_films_leave_the_base() {
+    // A film of no thickness, or one with the index of the air around it, changes nothing.
+    for (thickness, ior) in [(0.0, 1.33), (350.0, 1.0)] {
+        let film = film(thickness, ior);
+        for cos in COSINES {
+            for lambda in WAVELENGTHS {
+                let glass = film.reflectance(cos, thickness, lambda, 1.0, (1.5, 0.0));
+                let expected = microfacet::fresnel_dielectric(cos, 1.5);
+                assert!((glass - expected).abs() < 1e-9, "glass reflects {} instead of {}", glass, expected);
+
+                let gold = ComplexIor::GOLD;
+                let coated = film.reflectance(cos, thickness, lambda, 1.0, (gold.eta.x(), gold.k.x()));
+                let expected = microfacet::fresnel_conductor(cos, gold.eta, gold.k).x();
+                assert!((coated - expected).abs() < 1e-9, "gold reflects {} instead of {}", coated, expected);
+            }
+        }
+    }
+}
+
+#[test]
+fn quarter_wave_coatings_cancel_reflection() {
+    // An anti-reflection coating: index sqrt(n) and a quarter wavelength thick in the film.
+    let (n, lambda) = (1.5f64, 550.0);
+    let ior = n.sqrt();
+    let thickness = lambda / (4.0 * ior);
+    let coating = film(thickness, ior);
+    assert!(coating.reflectance(1.0, thickness, lambda, 1.0, (n, 0.0)) < 1e-9);
+    // It is tuned to one wavelength, away from it some light is reflected again.
+    assert!(coating.reflectance(1.0, thickness, 400.0, 1.0, (n, 0.0)) > 1e-3);
+
+    // A half wave layer reflects like the bare glass.
+    let thickness = lambda / (2.0 * ior);
+    let r = coating.reflectance(1.0, thickness, lambda, 1.0, (n, 0.0));
+    assert!((r - microfacet::fresnel_dielectric(1.0, n)).abs() < 1e-9);
+}
+
+#[test]
+fn clear_films_are_lossless_both_ways() {
+    // A soap film in air never reflects everything.
+    let soap = film(300.0, 1.33);
+    for cos in COSINES {
+        for lambda in WAVELENGTHS {
+            let r = soap.reflectance(cos, 300.0, lambda, 1.0, (1.0, 0.0));
+            assert!((0.0..1.0).contains(&r), "soap film reflects {}", r);
+        }
+    }
+    // A coated glass reflects the same from outside and, at the refracted angle, from inside.
+    let coated = film(200.0, 1.38);
+    for cos in COSINES {
+        let cos_inside = (1.0 - (1.0 - cos * cos) / (1.5 * 1.5)).sqrt();
+        for lambda in WAVELENGTHS {
+            let outside = coated.reflectance(cos, 200.0, lambda, 1.0, (1.5, 0.0));
+            let inside = coated.reflectance(cos_inside, 200.0, lambda, 1.5, (1.0, 0.0));
+            assert!((outside - inside).abs() < 1e-9, "{} from outside, {} from inside", outside, inside);
+        }
+    }
+    // And still reflects everything past the critical angle.
+    for lambda in WAVELENGTHS {
+        let r = coated.reflectance(0.5, 200.0, lambda, 1.5, (1.0, 0.0));
+        assert!((r - 1.0).abs() < 1e-9, "total internal reflection gives {}", r);
+    }
+}
+
+#[test]
+fn interference_colors_the_reflection() {
+    let rec = HitRecord::default();
+    let soap = film(400.0, 1.33);
+    let rgb = soap.dielectric_reflectance(&rec, 1.0, None, 1.0);
+    assert!((rgb.x() - rgb.y()).abs() > 0.01 || (rgb.y() - rgb.z()).abs() > 0.01, "{:?} is grey", rgb);
+
+    // In spectral renders the reflectance is that of the ray's wavelength.
+    let single = soap.dielectric_reflectance(&rec, 1.0, Some(500.0), 1.0);
+    let expected = soap.reflectance(1.0, 400.0, 500.0, 1.0, (1.0, 0.0));
+    assert_eq!(single, Color::new(expected, expected, expected));
+}
+
+#[test]
+fn flat_reflectance_is_white() {
+    let rgb = spectrum::reflectance_to_rgb(|_| 0.5);
+    assert!((rgb - Color::new(0.5, 0.5, 0.5)).length() < 1e-9, "{:?}", rgb);
+}